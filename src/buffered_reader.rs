@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::io::prelude::*;
+use std::io::Cursor;
 use std::net::TcpStream;
 use openssl::symm::{Cipher, Mode, Crypter};
 use tokio::net::tcp::OwnedReadHalf;
@@ -8,13 +9,15 @@ use tokio::io::AsyncReadExt;
 use crate::log;
 
 const PACKET_SIZE: usize = 65535;
+const MAX_STRING_LENGTH: usize = 32767;
 
-/// Provides a contiguous block of data of requested size from a TCP stream
+/// Provides a contiguous block of data of requested size from a TCP stream or an in-memory buffer
 pub struct BufferedReader {
     buffer: [u8; PACKET_SIZE*2],
     encrypted_buffer: [u8; PACKET_SIZE*2],
     stream: Option<TcpStream>,
     reader: Option<OwnedReadHalf>,
+    bytes: Option<Cursor<Vec<u8>>>,
     pos: usize,
     encrypted_pos: usize,
     available: usize,
@@ -37,7 +40,8 @@ impl BufferedReader {
             decrypt_enable: false,
             decrypter: None,
             stream: None,
-            reader: None
+            reader: None,
+            bytes: None,
         }
     }
 
@@ -47,6 +51,8 @@ impl BufferedReader {
                 Ok(n) => Ok(n),
                 Err(err) => Err(Box::new(err))
             }
+        } else if let Some(bytes) = self.bytes.as_mut() {
+            Ok(Read::read(bytes, &mut self.buffer[self.pos+self.available..]).map_err(|e| e.to_string())?)
         } else {
             Ok(self.stream.as_ref().unwrap()
                 .read(&mut self.buffer[self.pos+self.available..]).map_err(|e| e.to_string())?)
@@ -56,13 +62,12 @@ impl BufferedReader {
     async fn read(&mut self) -> Result<usize, Box<dyn Error + Send + Sync>> {
         if self.decrypt_enable {
             let ret = self.try_read().await?;
-            let mut temp = vec![0; ret];
+            let start = self.pos + self.available;
+            // CFB8 keeps the output length equal to the input, so the scratch buffer always fits
             self.decrypter.as_mut().unwrap().update(
-                &self.buffer[self.pos+self.available..self.pos+self.available+ret],
-                &mut temp[..])?;
-            for i in 0..ret {
-                self.buffer[self.pos+self.available+i] = temp[i];
-            }
+                &self.buffer[start..start+ret],
+                &mut self.encrypted_buffer[..ret])?;
+            self.buffer[start..start+ret].copy_from_slice(&self.encrypted_buffer[..ret]);
             Ok(ret)
         } else {
             self.try_read().await
@@ -81,6 +86,14 @@ impl BufferedReader {
         reader
     }
 
+    /// Reads from a fixed buffer, reporting end of stream once it's drained.
+    /// Decryption stays off unless enabled explicitly
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        let mut reader = BufferedReader::new();
+        reader.bytes = Some(Cursor::new(bytes));
+        reader
+    }
+
     pub fn enable_decryption(&mut self){
         self.decrypt_enable = true;
     }
@@ -147,13 +160,17 @@ impl BufferedReader {
         if length == 0 {
             return Ok(String::new());
         }
+        if length > MAX_STRING_LENGTH {
+            return Err(format!("String length {length} exceeds the protocol limit of {MAX_STRING_LENGTH}").into());
+        }
         let data = self.read_bytes(length*2).await?;
         let mut codepoints = vec![0u16; length];
         for i in 0..length {
             let indx = i*2;
             codepoints[i] = to_ushort(&data[indx..indx+2]);
         }
-        std::string::String::from_utf16(codepoints.as_slice()).map_err(|e| e.to_string().into())
+        std::string::String::from_utf16(codepoints.as_slice())
+            .map_err(|_| format!("String of length {length} is not valid UTF-16").into())
     }
 
     pub async fn read_bool(&mut self) -> Result<bool, Box<dyn Error + Send + Sync>> {
@@ -214,3 +231,64 @@ fn to_ushort(data: &[u8]) -> u16 {
     bytes.copy_from_slice(data);
     u16::from_be_bytes(bytes)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The buffers are too big to keep a reader on a test thread's stack
+    fn reader(bytes: Vec<u8>) -> Box<BufferedReader> {
+        Box::new(BufferedReader::from_bytes(bytes))
+    }
+
+    #[tokio::test]
+    async fn zero_length_string() {
+        let mut reader = reader(vec![0, 0, 0x2A]);
+        assert_eq!(reader.read_string().await.unwrap(), "");
+        assert_eq!(reader.read_ubyte().await.unwrap(), 0x2A);
+    }
+
+    #[tokio::test]
+    async fn lone_surrogate_is_an_error() {
+        let mut reader = reader(vec![0, 2, 0x00, b'a', 0xD8, 0x00]);
+        let error = reader.read_string().await.unwrap_err();
+        assert_eq!(error.to_string(), "String of length 2 is not valid UTF-16");
+    }
+
+    #[tokio::test]
+    async fn string_over_the_limit_is_an_error() {
+        let mut reader = reader(vec![0x80, 0x00]);
+        assert!(reader.read_string().await.is_err());
+    }
+
+    // Minecraft uses the shared secret as the IV too
+    #[tokio::test]
+    async fn decryption_matches_openssl() {
+        let key = *b"0123456789abcdef";
+        let plain: Vec<u8> = (0..300_000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+        let mut encrypter = Crypter::new(Cipher::aes_128_cfb8(), Mode::Encrypt, &key, Some(&key)).unwrap();
+        let mut encrypted = vec![0; plain.len()];
+        encrypter.update(&plain, &mut encrypted).unwrap();
+
+        let mut reader = reader(encrypted);
+        reader.set_decryption_key(&key).unwrap();
+        reader.enable_decryption();
+        // Odd sized reads so the buffer gets compacted and refilled mid chunk
+        for chunk in plain.chunks(9973) {
+            assert_eq!(reader.read_bytes(chunk.len()).await.unwrap(), chunk);
+        }
+        assert!(reader.read_ubyte().await.is_err());
+    }
+
+    // From openssl enc -aes-128-cfb8 with key and IV both 000102..0f
+    #[tokio::test]
+    async fn decryption_test_vector() {
+        let key: Vec<u8> = (0..16).collect();
+        let mut reader = reader(vec![
+            0x47, 0xA8, 0x0C, 0x00, 0xB2, 0x5E, 0xA2, 0xE7,
+            0x3E, 0xE5, 0x18, 0xE9, 0x5A, 0x75, 0x0F, 0x7C]);
+        reader.set_decryption_key(&key).unwrap();
+        reader.enable_decryption();
+        assert_eq!(reader.read_bytes(16).await.unwrap(), b"Minecraft 1.5.2!");
+    }
+}
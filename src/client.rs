@@ -0,0 +1,213 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+use tokio::time::{sleep_until, Duration, Instant};
+
+use crate::config::Config;
+use crate::game::{GlobalContext, Player};
+use crate::net::{self, Connection, ConnectPolicy, Observers};
+use crate::packets::Packet;
+use crate::ui::UiState;
+use crate::log;
+
+// Ticks run back to back to catch up at most this far, anything older is dropped
+const MAX_CATCH_UP: u32 = 5;
+
+// Packet observer for every bot, gets the bot name along with the packet
+type BotObserver = Arc<dyn Fn(&str, &Packet) + Send + Sync>;
+
+/// A set of bots on one server with the game loop that drives them, without any terminal UI
+pub struct Client {
+    ctx: GlobalContext,
+}
+
+impl Client {
+    /// Pings the server, then connects every configured bot at once.
+    /// Bots that can't connect are left out, it's only an error if none can
+    pub async fn start(config: &Config) -> Result<Self, Box<dyn Error>> {
+        Self::start_bots(config, None).await
+    }
+
+    /// Same as start, with `observe` called with the bot name and every packet it receives from
+    /// the handshake on. It runs on the connection reader tasks, see net::PacketObserver
+    pub async fn start_observed(
+        config: &Config,
+        observe: impl Fn(&str, &Packet) + Send + Sync + 'static)
+        -> Result<Self, Box<dyn Error>>
+    {
+        Self::start_bots(config, Some(Arc::new(observe))).await
+    }
+
+    async fn start_bots(
+        config: &Config,
+        observe: Option<BotObserver>)
+        -> Result<Self, Box<dyn Error>>
+    {
+        let status = Connection::ping(&config.host, config.port.into()).await
+            .map_err(|e| format!("Server {}:{} is not reachable: {}", config.host, config.port, e))?;
+        log::info!("Server \"{}\" ({}) has {}/{} players", status.motd,
+            status.version.as_deref().unwrap_or("unknown version"), status.players, status.max_players);
+        if status.protocol.is_some_and(|protocol| protocol != net::PROTOCOL_VERSION) {
+            log::warning!("Server speaks protocol {}, this client {}",
+                status.protocol.unwrap_or(0), net::PROTOCOL_VERSION);
+        }
+        if status.players as usize + config.count > status.max_players as usize {
+            return Err(format!("Server has room for {} more players, {} bots wouldn't fit",
+                status.max_players.saturating_sub(status.players), config.count).into());
+        }
+        let mut ctx = GlobalContext::init(config.resources.clone())?;
+        ctx.tick_duration = Duration::from_secs(1) / config.tick_rate;
+        ctx.stats_file = config.stats_file.clone();
+        ctx.ascii = config.ascii;
+        if let Some(events) = config.events.as_ref() {
+            ctx.events.surfaced = events.clone();
+        }
+        let policy = ConnectPolicy {
+            timeout: config.connect_timeout,
+            retries: config.connect_retries,
+        };
+        let mut connecting = JoinSet::new();
+        for i in 1..=config.count {
+            let name = format!("{}{}", config.name_prefix, i);
+            let host = config.host.clone();
+            let port = config.port.into();
+            let observers = Observers::new();
+            if let Some(observe) = observe.as_ref() {
+                let (observe, bot) = (Arc::clone(observe), name.clone());
+                observers.add(Arc::new(move |packet: &Packet| observe(&bot, packet)));
+            }
+            connecting.spawn(async move {
+                let player = Player::start(&host, port, name, policy, observers).await
+                    .map_err(|e| e.to_string());
+                (i, player)
+            });
+        }
+        let mut started = vec![];
+        while let Some(result) = connecting.join_next().await {
+            match result {
+                Ok(started_bot) => started.push(started_bot),
+                Err(e) => log::error!("Bot startup task failed: {}", e)
+            }
+        }
+        // Keep the configured order rather than the order they got in
+        started.sort_by_key(|(i, _)| *i);
+        let mut active = 0;
+        for (i, player) in started {
+            match player {
+                Ok(player) => {
+                    {
+                        let mut player = player.write().await;
+                        player.eat_threshold = config.eat_threshold;
+                        player.respawn_delay = config.respawn_delay;
+                        player.stay_dead = config.stay_dead;
+                    }
+                    if i == config.active {
+                        active = ctx.players.len();
+                    }
+                    ctx.add_player(player, false).await;
+                },
+                Err(e) => log::error!("{}", e)
+            }
+        }
+        log::info!("{} of {} bots connected", ctx.players.len(), config.count);
+        if ctx.players.is_empty() {
+            return Err("No bot could connect".into());
+        }
+        ctx.set_active_player(active).await;
+        Ok(Self { ctx })
+    }
+
+    /// Called with every inbound packet right before the player handles it, on the game loop
+    pub fn on_packet(&mut self, hook: impl Fn(&Player, &Packet) + Send + Sync + 'static) {
+        self.ctx.packet_hook = Some(Box::new(hook));
+    }
+
+    pub fn players(&self) -> &[Arc<RwLock<Player>>] {
+        &self.ctx.players
+    }
+
+    pub fn context(&self) -> &GlobalContext {
+        &self.ctx
+    }
+
+    pub fn context_mut(&mut self) -> &mut GlobalContext {
+        &mut self.ctx
+    }
+
+    /// Runs a prompt command like "/goto 10 64 -5" as the active player
+    pub async fn command(&mut self, line: &str) {
+        self.ctx.run_command(line).await;
+    }
+
+    /// Runs a single game tick, false once the client has quit
+    pub async fn tick(&mut self) -> bool {
+        if self.ctx.stop {
+            return false;
+        }
+        self.ctx.tick().await;
+        !self.ctx.stop
+    }
+
+    /// Ticks at the configured rate until quit
+    pub async fn run(&mut self) {
+        self.run_loop(None).await;
+    }
+
+    /// Same as run, publishing a UI snapshot after every tick
+    pub async fn run_rendering(&mut self, ui_state: &Arc<UiState>) {
+        self.run_loop(Some(ui_state)).await;
+        ui_state.stop();
+    }
+
+    pub async fn quit(&mut self) {
+        self.ctx.quit().await;
+    }
+
+    async fn run_loop(&mut self, ui_state: Option<&Arc<UiState>>) {
+        let mut next_tick = Instant::now();
+        while self.tick().await {
+            if let Some(ui_state) = ui_state {
+                self.ctx.update_render(ui_state).await;
+            }
+            let tick_duration = self.ctx.tick_duration;
+            next_tick += tick_duration;
+            let now = Instant::now();
+            if now > next_tick + tick_duration * MAX_CATCH_UP {
+                log::warning!("Game loop fell {}ms behind, skipping ahead",
+                    (now - next_tick).as_millis());
+                next_tick = now;
+            }
+            sleep_until(next_tick).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn on_packet_sees_inbound_packets_in_order() {
+        let ctx = GlobalContext::init(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources")).unwrap();
+        let mut client = Client { ctx };
+        let (connection, inbound, _server) = Connection::mock().await;
+        let player = Player::with_connection(connection, "bot1".to_string()).await;
+        client.context_mut().add_player(player, true).await;
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let hook_seen = Arc::clone(&seen);
+        client.on_packet(move |player, packet| {
+            hook_seen.lock().unwrap().push(format!("{} {:?}", player.name, packet));
+        });
+        inbound.send(Packet::TimeUpdate { age: 1, time: 2 }).await.unwrap();
+        inbound.send(Packet::ChatMessage { message: "hi".to_string() }).await.unwrap();
+        assert!(client.tick().await);
+        assert_eq!(*seen.lock().unwrap(), [
+            "bot1 TimeUpdate { age: 1, time: 2 }",
+            "bot1 ChatMessage { message: \"hi\" }",
+        ]);
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::error::Error;
+use std::time::Duration;
+
+use crate::game::{DEFAULT_TICK_RATE, DEFAULT_EAT_THRESHOLD, DEFAULT_RESPAWN_DELAY, EventKind};
+use crate::ui::{ColorSupport, unicode_locale, DEFAULT_ENTITY_RANGE, DEFAULT_ENTITY_RANGE_SLACK};
+
+const DEFAULT_CONFIG: &str = "config.json";
+const DEFAULT_DRAW_RATE: u32 = 60;
+// Sane bounds for the rates, per second
+const MAX_TICK_RATE: u32 = 100;
+const MAX_DRAW_RATE: u32 = 240;
+// Full food bar
+const MAX_FOOD: i16 = 20;
+
+/// Startup settings: defaults, then `config.json`, then command-line args
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub name_prefix: String,
+    pub count: usize,
+    pub active: usize,
+    pub resources: PathBuf,
+    pub headless: bool,
+    // Overlay radii, entities show up inside the first and disappear outside the second
+    pub entity_range: i32,
+    pub entity_remove_range: i32,
+    pub entity_circle: bool,
+    pub stats_file: Option<PathBuf>,
+    // Per attempt, and how many more attempts a bot gets before it's left out
+    pub connect_timeout: Duration,
+    pub connect_retries: u32,
+    // Game ticks and frames drawn per second
+    pub tick_rate: u32,
+    pub draw_rate: u32,
+    // Detected from the terminal unless set
+    pub colors: ColorSupport,
+    // ASCII glyphs instead of Unicode ones, on by default only for non UTF-8 locales
+    pub ascii: bool,
+    // Bots eat once their food drops below this
+    pub eat_threshold: i16,
+    // Ticks dead bots wait before respawning, or they stay dead for debugging
+    pub respawn_delay: u64,
+    pub stay_dead: bool,
+    // Sound and effect kinds shown in the event log, the built-in set unless given
+    pub events: Option<HashSet<EventKind>>,
+}
+
+const USAGE: &str = "usage: mc-tui [--config <file>] [--host <host>] [--port <port>] \
+    [--name <prefix>] [--count <n>] [--active <n>] [--resources <dir>] [--stats-file <file>] [--connect-timeout <seconds>] [--connect-retries <n>] \
+    [--tick-rate <per second>] [--draw-rate <per second>] [--color <256|truecolor>] [--ascii] [--eat-threshold <0-20>] \
+    [--respawn-delay <ticks>] [--stay-dead] [--events <kind,...>] [--headless]";
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 25565,
+            name_prefix: "UristMc_".to_string(),
+            count: 8,
+            active: 1,
+            resources: PathBuf::from("resources"),
+            headless: false,
+            entity_range: DEFAULT_ENTITY_RANGE,
+            entity_remove_range: DEFAULT_ENTITY_RANGE + DEFAULT_ENTITY_RANGE_SLACK,
+            entity_circle: false,
+            stats_file: None,
+            connect_timeout: Duration::from_secs(10),
+            connect_retries: 3,
+            tick_rate: DEFAULT_TICK_RATE,
+            draw_rate: DEFAULT_DRAW_RATE,
+            colors: ColorSupport::detect(),
+            ascii: !unicode_locale(),
+            eat_threshold: DEFAULT_EAT_THRESHOLD,
+            respawn_delay: DEFAULT_RESPAWN_DELAY,
+            stay_dead: false,
+            events: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let mut config = Self::default();
+
+        // An explicitly passed config file has to exist, the default one doesn't
+        let path = args.iter().position(|arg| arg == "--config")
+            .map(|i| args.get(i + 1).map(PathBuf::from).ok_or(USAGE))
+            .transpose()?;
+        match path {
+            Some(path) => config.read_file(&path)?,
+            None if PathBuf::from(DEFAULT_CONFIG).is_file() => config.read_file(&PathBuf::from(DEFAULT_CONFIG))?,
+            None => {}
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--headless" {
+                config.headless = true;
+                continue;
+            }
+            if arg == "--ascii" {
+                config.ascii = true;
+                continue;
+            }
+            if arg == "--stay-dead" {
+                config.stay_dead = true;
+                continue;
+            }
+            let value = args.next().ok_or(USAGE)?;
+            match &arg[..] {
+                "--config" => {},
+                "--host" => config.host = value,
+                "--port" => config.port = value.parse().map_err(|_| format!("Bad port: {}", value))?,
+                "--name" => config.name_prefix = value,
+                "--count" => config.count = value.parse().map_err(|_| format!("Bad bot count: {}", value))?,
+                "--active" => config.active = value.parse().map_err(|_| format!("Bad active bot: {}", value))?,
+                "--resources" => config.resources = PathBuf::from(value),
+                "--stats-file" => config.stats_file = Some(PathBuf::from(value)),
+                "--connect-timeout" => config.connect_timeout = value.parse().ok()
+                    .and_then(|seconds| Duration::try_from_secs_f32(seconds).ok())
+                    .ok_or_else(|| format!("Bad connect timeout: {}", value))?,
+                "--connect-retries" => config.connect_retries = value.parse()
+                    .map_err(|_| format!("Bad connect retries: {}", value))?,
+                "--tick-rate" => config.tick_rate = value.parse()
+                    .map_err(|_| format!("Bad tick rate: {}", value))?,
+                "--draw-rate" => config.draw_rate = value.parse()
+                    .map_err(|_| format!("Bad draw rate: {}", value))?,
+                "--color" => config.colors = ColorSupport::parse(&value)
+                    .ok_or_else(|| format!("Bad color mode: {}, expected 256 or truecolor", value))?,
+                "--eat-threshold" => config.eat_threshold = value.parse()
+                    .map_err(|_| format!("Bad eat threshold: {}", value))?,
+                "--respawn-delay" => config.respawn_delay = value.parse()
+                    .map_err(|_| format!("Bad respawn delay: {}", value))?,
+                "--events" => config.events = Some(value.split(',')
+                    .filter(|name| !name.is_empty())
+                    .map(EventKind::parse)
+                    .collect::<Result<_, _>>()?),
+                _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE).into())
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn read_file(&mut self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+        let data = json::parse(&data[..])
+            .map_err(|e| format!("Bad {}: {}", path.display(), e))?;
+        if let Some(host) = data["host"].as_str() {
+            self.host = host.to_string();
+        }
+        if let Some(port) = data["port"].as_u16() {
+            self.port = port;
+        }
+        if let Some(prefix) = data["name_prefix"].as_str() {
+            self.name_prefix = prefix.to_string();
+        }
+        if let Some(count) = data["count"].as_usize() {
+            self.count = count;
+        }
+        if let Some(active) = data["active"].as_usize() {
+            self.active = active;
+        }
+        if let Some(resources) = data["resources"].as_str() {
+            self.resources = PathBuf::from(resources);
+        }
+        if let Some(headless) = data["headless"].as_bool() {
+            self.headless = headless;
+        }
+        if let Some(range) = data["entity_range"].as_i32() {
+            self.entity_range = range;
+            // Keep the default slack unless the remove range is given too
+            self.entity_remove_range = range + DEFAULT_ENTITY_RANGE_SLACK;
+        }
+        if let Some(range) = data["entity_remove_range"].as_i32() {
+            self.entity_remove_range = range;
+        }
+        if let Some(circle) = data["entity_circle"].as_bool() {
+            self.entity_circle = circle;
+        }
+        if let Some(stats_file) = data["stats_file"].as_str() {
+            self.stats_file = Some(PathBuf::from(stats_file));
+        }
+        if let Some(seconds) = data["connect_timeout"].as_f32() {
+            self.connect_timeout = Duration::try_from_secs_f32(seconds)
+                .map_err(|_| format!("Bad connect timeout: {}", seconds))?;
+        }
+        if let Some(retries) = data["connect_retries"].as_u32() {
+            self.connect_retries = retries;
+        }
+        if let Some(rate) = data["tick_rate"].as_u32() {
+            self.tick_rate = rate;
+        }
+        if let Some(rate) = data["draw_rate"].as_u32() {
+            self.draw_rate = rate;
+        }
+        if let Some(ascii) = data["ascii"].as_bool() {
+            self.ascii = ascii;
+        }
+        if let Some(colors) = data["color"].as_str() {
+            self.colors = ColorSupport::parse(colors)
+                .ok_or_else(|| format!("Bad color mode: {}, expected 256 or truecolor", colors))?;
+        }
+        if let Some(threshold) = data["eat_threshold"].as_i16() {
+            self.eat_threshold = threshold;
+        }
+        if let Some(delay) = data["respawn_delay"].as_u64() {
+            self.respawn_delay = delay;
+        }
+        if let Some(stay_dead) = data["stay_dead"].as_bool() {
+            self.stay_dead = stay_dead;
+        }
+        if data["events"].is_array() {
+            let events = data["events"].members()
+                .map(|name| name.as_str()
+                    .ok_or_else(|| format!("Event kinds should be strings, got {}", name))
+                    .and_then(EventKind::parse))
+                .collect::<Result<_, _>>()?;
+            self.events = Some(events);
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.count < 1 {
+            return Err("Bot count must be at least 1".into());
+        }
+        // Bots are numbered from 1, same as their names
+        if self.active < 1 || self.active > self.count {
+            return Err(format!("Active bot must be between 1 and {}", self.count).into());
+        }
+        if self.tick_rate < 1 || self.tick_rate > MAX_TICK_RATE {
+            return Err(format!("Tick rate must be between 1 and {} per second", MAX_TICK_RATE).into());
+        }
+        if self.draw_rate < 1 || self.draw_rate > MAX_DRAW_RATE {
+            return Err(format!("Draw rate must be between 1 and {} per second", MAX_DRAW_RATE).into());
+        }
+        if !(0..=MAX_FOOD).contains(&self.eat_threshold) {
+            return Err(format!("Eat threshold must be between 0 and {}", MAX_FOOD).into());
+        }
+        if self.connect_timeout.is_zero() {
+            return Err("Connect timeout must be above 0".into());
+        }
+        if self.entity_range < 1 || self.entity_remove_range < self.entity_range {
+            return Err("Entity range must be at least 1 and no larger than the remove range".into());
+        }
+        if !self.resources.is_dir() {
+            return Err(format!("Resources directory {} not found", self.resources.display()).into());
+        }
+        Ok(())
+    }
+}
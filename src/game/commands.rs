@@ -0,0 +1,166 @@
+use crate::packets::Packet;
+use crate::log;
+
+use super::{GlobalContext, GameState};
+
+const USAGE: [&str; 10] = [
+    "/goto <x> <y> <z> - walk the active player there",
+    "/tp <x> <y> <z> - look at a position",
+    "/follow <eid> - follow an entity",
+    "/player <n> - switch to the n-th player",
+    "/say <message> - chat as the active player",
+    "/sleep <x> <y> <z> - get the active player into the bed there",
+    "/wake - get the active player out of bed",
+    "/map [id] - show a filled map, or close it",
+    "/enchant [1-3] - enchant with an option of the open table, or close it",
+    "/quit - disconnect everyone and exit",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Goto((i32, i32, i32)),
+    Tp((i32, i32, i32)),
+    Follow(i32),
+    Player(usize),
+    Say(String),
+    Sleep((i32, i32, i32)),
+    Wake,
+    Map(Option<i16>),
+    Enchant(Option<u8>),
+    Quit,
+}
+
+fn parse_pos(args: &[&str]) -> Option<(i32, i32, i32)> {
+    match args {
+        [x, y, z] => Some((x.parse().ok()?, y.parse().ok()?, z.parse().ok()?)),
+        _ => None
+    }
+}
+
+/// Turns a prompt line into a command, the error is the usage line to show
+pub fn parse(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let args: Vec<&str> = rest.split_whitespace().collect();
+    match name {
+        "/goto" => parse_pos(&args).map(Command::Goto).ok_or(USAGE[0].to_string()),
+        "/tp" => parse_pos(&args).map(Command::Tp).ok_or(USAGE[1].to_string()),
+        "/follow" => match args.as_slice() {
+            [eid] => eid.parse().map(Command::Follow).map_err(|_| USAGE[2].to_string()),
+            _ => Err(USAGE[2].to_string())
+        },
+        "/player" => match args.as_slice() {
+            [n] => n.parse::<usize>().ok()
+                .filter(|n| *n > 0)
+                .map(Command::Player)
+                .ok_or(USAGE[3].to_string()),
+            _ => Err(USAGE[3].to_string())
+        },
+        "/say" if !rest.trim().is_empty() => Ok(Command::Say(rest.trim().to_string())),
+        "/say" => Err(USAGE[4].to_string()),
+        "/sleep" => parse_pos(&args).map(Command::Sleep).ok_or(USAGE[5].to_string()),
+        "/wake" => Ok(Command::Wake),
+        "/map" => match args.as_slice() {
+            [] => Ok(Command::Map(None)),
+            [id] => id.parse().map(|id| Command::Map(Some(id))).map_err(|_| USAGE[7].to_string()),
+            _ => Err(USAGE[7].to_string())
+        },
+        "/enchant" => match args.as_slice() {
+            [] => Ok(Command::Enchant(None)),
+            [n] => n.parse::<u8>().ok()
+                .filter(|n| (1..=3).contains(n))
+                .map(|n| Command::Enchant(Some(n - 1)))
+                .ok_or(USAGE[8].to_string()),
+            _ => Err(USAGE[8].to_string())
+        },
+        "/quit" => Ok(Command::Quit),
+        _ => Err(format!("Unknown command: {}", line))
+    }
+}
+
+pub async fn run(line: &str, ctx: &mut GlobalContext) {
+    if line.trim().is_empty() {
+        return;
+    }
+    match parse(line) {
+        Ok(command) => dispatch(command, ctx).await,
+        Err(usage) => {
+            log::warning!("{}", usage);
+            if usage.starts_with("Unknown") {
+                for line in USAGE {
+                    log::info!("{}", line);
+                }
+            }
+        }
+    }
+}
+
+pub async fn dispatch(command: Command, ctx: &mut GlobalContext) {
+    match command {
+        Command::Goto(target) => {
+            if let Some(player) = ctx.active_player.as_ref() {
+                player.write().await.goto(&ctx.world, target);
+            }
+        },
+        Command::Tp(pos) => {
+            ctx.mode = GameState::WorldLook;
+            ctx.set_cam(pos);
+        },
+        Command::Follow(eid) => ctx.follow_entity(eid),
+        Command::Player(n) => ctx.set_active_player(n - 1).await,
+        Command::Say(message) => {
+            if let Some(player) = ctx.active_player.as_ref() {
+                let player = player.read().await;
+                if let Err(e) = player.connection.send(Packet::ChatMessage { message }).await {
+                    log::warning!("Failed to send chat for {}: {}", player.name, e);
+                }
+            }
+        },
+        Command::Sleep(pos) => {
+            if !ctx.world.get_block(pos).is_bed() {
+                log::warning!("No bed at {} {} {}", pos.0, pos.1, pos.2);
+                return;
+            }
+            if let Some(player) = ctx.active_player.as_ref() {
+                let mut player = player.write().await;
+                if let Err(e) = player.sleep(pos).await {
+                    log::warning!("{} can't sleep: {}", player.name, e);
+                }
+            }
+        },
+        Command::Wake => {
+            if let Some(player) = ctx.active_player.as_ref() {
+                let mut player = player.write().await;
+                if let Err(e) = player.leave_bed().await {
+                    log::warning!("{} can't leave bed: {}", player.name, e);
+                }
+            }
+        },
+        Command::Map(None) => ctx.shown_map = None,
+        Command::Map(Some(id)) => {
+            if ctx.maps.get(id).is_none() {
+                let known: Vec<String> = ctx.maps.ids().map(|id| id.to_string()).collect();
+                match known.is_empty() {
+                    true => log::warning!("No maps seen yet"),
+                    false => log::warning!("No map {}, seen: {}", id, known.join(", "))
+                }
+                return;
+            }
+            ctx.shown_map = Some(id);
+        },
+        Command::Enchant(option) => {
+            if let Some(player) = ctx.active_player.as_ref() {
+                let mut player = player.write().await;
+                let result = match (option, player.enchanting.as_ref().map(|w| w.window_id)) {
+                    (Some(option), Some(window_id)) => player.enchant(window_id, option).await,
+                    (Some(_), None) => Err("No enchantment table open".into()),
+                    (None, _) => player.close_window().await
+                };
+                if let Err(e) = result {
+                    log::warning!("{} can't enchant: {}", player.name, e);
+                }
+            }
+        },
+        Command::Quit => ctx.quit().await,
+    }
+}
@@ -8,7 +8,9 @@ pub struct Entity {
     pub last_position: (f64, f64, f64),
     pub info: Option<&'static EntityInfo>,
     pub parent: Option<i32>,
-    pub children: Vec<i32>
+    pub children: Vec<i32>,
+    // Item ids of the held item, boots, leggings, chestplate and helmet
+    pub equipment: [Option<i16>; 5],
 }
 
 impl Entity {
@@ -16,6 +18,34 @@ impl Entity {
         crate::util::world_pos(self.pos)
     }
 
+    pub fn is_armored(&self) -> bool {
+        self.equipment[1..].iter().any(|item| item.is_some())
+    }
+
+    /// Held item and worn armor, e.g. "holding 267, wearing 306 311"
+    pub fn equipment_summary(&self) -> String {
+        let mut summary = match self.equipment[0] {
+            Some(id) => format!("holding {}", id),
+            None => "empty handed".to_string(),
+        };
+        let armor: Vec<String> = self.equipment[1..].iter().rev()
+            .flatten()
+            .map(|id| id.to_string())
+            .collect();
+        if !armor.is_empty() {
+            summary.push_str(&format!(", wearing {}", armor.join(" ")));
+        }
+        summary
+    }
+
+    pub fn display_name(&self) -> &str {
+        match (&self.name, self.info) {
+            (Some(name), _) => name,
+            (None, Some(info)) => &info.name,
+            (None, None) => "unknown",
+        }
+    }
+
     pub fn sprites_or_default(&self) -> Vec<EntityRender> {
         if let Some(info) = self.info {
             info.sprites.clone()
@@ -42,6 +72,8 @@ type EntityRender = (char, (u8, u8, u8), Option<(u8, u8, u8)>);
 pub enum EntityType {
     Mob(MobType),
     Object(ObjectType),
+    ExperienceOrb,
+    Painting,
 }
 
 #[derive(Debug, Eq, PartialEq)]
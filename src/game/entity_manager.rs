@@ -10,7 +10,7 @@ use super::player::Player;
 
 use crate::log;
 use crate::util::{pos_add, pos_sub, from_abs_int};
-use crate::packets::Packet;
+use crate::packets::{Packet, Slot};
 
 pub struct EntityManager {
     pub update: bool,
@@ -19,27 +19,39 @@ pub struct EntityManager {
     ownership: HashMap<i32, usize>,
     pub moved: HashSet<i32>,
     orphaned: HashSet<i32>,
-    deleted: Vec<i32>,
+    pub deleted: Vec<i32>,
+    // Where something got picked up this tick
+    pub collected: Vec<(i32, i32, i32)>,
+    // Entity id and animation id of every animation this tick
+    pub animations: Vec<(i32, u8)>,
+    // Entities whose equipment changed this tick
+    pub equipped: Vec<i32>,
     info: Vec<&'static EntityInfo>,
 }
 
 impl EntityManager {
-    pub fn init(resources_root: std::path::PathBuf) -> Self {
-        Self {
+    pub fn init(resources_root: std::path::PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
             entities: vec![],
             ownership: HashMap::new(),
             ids: HashSet::new(),
             moved: HashSet::new(),
             deleted: vec![],
+            collected: vec![],
+            animations: vec![],
+            equipped: vec![],
             orphaned: HashSet::new(),
             update: true,
-            info: parse_info(resources_root)
-        }
+            info: parse_info(resources_root)?
+        })
     }
 
     pub fn tick(&mut self) {
         self.moved.clear();
         self.deleted.clear();
+        self.collected.clear();
+        self.animations.clear();
+        self.equipped.clear();
         self.update = false;
 
         for entity in self.entities.iter_mut() {
@@ -85,50 +97,49 @@ impl EntityManager {
                 }
             },
             Packet::SpawnObject {eid, obj_type, x, y, z, pitch, yaw, object_data } => {
-                if self.ids.contains(&eid) {
-                    return;
-                }
-                self.ownership.insert(eid, source);
-                self.ids.insert(eid);
                 let etype = EntityType::Object(to_object_type(obj_type));
-                let pos = from_abs_int((x, y, z));
-                let info = self.info.iter().find(|info| info.etype == etype).map(|e| *e);
-                self.entities.push(Entity {
-                   etype,
-                   new: true,
-                   id: eid,
-                   name: None,
-                   info,
-                   pos,
-                   last_position: pos,
-                   parent: None,
-                   children: vec![],
-                   last_movement: (0., 0., 0.),
-                });
-                self.update = true;
+                self.add_entity(eid, etype, None, from_abs_int((x, y, z)), source);
             },
             Packet::SpawnMob {eid, mob_type, x, y, z, pitch, head_pitch, yaw, dx, dy, dz, metadata} => {
-                if self.ids.contains(&eid) {
+                let etype = EntityType::Mob(to_mob_type(mob_type));
+                self.add_entity(eid, etype, None, from_abs_int((x, y, z)), source);
+            },
+            Packet::SpawnExperienceOrb {eid, x, y, z, ..} => {
+                self.add_entity(eid, EntityType::ExperienceOrb, None, from_abs_int((x, y, z)), source);
+            },
+            // Paintings are placed by block, not by fractional position
+            Packet::SpawnPainting {eid, title, x, y, z, ..} => {
+                let pos = (x as f64 + 0.5, y as f64, z as f64 + 0.5);
+                self.add_entity(eid, EntityType::Painting, Some(title), pos, source);
+            },
+            Packet::CollectItem {collected, collector} => {
+                // Our own players aren't tracked here, fall back to where the item was
+                let pos = self.entities.iter()
+                    .find(|e| e.id == collector)
+                    .or_else(|| self.entities.iter().find(|e| e.id == collected))
+                    .map(|e| e.world_pos());
+                if let Some(pos) = pos {
+                    self.collected.push(pos);
+                }
+                self.remove_entity(collected);
+            },
+            // Every player in range gets the same animation, only count the owner's copy
+            Packet::Animation {eid, anim} => {
+                if self.ownership.get(&eid) == Some(&source) {
+                    self.animations.push((eid, anim));
+                }
+            },
+            Packet::EntityEquipment {eid, slot, item} => {
+                if self.ownership.get(&eid) != Some(&source) || !(0..5).contains(&slot) {
                     return;
                 }
-                self.ownership.insert(eid, source);
-                self.ids.insert(eid);
-                let etype = EntityType::Mob(to_mob_type(mob_type));
-                let pos = from_abs_int((x, y, z));
-                let info = self.info.iter().find(|info| info.etype == etype).map(|e| *e);
-                self.entities.push(Entity {
-                   etype,
-                   new: true,
-                   id: eid,
-                   name: None,
-                   info,
-                   pos,
-                   last_position: pos,
-                   parent: None,
-                   children: vec![],
-                   last_movement: (0., 0., 0.),
-                });
-                self.update = true;
+                if let Some(entity) = self.entities.iter_mut().find(|e| e.id == eid) {
+                    entity.equipment[slot as usize] = match item {
+                        Slot::Empty => None,
+                        Slot::Item { id, .. } | Slot::ItemNbt { id, .. } => Some(id),
+                    };
+                    self.equipped.push(eid);
+                }
             },
             Packet::EntityTeleport {eid, x, y, z, yaw, pitch} => {
                 self.entity_move(from_abs_int((x, y, z)), true, eid, source);
@@ -161,8 +172,8 @@ impl EntityManager {
                 self.moved.insert(eid);
                 children = entity.children.clone();
                 if absolute {
-                    entity.pos = vector;
                     entity.last_movement = pos_add(entity.last_movement, pos_sub(vector, entity.pos));
+                    entity.pos = vector;
                 } else {
                     entity.pos = pos_add(entity.pos, vector);
                     entity.last_movement = pos_add(entity.last_movement, vector);
@@ -181,6 +192,48 @@ impl EntityManager {
         }
     }
 
+    fn add_entity(
+        &mut self,
+        eid: i32,
+        etype: EntityType,
+        name: Option<String>,
+        pos: (f64, f64, f64),
+        source: usize)
+    {
+        if self.ids.contains(&eid) {
+            return;
+        }
+        self.ownership.insert(eid, source);
+        self.ids.insert(eid);
+        let info = self.info.iter().find(|info| info.etype == etype).map(|e| *e);
+        self.entities.push(Entity {
+           etype,
+           new: true,
+           id: eid,
+           name,
+           info,
+           pos,
+           last_position: pos,
+           parent: None,
+           children: vec![],
+           last_movement: (0., 0., 0.),
+           equipment: [None; 5],
+        });
+        self.update = true;
+    }
+
+    /// Stops tracking an entity for every player at once
+    fn remove_entity(&mut self, eid: i32) {
+        if let Some(index) = self.entities.iter().position(|e| e.id == eid) {
+            self.entities.remove(index);
+            self.deleted.push(eid);
+            self.update = true;
+        }
+        self.ids.remove(&eid);
+        self.ownership.remove(&eid);
+        self.orphaned.remove(&eid);
+    }
+
     pub fn entity_destroy(&mut self, ids: Vec<i32>, source: usize) {
         for eid in ids {
             if !self.ids.contains(&eid) {
@@ -221,15 +274,20 @@ impl EntityManager {
     }
 }
 
-fn parse_info(resources_root: std::path::PathBuf) -> Vec<&'static EntityInfo> {
+fn parse_info(resources_root: std::path::PathBuf) -> Result<Vec<&'static EntityInfo>, Box<dyn std::error::Error>> {
     let entity_data_path = resources_root.join("entity_data.json");
-    let entity_data = json::parse(&std::fs::read_to_string(entity_data_path).unwrap()[..]).unwrap();
-    entity_data["data"]
+    let entity_data = std::fs::read_to_string(&entity_data_path)
+        .map_err(|e| format!("Can't read {}: {}", entity_data_path.display(), e))?;
+    let entity_data = json::parse(&entity_data[..])
+        .map_err(|e| format!("Bad {}: {}", entity_data_path.display(), e))?;
+    Ok(entity_data["data"]
         .members()
         .map(|entity| {
             let etype = match entity["type"].as_str().unwrap() {
                 "mob" => EntityType::Mob(to_mob_type(entity["id"].as_u8().unwrap())),
                 "object" => EntityType::Object(to_object_type(entity["id"].as_u8().unwrap())),
+                "orb" => EntityType::ExperienceOrb,
+                "painting" => EntityType::Painting,
                 _ => panic!("Unknown type of entity: {:?}", entity["type"])
             };
             &*Box::leak(Box::new(EntityInfo {
@@ -248,5 +306,27 @@ fn parse_info(resources_root: std::path::PathBuf) -> Vec<&'static EntityInfo> {
                 }).collect()
             }))
         })
-        .collect()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> EntityManager {
+        EntityManager::init(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn teleport_reports_the_jump() {
+        let mut manager = manager();
+        // Absolute positions are in 32nds of a block
+        manager.handle_packet(Packet::SpawnExperienceOrb { eid: 7, x: 32, y: 64 * 32, z: -32, count: 1 }, 0).await;
+        manager.tick();
+        manager.handle_packet(Packet::EntityTeleport { eid: 7, x: 5 * 32, y: 66 * 32, z: -32, yaw: 0, pitch: 0 }, 0).await;
+        let entity = manager.entities.iter().find(|e| e.id == 7).unwrap();
+        assert_eq!(entity.pos, (5., 66., -1.));
+        assert_eq!(entity.last_movement, (4., 2., 0.));
+        assert!(manager.moved.contains(&7));
+    }
 }
@@ -0,0 +1,163 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::log;
+
+const EVENT_CAPACITY: usize = 64;
+// Names the config uses for each kind
+const KIND_NAMES: [(&str, EventKind); 9] = [
+    ("door", EventKind::Door),
+    ("mob_hurt", EventKind::MobHurt),
+    ("mob_death", EventKind::MobDeath),
+    ("explosion", EventKind::Explosion),
+    ("fire", EventKind::Fire),
+    ("block_break", EventKind::BlockBreak),
+    ("particle", EventKind::Particle),
+    ("lightning", EventKind::Lightning),
+    ("other", EventKind::Other),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Door,
+    MobHurt,
+    MobDeath,
+    Explosion,
+    Fire,
+    BlockBreak,
+    Particle,
+    Lightning,
+    Other,
+}
+
+impl EventKind {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        KIND_NAMES.iter()
+            .find(|(kind_name, _)| *kind_name == name)
+            .map(|(_, kind)| *kind)
+            .ok_or_else(|| format!("Unknown event kind {}, expected one of {}", name,
+                KIND_NAMES.map(|(kind_name, _)| kind_name).join(", ")))
+    }
+
+    pub fn from_sound(name: &str) -> Self {
+        if name.starts_with("random.door") {
+            EventKind::Door
+        } else if name == "random.explode" {
+            EventKind::Explosion
+        } else if name.starts_with("fire.") {
+            EventKind::Fire
+        } else if name.starts_with("dig.") || name == "random.break" {
+            EventKind::BlockBreak
+        } else if name.starts_with("mob.") && name.ends_with("death") {
+            EventKind::MobDeath
+        } else if name.starts_with("mob.") && name.ends_with("hurt") || name == "damage.hit" {
+            EventKind::MobHurt
+        } else {
+            EventKind::Other
+        }
+    }
+
+    // Ids of the 1.5.2 sound/particle effect packet
+    pub fn from_effect(effect_id: i32) -> Self {
+        match effect_id {
+            1003 => EventKind::Door,
+            2001 => EventKind::BlockBreak,
+            2000..=2999 => EventKind::Particle,
+            _ => EventKind::Other,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GameEvent {
+    pub kind: EventKind,
+    pub text: String,
+    pub pos: (i32, i32, i32),
+    pub tick: u64,
+}
+
+/// Recent sounds and effects, only the kinds in `surfaced` are kept
+pub struct Events {
+    buffer: VecDeque<GameEvent>,
+    pub surfaced: HashSet<EventKind>,
+    // Particle positions seen this tick
+    pub particles: Vec<(i32, i32, i32)>,
+    // Lightning strikes this tick, once each however many players saw them
+    pub lightning: Vec<(i32, i32, i32)>,
+    pub update: bool,
+}
+
+impl Events {
+    pub fn init() -> Self {
+        Self {
+            buffer: VecDeque::with_capacity(EVENT_CAPACITY),
+            surfaced: HashSet::from([
+                EventKind::Door,
+                EventKind::MobHurt,
+                EventKind::MobDeath,
+                EventKind::Explosion,
+                EventKind::Lightning,
+            ]),
+            particles: vec![],
+            lightning: vec![],
+            update: false,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        self.particles.clear();
+        self.lightning.clear();
+        self.update = false;
+    }
+
+    pub fn push(&mut self, kind: EventKind, text: String, pos: (i32, i32, i32), tick: u64) {
+        if kind == EventKind::Particle {
+            self.particles.push(pos);
+        }
+        if kind == EventKind::Lightning && !self.lightning.contains(&pos) {
+            log::info!("Lightning struck at {} {} {}", pos.0, pos.1, pos.2);
+            self.lightning.push(pos);
+        }
+        if !self.surfaced.contains(&kind) {
+            return;
+        }
+        // Every player nearby hears the same sound
+        if self.buffer.iter().rev()
+            .take_while(|e| e.tick == tick)
+            .any(|e| e.kind == kind && e.pos == pos)
+        {
+            return;
+        }
+        if self.buffer.len() == EVENT_CAPACITY {
+            self.buffer.pop_front();
+        }
+        self.buffer.push_back(GameEvent { kind, text, pos, tick });
+        self.update = true;
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &GameEvent> {
+        self.buffer.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_parse_by_name() {
+        assert_eq!(EventKind::parse("mob_hurt"), Ok(EventKind::MobHurt));
+        assert_eq!(EventKind::parse("block_break"), Ok(EventKind::BlockBreak));
+        let error = EventKind::parse("footsteps").unwrap_err();
+        assert!(error.starts_with("Unknown event kind footsteps, expected one of door, mob_hurt"), "{}", error);
+    }
+
+    #[test]
+    fn only_surfaced_kinds_are_kept() {
+        let mut events = Events::init();
+        events.surfaced = HashSet::from([EventKind::Fire]);
+        events.push(EventKind::Door, "door".to_string(), (0, 64, 0), 1);
+        events.push(EventKind::Fire, "fire".to_string(), (0, 64, 0), 1);
+        let kinds: Vec<_> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [EventKind::Fire]);
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::log;
+
+pub const MAP_SIZE: usize = 128;
+
+/// Pixels of a filled map, row by row. Each is a base color times 4 plus a shade,
+/// base color 0 being the parts nobody has explored
+#[derive(Clone)]
+pub struct MapItem {
+    pub colors: Vec<u8>,
+    pub scale: u8,
+}
+
+impl MapItem {
+    fn new() -> Self {
+        Self {
+            colors: vec![0; MAP_SIZE * MAP_SIZE],
+            scale: 0,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.colors[y * MAP_SIZE + x]
+    }
+}
+
+/// Filled maps by their item damage, built up from ItemData packets.
+/// Every player holding a map gets the same updates, applying them twice is harmless
+pub struct Maps {
+    maps: BTreeMap<i16, Arc<MapItem>>,
+}
+
+impl Maps {
+    pub fn init() -> Self {
+        Self {
+            maps: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, id: i16) -> Option<Arc<MapItem>> {
+        self.maps.get(&id).cloned()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &i16> {
+        self.maps.keys()
+    }
+
+    /// The first byte says what the rest is: 0 is a column of pixels (x, first row, colors
+    /// going down), 1 is the player markers and 2 the scale. Markers aren't drawn
+    pub fn update(&mut self, id: i16, data: &[u8]) {
+        match data {
+            [0, x, y, colors @ ..] => {
+                let (x, y) = (*x as usize, *y as usize);
+                if x >= MAP_SIZE || y >= MAP_SIZE {
+                    log::warning!("Map {} column {} from row {} is out of bounds", id, x, y);
+                    return;
+                }
+                let map = Arc::make_mut(self.maps.entry(id).or_insert_with(|| Arc::new(MapItem::new())));
+                for (row, color) in (y..MAP_SIZE).zip(colors) {
+                    map.colors[row * MAP_SIZE + x] = *color;
+                }
+            },
+            [1, ..] => {},
+            [2, scale, ..] => {
+                Arc::make_mut(self.maps.entry(id).or_insert_with(|| Arc::new(MapItem::new()))).scale = *scale;
+            },
+            _ => log::debug!("Unknown map {} data: {:?}", id, data.first())
+        }
+    }
+}
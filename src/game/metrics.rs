@@ -0,0 +1,80 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tokio::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Counters for the debug overlay, rates are over the last full second.
+/// Packets are counted by each connection's reader task, the game loop only samples them
+pub struct Metrics {
+    // Name, inbound packet counter and its value when the window started, per player
+    received: Vec<(String, Arc<AtomicU64>, u64)>,
+    window_start: Instant,
+    ticks: u32,
+    overruns: u32,
+    slowest: Duration,
+    // Duration of the last tick
+    pub tick_time: Duration,
+    // Results of the last full window
+    pub packet_rates: Vec<(String, u64)>,
+    pub ticks_per_sec: u32,
+    pub overruns_per_sec: u32,
+    pub slowest_tick: Duration,
+}
+
+impl Metrics {
+    pub fn init() -> Self {
+        Self {
+            received: vec![],
+            window_start: Instant::now(),
+            ticks: 0,
+            overruns: 0,
+            slowest: Duration::ZERO,
+            tick_time: Duration::ZERO,
+            packet_rates: vec![],
+            ticks_per_sec: 0,
+            overruns_per_sec: 0,
+            slowest_tick: Duration::ZERO,
+        }
+    }
+
+    pub fn track(&mut self, name: String, counter: Arc<AtomicU64>) {
+        let start = counter.load(Ordering::Relaxed);
+        self.received.push((name, counter, start));
+    }
+
+    /// Records a finished tick, and closes the window once a second has passed
+    pub fn end_tick(&mut self, elapsed: Duration, budget: Duration) {
+        self.tick_time = elapsed;
+        self.ticks += 1;
+        if elapsed > budget {
+            self.overruns += 1;
+        }
+        self.slowest = self.slowest.max(elapsed);
+        if self.window_start.elapsed() < WINDOW {
+            return;
+        }
+        // Scaled in case the window ran long behind a slow tick
+        let seconds = self.window_start.elapsed().as_secs_f64();
+        self.packet_rates = self.received.iter_mut()
+            .map(|(name, counter, start)| {
+                let now = counter.load(Ordering::Relaxed);
+                let rate = ((now - *start) as f64 / seconds).round() as u64;
+                *start = now;
+                (name.clone(), rate)
+            })
+            .collect();
+        self.ticks_per_sec = self.ticks;
+        self.overruns_per_sec = self.overruns;
+        self.slowest_tick = self.slowest;
+        self.window_start = Instant::now();
+        self.ticks = 0;
+        self.overruns = 0;
+        self.slowest = Duration::ZERO;
+    }
+
+    pub fn total_packet_rate(&self) -> u64 {
+        self.packet_rates.iter().map(|(_, rate)| rate).sum()
+    }
+}
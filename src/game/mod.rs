@@ -1,118 +1,613 @@
 use std::sync::Arc;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::error::Error;
 
 use tokio::sync::RwLock;
-use tokio::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::time::{Duration, Instant};
 use crossterm::event::{self, Event, KeyEventKind, KeyCode};
 
+mod commands;
 mod entity;
 mod entity_manager;
+mod events;
+mod maps;
+mod metrics;
 mod player;
+mod stats;
 
 use entity_manager::EntityManager;
+use maps::Maps;
+use metrics::Metrics;
 
 use crate::packets::Packet;
-use crate::world::{World, BlockInfo};
+use crate::world::{World, DEFAULT_RENDER_DEPTH, MAX_RENDER_DEPTH};
 use crate::log;
 use crate::util::pos_add;
+use crate::ui::{StatusWidgetState, SplitView, RenderMode, Palette, DEFAULT_ENTITY_DEPTH};
+
+/// Sees every inbound packet before the player it was sent to handles it
+pub type PacketHook = Box<dyn Fn(&Player, &Packet) + Send + Sync>;
+
+// Where commands come from, embedded clients take none
+enum Input {
+    None,
+    Keys,
+    Lines(UnboundedReceiver<String>),
+}
+
+const UNLOAD_INTERVAL: u64 = 100;
+const UNLOAD_RADIUS: i32 = 12;
+const MAX_ZOOM: i32 = 8;
+pub const DEFAULT_TICK_RATE: u32 = 20;
+// Share of a tick spent on packets, split between the players
+const PACKET_SHARE: f32 = 0.6;
+// Blocks away an entity can still be hit or used
+const REACH: i32 = 4;
 
 pub use {
     entity::Entity,
-    player::Player
+    player::{Player, Effect, DEFAULT_EAT_THRESHOLD, DEFAULT_RESPAWN_DELAY},
+    events::{Events, EventKind},
+    maps::{MapItem, MAP_SIZE}
 };
 
+#[derive(Debug)]
 pub enum GameState {
     World,
     WorldLook,
     Follow,
+    Command,
 }
 
 pub struct GlobalContext {
     pub tick: u64,
+    // Time between ticks, game timers like effects and the clock count ticks
+    pub tick_duration: Duration,
     pub stop: bool,
     pub mode: GameState,
     pub entities: EntityManager,
+    pub events: Events,
     pub world: World,
     pub active_player: Option<Arc<RwLock<Player>>>,
     pub players: Vec<Arc<RwLock<Player>>>,
     pub camera: (i32, i32, i32),
     pub prev_camera: (i32, i32, i32),
     pub camera_update: bool,
+    pub unload_radius: i32,
+    pub dimension: i32,
+    pub follow: Option<i32>,
+    pub command_line: String,
+    pub formation: bool,
+    pub zoom: i32,
+    pub render_depth: i32,
+    pub entity_depth: i32,
+    pub render_mode: RenderMode,
+    // Keep the camera on the active player while in World mode, see sync_camera
+    pub follow_active: bool,
+    // Player name and reason for every kick not yet dismissed
+    pub kicks: Vec<(String, String)>,
+    // Everyone on the server with their ping, sorted by name
+    pub online: BTreeMap<String, u16>,
+    pub show_online: bool,
+    pub show_stats: bool,
+    // Where to write every player's stats on quit, if anywhere
+    pub stats_file: Option<PathBuf>,
+    // World age and time of day in ticks, see advance_time
+    pub age: i64,
+    pub time: i64,
+    // Announced by the server on MC|Brand
+    pub server_brand: Option<String>,
+    // Weather in the dimension on screen
+    pub raining: bool,
+    // Index of the player shown in the second map pane, None when not split
+    pub split: Option<usize>,
+    // Examine panel for the block under the look cursor, cleared by the next key
+    pub examine: Option<Vec<String>>,
+    pub maps: Maps,
+    // Filled map opened with /map, by item damage
+    pub shown_map: Option<i16>,
+    pub metrics: Metrics,
+    pub show_metrics: bool,
+    // Color overrides from palette.json in the resources, shared with the UI
+    pub palette: Arc<Palette>,
+    // Draw blocks and entities with ASCII stand-ins for fonts missing the fancy glyphs
+    pub ascii: bool,
+    pub packet_hook: Option<PacketHook>,
+    input: Input,
 }
 
 impl GlobalContext {
-    pub fn init(resources_root: PathBuf) -> Self {
-        Self {
+    pub fn init(resources_root: PathBuf) -> Result<Self, Box<dyn Error>> {
+        if !resources_root.is_dir() {
+            return Err(format!("Resources directory {} not found", resources_root.display()).into());
+        }
+        let palette = Palette::load(&resources_root.join("palette.json"))?;
+        Ok(Self {
             tick: 0,
+            tick_duration: Duration::from_secs(1) / DEFAULT_TICK_RATE,
             stop: false,
             mode: GameState::World,
-            entities: EntityManager::init(resources_root.clone()),
-            world: World::init(resources_root),
+            entities: EntityManager::init(resources_root.clone())?,
+            events: Events::init(),
+            world: World::init(resources_root)?,
             active_player: None,
             players: vec![],
             camera: (0, 0, 0),
             prev_camera: (0, 0, 0),
             camera_update: true,
-        }
+            unload_radius: UNLOAD_RADIUS,
+            dimension: 0,
+            follow: None,
+            command_line: String::new(),
+            formation: false,
+            zoom: 1,
+            render_depth: DEFAULT_RENDER_DEPTH,
+            entity_depth: DEFAULT_ENTITY_DEPTH,
+            render_mode: RenderMode::TopDown,
+            follow_active: true,
+            kicks: vec![],
+            raining: false,
+            examine: None,
+            maps: Maps::init(),
+            shown_map: None,
+            split: None,
+            server_brand: None,
+            age: 0,
+            time: 0,
+            online: BTreeMap::new(),
+            show_online: false,
+            show_stats: false,
+            stats_file: None,
+            metrics: Metrics::init(),
+            show_metrics: false,
+            palette: Arc::new(palette),
+            ascii: false,
+            packet_hook: None,
+            input: Input::None,
+        })
     }
 
     pub async fn tick(&mut self) {
+        let started = Instant::now();
         if self.tick == std::u64::MAX {
             log::info!("How did you get here?");
             self.tick = 0;
         }
         self.tick += 1;
         self.world.update = false;
+        self.world.breaking_update = false;
+        self.world.explosions.clear();
         self.camera_update = false;
+        self.world.expire_breaks(self.tick);
+        self.world.expire_block_actions(self.tick);
+        self.world.apply_decoded();
+        self.advance_time();
 
         self.entities.tick();
+        self.events.tick();
+        let budget = self.tick_duration.mul_f32(PACKET_SHARE) / self.players.len().max(1) as u32;
         for player in self.players.clone().iter() {
             {
                 let mut player = player.write().await;
                 if !player.stop {
-                    player.tick(self).await;
+                    player.tick(self, budget).await;
                 }
             }
             self.entities.check_orphaned(&self.players).await;
         }
 
-        if event::poll(Duration::from_millis(1)).unwrap() {
-            if let Ok(Event::Key(key)) = event::read() {
-                if key.kind == KeyEventKind::Press {
-                    handle_input(key.code, self).await;
+        if let GameState::Follow = self.mode {
+            self.update_follow().await;
+        }
+        self.sync_camera().await;
+
+        if self.tick % UNLOAD_INTERVAL == 0 {
+            self.unload_far_chunks().await;
+        }
+
+        match &mut self.input {
+            Input::Lines(input) => {
+                let mut lines = vec![];
+                while let Ok(line) = input.try_recv() {
+                    lines.push(line);
+                }
+                for line in lines {
+                    commands::run(&line, self).await;
+                }
+            },
+            Input::Keys => {
+                if event::poll(Duration::from_millis(1)).unwrap() {
+                    if let Ok(Event::Key(key)) = event::read() {
+                        if key.kind == KeyEventKind::Press {
+                            handle_input(key.code, self).await;
+                        }
+                    }
+                }
+            },
+            Input::None => {}
+        }
+
+        let elapsed = started.elapsed();
+        self.metrics.end_tick(elapsed, self.tick_duration);
+        if elapsed > self.tick_duration {
+            log::debug!("Tick {} overran its budget: {}ms", self.tick, elapsed.as_millis());
+        }
+    }
+
+    /// Takes key presses from the terminal
+    pub fn read_keys(&mut self) {
+        self.input = Input::Keys;
+    }
+
+    /// Takes commands line by line from stdin instead of reading keys from the terminal
+    pub fn read_stdin(&mut self) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::task::spawn(async move {
+            let mut lines = BufReader::new(tokio::io::stdin()).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if tx.send(line).is_err() {
+                    break;
                 }
             }
+        });
+        self.input = Input::Lines(rx);
+    }
+
+    pub async fn run_command(&mut self, line: &str) {
+        commands::run(line, self).await;
+    }
+
+    /// Says goodbye for every player and stops the game loop
+    pub async fn quit(&mut self) {
+        for player in self.players.iter() {
+            let mut player = player.write().await;
+            if let Err(e) = player.connection.send(Packet::Disconnect {
+                reason: "I'm done".to_string()
+             }).await {
+                log::warning!("Failed to say goodbye for {}: {}", player.name, e);
+            }
+            player.shutdown();
         }
+        if let Some(path) = self.stats_file.as_ref() {
+            match self.save_stats(path).await {
+                Ok(()) => log::info!("Saved stats to {}", path.display()),
+                Err(e) => log::error!("Failed to save stats to {}: {}", path.display(), e)
+            }
+        }
+        self.stop = true;
+    }
+
+    /// Writes the stats of this session as {player: [{id, name, value}]}
+    async fn save_stats(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let mut data = json::JsonValue::new_object();
+        for player in self.players.iter() {
+            let player = player.read().await;
+            let mut ids: Vec<&i32> = player.stats.keys().collect();
+            ids.sort();
+            let mut stats = json::JsonValue::new_array();
+            for id in ids {
+                stats.push(json::object! {
+                    id: *id,
+                    name: stats::stat_name(*id, &self.world),
+                    value: player.stats[id]
+                })?;
+            }
+            data[player.name.as_str()] = stats;
+        }
+        std::fs::write(path, data.pretty(4))?;
+        Ok(())
+    }
+
+    /// Debug overlay lines, label on the left and value on the right
+    fn metrics_lines(&self) -> Vec<(String, String)> {
+        let metrics = &self.metrics;
+        let (columns, chunks) = self.world.loaded_chunks();
+        let mut lines = vec![
+            ("Tick".to_string(), format!("{:.1}ms", metrics.tick_time.as_secs_f64() * 1000.)),
+            ("Slowest tick".to_string(), format!("{:.1}ms", metrics.slowest_tick.as_secs_f64() * 1000.)),
+            ("Ticks/s".to_string(), format!("{} ({} over)", metrics.ticks_per_sec, metrics.overruns_per_sec)),
+            ("Chunks".to_string(), format!("{} in {} columns", chunks, columns)),
+            ("Entities".to_string(), self.entities.entities.len().to_string()),
+            ("Packets/s".to_string(), metrics.total_packet_rate().to_string()),
+        ];
+        lines.extend(metrics.packet_rates.iter()
+            .map(|(name, rate)| (format!("  {}", name), rate.to_string())));
+        lines
+    }
+
+    /// Named and formatted stats of the active player, sorted by id
+    async fn stats_lines(&self) -> Option<Vec<(String, String)>> {
+        let player = self.active_player.as_ref()?.read().await;
+        let mut stats: Vec<(&i32, &i64)> = player.stats.iter().collect();
+        stats.sort();
+        Some(stats.into_iter()
+            .map(|(id, value)| (stats::stat_name(*id, &self.world), stats::stat_value(*id, *value)))
+            .collect())
     }
 
     pub async fn update_render(&self, ui_state: &Arc<crate::ui::UiState>) {
         if let Some(player) = self.active_player.as_ref() {
-            let (hp, food) = {
+            let (hp, food, xp_bar, level) = {
                 let player = player.read().await;
-                (player.hp, player.food)
+                (player.hp, player.food, player.xp_bar, player.level)
             };
-            ui_state.set_hp(hp as u16).await;
-            ui_state.set_food(food as u16).await;
+            ui_state.set_hp(hp as u16);
+            ui_state.set_food(food as u16);
+            ui_state.set_xp(xp_bar, level as u16);
         }
-        if self.world.update || self.camera_update {
+        if let Some(player) = self.active_player.as_ref() {
+            let player = player.read().await;
+            ui_state.set_player_name(player.name.clone());
+            ui_state.set_effects(player.effects.clone());
+            ui_state.set_enchanting(player.enchanting.as_ref().map(|window| (window.costs, player.level)));
+            let camera = match self.mode {
+                GameState::WorldLook => Some(self.camera),
+                _ => None
+            };
+            ui_state.set_status(StatusWidgetState {
+                mode: format!("{:?}", self.mode),
+                player_pos: player.world_pos(),
+                facing: player.facing(),
+                raining: self.raining,
+                brand: self.server_brand.clone(),
+                sneaking: player.sneaking,
+                sprinting: player.sprinting,
+                sleeping: player.sleeping.is_some(),
+                camera
+            });
+        }
+        let prompt = match self.mode {
+            GameState::Command => Some(self.command_line.clone()),
+            _ => None
+        };
+        ui_state.set_prompt(prompt);
+        ui_state.set_kicks(self.kicks.clone());
+        ui_state.set_examine(self.examine.clone());
+        ui_state.set_clock(self.age, self.time);
+        let online = self.show_online
+            .then(|| self.online.iter().map(|(name, ping)| (name.clone(), *ping)).collect());
+        ui_state.set_online(online);
+        let stats = match self.show_stats {
+            true => self.stats_lines().await,
+            false => None
+        };
+        ui_state.set_stats(stats);
+        ui_state.set_metrics(self.show_metrics.then(|| self.metrics_lines()));
+        ui_state.set_map_item(self.shown_map.and_then(|id| Some((id, self.maps.get(id)?))));
+        if self.events.update {
+            ui_state.set_events(&self.events);
+        }
+        ui_state.update_split(self, self.split_view().await).await;
+        if self.world.update || self.camera_update || ui_state.world_resized() {
             ui_state.update_world(&self).await;
         }
-        ui_state.update_entities(&self).await;
+        ui_state.update_entities(&self);
+        ui_state.publish();
+    }
+
+    /// Title and camera of the split pane, the camera is None if that player is in another dimension
+    async fn split_view(&self) -> Option<SplitView> {
+        let player = self.players.get(self.split?)?.read().await;
+        let camera = (player.dimension == self.dimension)
+            .then(|| player.camera.unwrap_or(player.camera_pos()));
+        let title = match camera {
+            Some(_) => player.name.clone(),
+            None => format!("{} (other dimension)", player.name)
+        };
+        Some(SplitView { title, camera })
+    }
+
+    /// Splits the map to also show the next running player after the active one, or closes the split
+    pub async fn toggle_split(&mut self) {
+        if self.split.take().is_some() {
+            return;
+        }
+        let count = self.players.len();
+        let current = self.active_player.as_ref()
+            .and_then(|active| self.players.iter().position(|p| Arc::ptr_eq(p, active)))
+            .unwrap_or(0);
+        for i in 1..count {
+            let index = (current + i) % count;
+            if !self.players[index].read().await.stop {
+                self.split = Some(index);
+                return;
+            }
+        }
+        log::warning!("No other player to split the map with");
+    }
+
+    /// Camera policy, applied once per tick and after every manual move:
+    /// - World (and Command, typed over it): the camera sits on the active player
+    ///   whenever `follow_active` is on, whatever moved the player
+    /// - WorldLook: the camera is detached and moved by hand
+    /// - Follow: the camera tracks an entity, see update_follow
+    /// Leaving WorldLook or Follow goes back to World, so the next sync re-centers
+    async fn sync_camera(&mut self) {
+        if !self.follow_active || !matches!(self.mode, GameState::World | GameState::Command) {
+            return;
+        }
+        if let Some(player) = self.active_player.as_ref() {
+            let cam_pos = player.read().await.camera_pos();
+            if cam_pos != self.camera {
+                self.set_cam(cam_pos);
+            }
+        }
+    }
+
+    /// Whether the camera is somewhere other than on the active player's body
+    fn camera_detached(&self) -> bool {
+        match self.mode {
+            GameState::WorldLook => true,
+            GameState::World | GameState::Command => !self.follow_active,
+            _ => false
+        }
+    }
+
+    pub async fn toggle_follow_active(&mut self) {
+        self.follow_active = !self.follow_active;
+        log::info!("Camera follows the active player: {}", self.follow_active);
+        self.sync_camera().await;
+    }
+
+    /// Keeps the camera on the followed entity, or returns to the active player once it's gone
+    async fn update_follow(&mut self) {
+        let target = self.follow
+            .and_then(|eid| self.entities.entities.iter().find(|e| e.id == eid))
+            .map(|e| pos_add(e.world_pos(), (0, 1, 0)));
+        match target {
+            Some(cam_pos) => {
+                if cam_pos != self.camera {
+                    self.set_cam(cam_pos);
+                }
+            },
+            None => {
+                log::info!("Lost follow target {:?}", self.follow);
+                self.stop_follow().await;
+            }
+        }
+    }
+
+    pub fn start_follow(&mut self) {
+        let camera = self.camera;
+        let nearest = self.entities.entities.iter()
+            .min_by_key(|e| {
+                let pos = e.world_pos();
+                (pos.0 - camera.0).pow(2) + (pos.1 - camera.1).pow(2) + (pos.2 - camera.2).pow(2)
+            })
+            .map(|e| e.id);
+        if nearest.is_none() {
+            log::warning!("Nothing to follow");
+            return;
+        }
+        self.follow = nearest;
+        self.mode = GameState::Follow;
+        log::info!("Following entity {}", nearest.unwrap());
+    }
+
+    /// Attacks or interacts with the entity closest to the active player
+    pub async fn use_nearest(&mut self, attack: bool) {
+        let Some(player) = self.active_player.as_ref() else {
+            return;
+        };
+        let mut player = player.write().await;
+        let pos = player.world_pos();
+        let target = self.entities.entities.iter()
+            .filter(|e| e.id != player.eid)
+            .map(|e| {
+                let target = e.world_pos();
+                let distance = (target.0 - pos.0).pow(2) + (target.1 - pos.1).pow(2) + (target.2 - pos.2).pow(2);
+                (distance, e)
+            })
+            .filter(|(distance, _)| *distance <= REACH * REACH)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, e)| (e.id, e.display_name().to_string()));
+        let Some((eid, name)) = target else {
+            log::warning!("Nothing in reach of {}", player.name);
+            return;
+        };
+        let action = if attack { "attack" } else { "interact with" };
+        match player.use_entity(eid, attack).await {
+            Ok(_) => log::info!("{} tries to {} {} ({})", player.name, action, name, eid),
+            Err(e) => log::error!("{} failed to {} {} ({}): {}", player.name, action, name, eid, e),
+        }
+    }
+
+    /// Logs name and equipment of the entities in the column under the camera
+    pub fn examine_entities(&self) {
+        let mut found = false;
+        for entity in self.entities.entities.iter() {
+            let pos = entity.world_pos();
+            if pos.0 != self.camera.0 || pos.2 != self.camera.2 {
+                continue;
+            }
+            found = true;
+            log::info!("Examine entity {} at {:?}: {}, {}",
+                entity.id, pos, entity.display_name(), entity.equipment_summary());
+        }
+        if !found {
+            log::info!("No entities at {} {}", self.camera.0, self.camera.2);
+        }
+    }
+
+    pub fn follow_entity(&mut self, eid: i32) {
+        if !self.entities.entities.iter().any(|e| e.id == eid) {
+            log::warning!("No entity {} to follow", eid);
+            return;
+        }
+        self.follow = Some(eid);
+        self.mode = GameState::Follow;
+        log::info!("Following entity {}", eid);
+    }
+
+    /// Moves the follow target `step` entities along, ordered by entity id
+    pub fn cycle_follow(&mut self, step: isize) {
+        let mut ids: Vec<i32> = self.entities.entities.iter().map(|e| e.id).collect();
+        if ids.is_empty() {
+            return;
+        }
+        ids.sort_unstable();
+        let current = self.follow
+            .and_then(|eid| ids.iter().position(|id| *id == eid))
+            .unwrap_or(0) as isize;
+        let next = (current + step).rem_euclid(ids.len() as isize) as usize;
+        self.follow = Some(ids[next]);
+        log::info!("Following entity {}", ids[next]);
+    }
+
+    pub async fn stop_follow(&mut self) {
+        self.follow = None;
+        self.mode = GameState::World;
+        if let Some(player) = self.active_player.as_ref() {
+            let cam_pos = player.read().await.camera_pos();
+            self.set_cam(cam_pos);
+        }
+    }
+
+    async fn unload_far_chunks(&mut self) {
+        let mut positions = vec![];
+        for player in self.players.iter() {
+            let player = player.read().await;
+            if !player.stop {
+                positions.push(player.world_pos());
+            }
+        }
+        if positions.is_empty() {
+            return;
+        }
+        let freed = self.world.unload_far(&positions, self.unload_radius);
+        if freed > 0 {
+            log::info!("Unloaded {} far chunk columns", freed);
+        }
     }
 
     pub async fn add_player(&mut self, player: Arc<RwLock<Player>>, set_active: bool) {
+        {
+            let player = player.read().await;
+            self.metrics.track(player.name.clone(), player.connection.received_counter());
+        }
         self.players.push(Arc::clone(&player));
         if set_active {
             self.set_active_player(self.players.len()-1).await;
         }
     }
 
+    /// Switches focus, saving the camera of the previous player and restoring the one of the next
     pub async fn set_active_player(&mut self, index: usize) {
         if index < self.players.len() {
+            let detached = self.camera_detached();
             if let Some(previous_player) = self.active_player.as_mut() {
-                previous_player.write().await.is_focused = false;
+                let mut previous_player = previous_player.write().await;
+                previous_player.is_focused = false;
+                previous_player.camera = detached.then_some(self.camera);
             }
-            let cam_pos = self.players[index].read().await.camera_pos();
+            let (cam_pos, dimension) = {
+                let player = self.players[index].read().await;
+                (player.camera.unwrap_or(player.camera_pos()), player.dimension)
+            };
+            self.set_dimension(dimension);
             self.set_cam(cam_pos);
             let active_player = &self.players[index];
             active_player.write().await.is_focused = true;
@@ -122,6 +617,87 @@ impl GlobalContext {
         }
     }
 
+    /// Moves focus `step` players along, skipping the stopped ones
+    pub async fn cycle_active_player(&mut self, step: isize) {
+        let count = self.players.len() as isize;
+        let current = self.active_player.as_ref()
+            .and_then(|active| self.players.iter().position(|p| Arc::ptr_eq(p, active)))
+            .unwrap_or(0) as isize;
+        for i in 1..=count {
+            let index = (current + step * i).rem_euclid(count) as usize;
+            if !self.players[index].read().await.stop {
+                self.set_active_player(index).await;
+                return;
+            }
+        }
+    }
+
+    /// Switches the rendered dimension, dropping the terrain of the previous one
+    pub fn set_dimension(&mut self, dimension: i32) {
+        if self.dimension != dimension {
+            log::info!("Switching dimension {} -> {}", self.dimension, dimension);
+            self.dimension = dimension;
+            self.world.clear();
+            // The server resends rain if it's raining where we land
+            self.raining = false;
+        }
+    }
+
+    /// The server only sends the time every second, count the ticks in between
+    fn advance_time(&mut self) {
+        self.age += 1;
+        // A stopped daylight cycle is sent as negative time
+        if self.time >= 0 {
+            self.time += 1;
+        }
+    }
+
+    pub fn set_raining(&mut self, raining: bool) {
+        if raining != self.raining {
+            self.raining = raining;
+            log::info!("{}", if raining { "It started raining" } else { "The rain stopped" });
+        }
+    }
+
+    pub fn set_zoom(&mut self, zoom: i32) {
+        let zoom = zoom.clamp(1, MAX_ZOOM);
+        if zoom != self.zoom {
+            self.zoom = zoom;
+            self.camera_update = true;
+            log::info!("Zoom level {}", zoom);
+        }
+    }
+
+    /// How many blocks below the camera the terrain is rendered
+    pub fn set_render_depth(&mut self, depth: i32) {
+        let depth = depth.clamp(1, MAX_RENDER_DEPTH);
+        if depth != self.render_depth {
+            self.render_depth = depth;
+            self.world.clear_render_cache();
+            self.world.update = true;
+            log::info!("Render depth {}", depth);
+        }
+    }
+
+    /// How many blocks above and below the camera entities are shown
+    pub fn set_entity_depth(&mut self, depth: i32) {
+        let depth = depth.max(1);
+        if depth != self.entity_depth {
+            self.entity_depth = depth;
+            self.camera_update = true;
+            log::info!("Entity depth {}", depth);
+        }
+    }
+
+    pub fn toggle_render_mode(&mut self) {
+        self.render_mode = match self.render_mode {
+            RenderMode::TopDown => RenderMode::Side,
+            RenderMode::Side => RenderMode::TopDown,
+        };
+        self.camera_update = true;
+        log::info!("{:?} view", self.render_mode);
+    }
+
     pub fn move_cam(&mut self, delta: (i32, i32, i32)) {
         self.set_cam((
             self.camera.0 + delta.0,
@@ -137,30 +713,116 @@ impl GlobalContext {
     }
 
     pub async fn move_player(&mut self, delta: (i32, i32, i32)) {
+        if self.formation {
+            self.move_formation(delta).await;
+            return;
+        }
         match &self.active_player {
             None => {
                 return;
             },
             Some(p) => {
-                let cam_delta = p.write().await.move_by(&self.world, delta);
-                self.move_cam(cam_delta);
+                p.write().await.move_by(&self.world, delta);
             }
         }
+        self.sync_camera().await;
+    }
+
+    pub async fn select_slot(&mut self, slot_id: i16) {
+        if let Some(player) = self.active_player.as_ref() {
+            let mut player = player.write().await;
+            if let Err(e) = player.select_slot(slot_id).await {
+                log::error!("{} failed to switch to slot {}: {}", player.name, slot_id + 1, e);
+            }
+        }
+    }
+
+    pub async fn toggle_sneaking(&mut self) {
+        if let Some(player) = self.active_player.as_ref() {
+            let mut player = player.write().await;
+            let sneaking = !player.sneaking;
+            if let Err(e) = player.set_sneaking(sneaking).await {
+                log::error!("{} failed to toggle sneaking: {}", player.name, e);
+            }
+        }
+    }
+
+    pub async fn toggle_sprinting(&mut self) {
+        if let Some(player) = self.active_player.as_ref() {
+            let mut player = player.write().await;
+            let sprinting = !player.sprinting;
+            if let Err(e) = player.set_sprinting(sprinting).await {
+                log::error!("{} failed to toggle sprinting: {}", player.name, e);
+            }
+        }
+    }
+
+    /// Moves every running player by the same delta, blocked ones just stay put
+    async fn move_formation(&mut self, delta: (i32, i32, i32)) {
+        for player in self.players.iter() {
+            let mut player = player.write().await;
+            if player.stop {
+                continue;
+            }
+            player.move_by(&self.world, delta);
+        }
+        self.sync_camera().await;
+    }
+
+    pub fn toggle_formation(&mut self) {
+        self.formation = !self.formation;
+        if self.formation {
+            log::info!("Formation control: moving all players");
+        } else {
+            log::info!("Single control: moving the active player");
+        }
     }
 }
 
 pub async fn handle_input(key: KeyCode, ctx: &mut GlobalContext) {
+    // Debug overlay toggles in every mode, it's never typed into the prompt
+    if key == KeyCode::F(3) {
+        ctx.show_metrics = !ctx.show_metrics;
+        return;
+    }
     match ctx.mode {
         GameState::World => handle_input_world(key, ctx).await,
         GameState::WorldLook => handle_input_world_look(key, ctx).await,
         GameState::Follow => handle_input_follow(key, ctx).await,
+        GameState::Command => handle_input_command(key, ctx).await,
+    }
+}
+
+async fn handle_input_command(key: KeyCode, ctx: &mut GlobalContext) {
+    match key {
+        KeyCode::Esc => {
+            ctx.command_line.clear();
+            ctx.mode = GameState::World;
+        },
+        KeyCode::Backspace => {
+            ctx.command_line.pop();
+        },
+        KeyCode::Enter => {
+            let line = std::mem::take(&mut ctx.command_line);
+            ctx.mode = GameState::World;
+            commands::run(&line, ctx).await;
+        },
+        KeyCode::Char(c) => ctx.command_line.push(c),
+        _ => {}
     }
 }
 
 async fn handle_input_follow(key: KeyCode, ctx: &mut GlobalContext) {
+    match key {
+        KeyCode::Char('q') => ctx.stop_follow().await,
+        KeyCode::Char('j') => ctx.cycle_follow(-1),
+        KeyCode::Char('k') => ctx.cycle_follow(1),
+        _ => {}
+    }
 }
 
 async fn handle_input_world_look(key: KeyCode, ctx: &mut GlobalContext) {
+    ctx.examine = None;
     match key {
         KeyCode::Char('q') => {
             if let Some(player) = ctx.active_player.as_ref() {
@@ -169,10 +831,8 @@ async fn handle_input_world_look(key: KeyCode, ctx: &mut GlobalContext) {
             }
             ctx.mode = GameState::World;
         },
-        KeyCode::Char('e') => {
-            let block = ctx.world.get_block(ctx.camera);
-            log::info!("Examine {:?}: {:?}", ctx.camera, block);
-        },
+        KeyCode::Char('e') => ctx.examine = Some(ctx.world.describe_block(ctx.camera)),
+        KeyCode::Char('i') => ctx.examine_entities(),
         KeyCode::Char('y') => ctx.move_cam((-1, 0, -1)),
         KeyCode::Char('u') => ctx.move_cam((1, 0, -1)),
         KeyCode::Char('b') => ctx.move_cam((-1, 0, 1)),
@@ -189,17 +849,34 @@ async fn handle_input_world_look(key: KeyCode, ctx: &mut GlobalContext) {
 
 async fn handle_input_world(key: KeyCode, ctx: &mut GlobalContext) {
     match key {
-        KeyCode::Char('q') => {
-            for player in ctx.players.iter() {
-                player.read().await.connection.send(Packet::Disconnect {
-                    reason: "I'm done".to_string()
-                 }).await;
-            }
-            ctx.stop = true;
-        },
+        KeyCode::Char('q') => ctx.quit().await,
         KeyCode::Char('x') => {
             ctx.mode = GameState::WorldLook;
         },
+        KeyCode::Enter => ctx.kicks.clear(),
+        KeyCode::Char('w') => ctx.start_follow(),
+        KeyCode::Char('f') => ctx.use_nearest(true).await,
+        KeyCode::Char('r') => ctx.use_nearest(false).await,
+        KeyCode::Char('g') => ctx.toggle_formation(),
+        KeyCode::Char('c') => ctx.toggle_follow_active().await,
+        KeyCode::Char('p') => ctx.show_online = !ctx.show_online,
+        KeyCode::Char('t') => ctx.show_stats = !ctx.show_stats,
+        KeyCode::Char('s') => ctx.toggle_sneaking().await,
+        KeyCode::Char('S') => ctx.toggle_sprinting().await,
+        KeyCode::Char('m') => ctx.toggle_split().await,
+        KeyCode::Tab => ctx.cycle_active_player(1).await,
+        KeyCode::Char('v') => ctx.toggle_render_mode(),
+        KeyCode::Char('+') => ctx.set_zoom(ctx.zoom + 1),
+        KeyCode::Char('-') => ctx.set_zoom(ctx.zoom - 1),
+        KeyCode::Char('[') => ctx.set_render_depth(ctx.render_depth - 1),
+        KeyCode::Char(']') => ctx.set_render_depth(ctx.render_depth + 1),
+        KeyCode::Char('{') => ctx.set_entity_depth(ctx.entity_depth - 1),
+        KeyCode::Char('}') => ctx.set_entity_depth(ctx.entity_depth + 1),
+        KeyCode::BackTab => ctx.cycle_active_player(-1).await,
+        KeyCode::Char('/') => {
+            ctx.command_line = String::from("/");
+            ctx.mode = GameState::Command;
+        },
         KeyCode::Char('y') => ctx.move_player((-1, 0, -1)).await,
         KeyCode::Char('u') => ctx.move_player((1, 0, -1)).await,
         KeyCode::Char('b') => ctx.move_player((-1, 0, 1)).await,
@@ -210,6 +887,7 @@ async fn handle_input_world(key: KeyCode, ctx: &mut GlobalContext) {
         KeyCode::Char('l') => ctx.move_player((1, 0, 0)).await,
         KeyCode::Char('<') => ctx.move_player((0, 1, 0)).await,
         KeyCode::Char('>') => ctx.move_player((0, -1, 0)).await,
+        KeyCode::Char(c @ '1'..='9') => ctx.select_slot(c as i16 - '1' as i16).await,
         _ => {}
     }
 }
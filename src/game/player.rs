@@ -1,89 +1,195 @@
-use std::collections::HashSet;
+use std::collections::{HashSet, HashMap, VecDeque};
+use std::error::Error;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
-use tokio::time::{interval, Duration};
+use tokio::time::{interval, Duration, Instant};
 use tokio::sync::RwLock;
 
-use crate::packets::Packet;
-use crate::net::Connection;
+use crate::packets::{Packet, Slot};
+use crate::net::{Connection, ConnectPolicy, Observers};
 use crate::log;
-use crate::util::pos_add;
+use crate::util::{pos_add, plain_text, hex_preview, chunk_coords};
 use crate::world::World;
 
-use super::{GlobalContext, GameState};
+use super::{GlobalContext, GameState, EventKind};
 
 static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
 
+const MAX_PATH_NODES: usize = 4096;
+// Blocks per tick, falling speeds up by one block each tick until this
+const MAX_FALL_SPEED: i32 = 3;
+const DIRECTIONS: [(i32, i32, i32); 8] = [
+    (-1, 0, -1), (0, 0, -1), (1, 0, -1),
+    (-1, 0, 0), (1, 0, 0),
+    (-1, 0, 1), (0, 0, 1), (1, 0, 1),
+];
+
+pub const DEFAULT_EAT_THRESHOLD: i16 = 14;
+// Eating takes 32 ticks server side, leave some slack before trying again
+const EAT_COOLDOWN: u64 = 40;
+const HOTBAR_START: i16 = 36;
+pub const DEFAULT_RESPAWN_DELAY: u64 = 20;
+// Dying this many times within the window delays every further respawn
+const DEATH_LIMIT: usize = 3;
+const DEATH_WINDOW: u64 = 1200;
+const RESPAWN_BACKOFF: u64 = 600;
+const FOOD_IDS: [i16; 22] = [
+    260, 282, 297, 319, 320, 322, 349, 350, 357, 360, 363,
+    364, 365, 366, 367, 375, 391, 392, 393, 394, 396, 400,
+];
+
+// Bytes of an unknown plugin message payload worth logging
+const PLUGIN_PREVIEW_BYTES: usize = 16;
+const GAME_MODES: [&str; 3] = ["survival", "creative", "adventure"];
+// EntityAction ids
+const ACTION_SNEAK: u8 = 1;
+const ACTION_UNSNEAK: u8 = 2;
+const ACTION_LEAVE_BED: u8 = 3;
+const ACTION_SPRINT: u8 = 4;
+const ACTION_UNSPRINT: u8 = 5;
+// OpenWindow type of an enchantment table
+const ENCHANT_WINDOW: u8 = 4;
+
+#[derive(Clone)]
+pub struct Effect {
+    pub id: u8,
+    pub amplifier: u8,
+    pub remaining: i16,
+}
+
+/// Open enchantment table, window properties 0 to 2 are the level costs of its options
+#[derive(Clone, Debug)]
+pub struct EnchantWindow {
+    pub window_id: u8,
+    // 0 while there's no item to enchant in the table
+    pub costs: [i16; 3],
+}
+
 pub struct Player {
     pub connection: Connection,
     pub id: usize,
+    pub eid: i32,
     pub name: String,
     pos: (f64, f64, f64),
     pos_update: bool,
     stance: f64,
     look: (f32, f32),
+    on_ground: bool,
+    fall_speed: i32,
+    pub sneaking: bool,
+    pub sprinting: bool,
+    // Bed we're in, set once the server confirms with UseBed
+    pub sleeping: Option<(i32, i32, i32)>,
     pub hp: i16,
     pub food: i16,
     pub saturation: f32,
+    pub effects: Vec<Effect>,
+    pub xp_bar: f32,
+    pub level: i16,
+    pub eat_threshold: i16,
+    pub game_mode: u8,
+    // (id, count, damage) of every hotbar slot
+    hotbar: [Option<(i16, i8, i16)>; 9],
+    held_slot: i16,
+    eat_cooldown: u64,
+    // Window clicks waiting on a ConfirmTransaction, by action number
+    next_action: u16,
+    pending_clicks: HashMap<u16, (u8, u16)>,
+    pub enchanting: Option<EnchantWindow>,
+    pub respawn_delay: u64,
+    pub stay_dead: bool,
+    pub last_death_pos: Option<(i32, i32, i32)>,
+    dead: bool,
+    deaths: VecDeque<u64>,
+    respawn_at: Option<u64>,
     pub stop: bool,
     pub is_focused: bool,
+    pub dimension: i32,
     pub known_entities: HashSet<i32>,
-    pos_update_loop: Option<tokio::task::JoinHandle<()>>
+    // Statistic id to its total this session, see stats::stat_name
+    pub stats: HashMap<i32, i64>,
+    // Where the camera was left while this player was active, None when it was on the body
+    pub camera: Option<(i32, i32, i32)>,
+    goto_target: Option<(i32, i32, i32)>,
+    path: VecDeque<(i32, i32, i32)>,
+    pos_update_loop: Option<tokio::task::JoinHandle<()>>,
+    pos_update_stop: Arc<AtomicBool>,
 }
 
 impl Player {
     pub async fn start(
         host: &str,
         port: i32,
-        name: String
+        name: String,
+        policy: ConnectPolicy,
+        observers: Observers
         ) -> Result<Arc<RwLock<Player>>, Box<dyn std::error::Error>>
     {
-        let connection = Connection::connect_offline(host, port, name.as_str()).await?;
+        let connection = Connection::connect(host, port, name.as_str(), policy, observers).await?;
+        Ok(Self::with_connection(connection, name).await)
+    }
+
+    pub(crate) async fn with_connection(connection: Connection, name: String) -> Arc<RwLock<Player>> {
         // TODO obtain position and initial status from connection
+        let pos_update_stop = Arc::new(AtomicBool::new(false));
         let player = Arc::new(RwLock::new(Player {
             connection,
             name: name.to_string(),
             id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            eid: -1,
             stop: false,
             pos: (0., 0., 0.),
             pos_update: false,
             stance: 0.,
             look: (0., 0.),
+            on_ground: true,
+            fall_speed: 1,
+            sneaking: false,
+            sprinting: false,
+            sleeping: None,
             hp: 0,
             food: 0,
             saturation: 0.,
+            effects: vec![],
+            xp_bar: 0.,
+            level: 0,
+            eat_threshold: DEFAULT_EAT_THRESHOLD,
+            game_mode: 0,
+            hotbar: [None; 9],
+            held_slot: 0,
+            eat_cooldown: 0,
+            next_action: 1,
+            pending_clicks: HashMap::new(),
+            enchanting: None,
+            respawn_delay: DEFAULT_RESPAWN_DELAY,
+            stay_dead: false,
+            last_death_pos: None,
+            dead: false,
+            deaths: VecDeque::new(),
+            respawn_at: None,
             is_focused: false,
+            dimension: 0,
             pos_update_loop: None,
             known_entities: HashSet::new(),
+            stats: HashMap::new(),
+            camera: None,
+            goto_target: None,
+            path: VecDeque::new(),
+            pos_update_stop: Arc::clone(&pos_update_stop),
         }));
-        player.write().await.pos_update_loop = Some(Self::position_update_loop(Arc::clone(&player)));
-        Ok(player)
+        player.write().await.pos_update_loop = Some(Self::position_update_loop(Arc::clone(&player), pos_update_stop));
+        player
     }
 
     pub fn move_by(&mut self, world: &World, delta: (i32, i32, i32)) -> (i32, i32, i32) {
-        let world_pos = self.world_pos();
-        let mut delta = delta;
-        if delta.0 != 0 || delta.2 != 0 {
-            let next = pos_add(world_pos, delta);
-            // if lower target block is solid, check for two above and ascend if possible
-            if let Some(block) = world.get_block_info(next) && block.is_solid {
-                let bottom = pos_add(next, (0, 1, 0));
-                let top = pos_add(bottom, (0, 1, 0));
-                if world.get_block_info(bottom).unwrap().is_solid
-                   || world.get_block_info(top).unwrap().is_solid
-                {
-                    return (0, 0, 0);
-                } else {
-                    delta = pos_add(delta, (0, 1, 0));
-                }
-            // if not, check if block below is not solid too and descent
-            } else if let Some(block) = world.get_block_info(pos_add(next, (0, -1, 0))) && !block.is_solid {
-                let top = pos_add(next, (0, 1, 0));
-                if !world.get_block_info(top).unwrap().is_solid {
-                    delta = pos_add(delta, (0, -1, 0));
-                }
-            }
+        let delta = match step_delta(world, self.world_pos(), delta) {
+            Some(delta) => delta,
+            None => return (0, 0, 0)
+        };
+        // Sneaking keeps the player from walking off edges, same as vanilla
+        if self.sneaking && delta.1 < 0 {
+            return (0, 0, 0);
         }
 
         let yaw = match (delta.0, delta.2) {
@@ -104,6 +210,79 @@ impl Player {
         delta
     }
 
+    /// Plans a path to `target` and starts walking it, one step per tick
+    pub fn goto(&mut self, world: &World, target: (i32, i32, i32)) -> bool {
+        match find_path(world, self.world_pos(), target) {
+            Some(path) => {
+                log::info!("{} walking to {:?}, {} steps", self.name, target, path.len());
+                self.goto_target = Some(target);
+                self.path = path;
+                true
+            },
+            None => {
+                log::warning!("{} found no path to {:?}", self.name, target);
+                self.stop_goto();
+                false
+            }
+        }
+    }
+
+    pub fn stop_goto(&mut self) {
+        self.goto_target = None;
+        self.path.clear();
+    }
+
+    fn step_path(&mut self, ctx: &mut GlobalContext) {
+        let target = match self.goto_target {
+            Some(target) => target,
+            None => return
+        };
+        let next = match self.path.pop_front() {
+            Some(next) => next,
+            None => {
+                log::info!("{} reached {:?}", self.name, target);
+                self.stop_goto();
+                return;
+            }
+        };
+        let from = self.world_pos();
+        self.move_by(&ctx.world, (next.0 - from.0, 0, next.2 - from.2));
+        // The world changed under us, plan again from where we ended up
+        if self.world_pos() != next {
+            log::debug!("{} got off the path at {:?}, replanning", self.name, self.world_pos());
+            self.goto(&ctx.world, target);
+        }
+    }
+
+    /// Local prediction of falling, the server corrects us with PlayerPositionAndLook if it disagrees
+    fn apply_gravity(&mut self, world: &World) {
+        if !self.pos_update || self.dead {
+            return;
+        }
+        let pos = self.world_pos();
+        // Unloaded columns read as air, wait for the chunk instead of dropping into it
+        let (chunk_x, chunk_z) = chunk_coords(pos.0, pos.2);
+        if !world.is_loaded(chunk_x, chunk_z) {
+            return;
+        }
+        let mut fallen = 0;
+        while fallen < self.fall_speed {
+            let below = pos_add(pos, (0, -fallen - 1, 0));
+            if below.1 < 0 || world.is_solid(below) {
+                break;
+            }
+            fallen += 1;
+        }
+        self.on_ground = fallen < self.fall_speed;
+        if fallen > 0 {
+            self.move_pos((0, -fallen, 0));
+        }
+        self.fall_speed = match self.on_ground {
+            true => 1,
+            false => (self.fall_speed + 1).min(MAX_FALL_SPEED)
+        };
+    }
+
     fn move_pos(&mut self, delta: (i32, i32, i32)) {
         self.pos = (
             self.pos.0 + delta.0 as f64,
@@ -126,15 +305,56 @@ impl Player {
     }
 
     
+    /// Compass direction of the current yaw
+    pub fn facing(&self) -> &'static str {
+        match (self.look.0.rem_euclid(360.) / 45.).round() as i32 {
+            0 | 8 => "S",
+            1 => "SW",
+            2 => "W",
+            3 => "NW",
+            4 => "N",
+            5 => "NE",
+            6 => "E",
+            _ => "SE",
+        }
+    }
+
+    pub fn game_mode_name(&self) -> &'static str {
+        GAME_MODES.get(self.game_mode as usize).unwrap_or(&"unknown")
+    }
+
+    fn set_game_mode(&mut self, game_mode: u8) {
+        // The hardcore flag rides along in bit 3
+        let game_mode = game_mode & 0x7;
+        if game_mode != self.game_mode {
+            self.game_mode = game_mode;
+            log::info!("{} is now in {} mode", self.name, self.game_mode_name());
+        }
+    }
+
     pub fn set_look(&mut self, look: (f32, f32)) {
         self.look = look;
     }
 
-    fn position_update_loop(player: Arc<RwLock<Player>>) -> tokio::task::JoinHandle<()> {
-        let player = Arc::clone(&player);
+    /// Stops the player and its background position updates
+    pub fn shutdown(&mut self) {
+        self.stop = true;
+        self.pos_update_stop.store(true, Ordering::Relaxed);
+        if let Some(pos_update) = self.pos_update_loop.take() {
+            pos_update.abort();
+        }
+    }
+
+    fn position_update_loop(
+        player: Arc<RwLock<Player>>,
+        stop: Arc<AtomicBool>) -> tokio::task::JoinHandle<()>
+    {
         tokio::task::spawn(async move {
             let mut interval = interval(Duration::from_millis(50));
             loop {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
                 {
                     let player = player.read().await;
                     if player.pos_update {
@@ -145,9 +365,13 @@ impl Player {
                             z: player.pos.2,
                             yaw: player.look.0,
                             pitch: player.look.1,
-                            on_ground: true
+                            on_ground: player.on_ground
                         };
-                        player.connection.send(packet).await;
+                        if let Err(e) = player.connection.send(packet).await {
+                            log::error!("Failed to send position of {}: {}", player.name, e);
+                            stop.store(true, Ordering::Relaxed);
+                            break;
+                        }
                     }
                 }
                 interval.tick().await;
@@ -155,38 +379,369 @@ impl Player {
         })
     }
 
-    pub async fn tick(&mut self, ctx: &mut GlobalContext) -> bool {
-        let mut inbound_buffer = vec![];
-        self.connection.recv(&mut inbound_buffer).await;
-        for packet in inbound_buffer.drain(..) {
-            self.handle_packet(ctx, packet).await;
+    /// Handles packets until `budget` runs out, the rest wait for the next tick
+    pub async fn tick(&mut self, ctx: &mut GlobalContext, budget: Duration) -> bool {
+        // The position loop stops by itself once the connection can't be written to
+        if self.pos_update_stop.load(Ordering::Relaxed) {
+            self.shutdown();
+            return true;
+        }
+        self.step_path(ctx);
+        self.apply_gravity(&ctx.world);
+        self.tick_effects();
+        if let Err(e) = self.respawn(ctx.tick).await {
+            log::error!("Player {} lost connection: {}", self.name, e);
+            self.shutdown();
+            return true;
+        }
+        if let Err(e) = self.auto_eat(ctx.tick).await {
+            log::error!("Player {} lost connection: {}", self.name, e);
+            self.shutdown();
+            return true;
+        }
+        let deadline = Instant::now() + budget;
+        // At least one packet goes through however little time is left
+        while let Some(packet) = self.connection.try_recv() {
+            if let Some(hook) = ctx.packet_hook.as_ref() {
+                hook(self, &packet);
+            }
+            if let Err(e) = self.handle_packet(ctx, packet).await {
+                log::error!("Player {} lost connection: {}", self.name, e);
+                self.stop = true;
+            }
             if self.stop {
-                if let Some(pos_update) = self.pos_update_loop.as_ref() {
-                    pos_update.abort();
-                }
+                self.shutdown();
                 return true;
             }
+            if Instant::now() >= deadline {
+                break;
+            }
         }
         return false;
     }
 
-    async fn handle_packet(&mut self, ctx: &mut GlobalContext, inbound: Packet) {
+    fn tick_effects(&mut self) {
+        for effect in self.effects.iter_mut() {
+            effect.remaining -= 1;
+        }
+        self.effects.retain(|e| e.remaining > 0);
+    }
+
+    async fn respawn(&mut self, tick: u64) -> Result<(), Box<dyn Error>> {
+        if self.respawn_at.is_some_and(|at| tick >= at) {
+            self.respawn_at = None;
+            log::info!("Respawning {}", self.name);
+            self.connection.send(Packet::ClientStatuses {
+                payload: 1
+            }).await?;
+        }
+        Ok(())
+    }
+
+    fn on_death(&mut self, tick: u64) {
+        let pos = self.world_pos();
+        self.last_death_pos = Some(pos);
+        log::warning!("{} died at {} {} {}", self.name, pos.0, pos.1, pos.2);
+        if self.stay_dead {
+            return;
+        }
+        while self.deaths.front().is_some_and(|t| tick.saturating_sub(*t) > DEATH_WINDOW) {
+            self.deaths.pop_front();
+        }
+        self.deaths.push_back(tick);
+        let mut delay = self.respawn_delay;
+        if self.deaths.len() >= DEATH_LIMIT {
+            delay += RESPAWN_BACKOFF * (self.deaths.len() - DEATH_LIMIT + 1) as u64;
+            log::warning!("{} keeps dying, respawning in {} ticks", self.name, delay);
+        }
+        self.respawn_at = Some(tick + delay);
+    }
+
+    /// Starts eating from the hotbar once food drops below the threshold
+    async fn auto_eat(&mut self, tick: u64) -> Result<(), Box<dyn Error>> {
+        if self.hp <= 0 || self.food >= self.eat_threshold || tick < self.eat_cooldown {
+            return Ok(());
+        }
+        let food = self.hotbar.iter()
+            .enumerate()
+            .find_map(|(i, slot)| slot.filter(|(id, ..)| FOOD_IDS.contains(id)).map(|item| (i as i16, item)));
+        let Some((slot_id, (id, count, damage))) = food else {
+            return Ok(());
+        };
+        self.eat_cooldown = tick + EAT_COOLDOWN;
+        log::info!("{} is hungry ({}), eating item {}", self.name, self.food, id);
+        self.select_slot(slot_id).await?;
+        // Using an item in the air, the server finishes eating on its own
+        self.connection.send(Packet::PlayerBlockPlacement {
+            x: -1,
+            y: 255,
+            z: -1,
+            dir: 255,
+            item: Slot::Item { id, count, damage },
+            cur_x: 0,
+            cur_y: 0,
+            cur_z: 0
+        }).await?;
+        Ok(())
+    }
+
+    /// Left clicks (attack) or right clicks (interact) an entity
+    pub async fn use_entity(&mut self, target: i32, attack: bool) -> Result<(), Box<dyn Error>> {
+        self.connection.send(Packet::UseEntity {
+            user: self.eid,
+            target,
+            mouse_button: attack
+        }).await?;
+        Ok(())
+    }
+
+    pub async fn send_plugin_message(&mut self, channel: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.connection.send(Packet::PluginMessage {
+            channel: channel.to_string(),
+            data: data.into()
+        }).await?;
+        Ok(())
+    }
+
+    fn plugin_message(&mut self, ctx: &mut GlobalContext, channel: &str, data: &[u8]) {
+        match channel {
+            "MC|Brand" => {
+                let brand = String::from_utf8_lossy(data).trim().to_string();
+                if ctx.server_brand.as_ref() != Some(&brand) {
+                    log::info!("Server brand: {}", brand);
+                    ctx.server_brand = Some(brand);
+                }
+            },
+            // Channel names separated by NUL
+            "REGISTER" | "UNREGISTER" => {
+                let channels: Vec<String> = data.split(|b| *b == 0)
+                    .filter(|c| !c.is_empty())
+                    .map(|c| String::from_utf8_lossy(c).to_string())
+                    .collect();
+                if self.is_focused {
+                    log::info!("{}: {} {}", self.name, channel, channels.join(", "));
+                }
+            },
+            // Everyone gets these, only the player on screen reports them
+            _ if self.is_focused => {
+                log::info!("{}: plugin message on {}: {}", self.name, channel, hex_preview(data, PLUGIN_PREVIEW_BYTES));
+            },
+            _ => {}
+        }
+    }
+
+    /// Starts or stops sneaking, the hitbox doesn't shrink in 1.5.2 so position updates stay the same
+    pub async fn set_sneaking(&mut self, sneaking: bool) -> Result<(), Box<dyn Error>> {
+        if sneaking == self.sneaking {
+            return Ok(());
+        }
+        // Can't sprint and sneak at once
+        if sneaking {
+            self.set_sprinting(false).await?;
+        }
+        self.entity_action(if sneaking { ACTION_SNEAK } else { ACTION_UNSNEAK }).await?;
+        self.sneaking = sneaking;
+        Ok(())
+    }
+
+    pub async fn set_sprinting(&mut self, sprinting: bool) -> Result<(), Box<dyn Error>> {
+        if sprinting == self.sprinting {
+            return Ok(());
+        }
+        if sprinting && self.sneaking {
+            return Err("can't sprint while sneaking".into());
+        }
+        self.entity_action(if sprinting { ACTION_SPRINT } else { ACTION_UNSPRINT }).await?;
+        self.sprinting = sprinting;
+        Ok(())
+    }
+
+    /// Right clicks the bed at `pos`, the server answers with UseBed if we can sleep now
+    pub async fn sleep(&mut self, pos: (i32, i32, i32)) -> Result<(), Box<dyn Error>> {
+        if !(0..256).contains(&pos.1) {
+            return Err(format!("no bed at height {}", pos.1).into());
+        }
+        self.connection.send(Packet::PlayerBlockPlacement {
+            x: pos.0,
+            y: pos.1 as u8,
+            z: pos.2,
+            dir: 1,
+            item: Slot::Empty,
+            cur_x: 8,
+            cur_y: 8,
+            cur_z: 8
+        }).await
+    }
+
+    pub async fn leave_bed(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.sleeping.is_none() {
+            return Ok(());
+        }
+        self.entity_action(ACTION_LEAVE_BED).await
+    }
+
+    fn wake(&mut self) {
+        if self.sleeping.take().is_some() {
+            log::info!("{} woke up", self.name);
+        }
+    }
+
+    async fn entity_action(&mut self, action: u8) -> Result<(), Box<dyn Error>> {
+        self.connection.send(Packet::EntityAction {
+            eid: self.eid,
+            action
+        }).await
+    }
+
+    /// Switches the held item to hotbar slot 0-8
+    pub async fn select_slot(&mut self, slot_id: i16) -> Result<(), Box<dyn Error>> {
+        if !(0..9).contains(&slot_id) {
+            return Err(format!("No hotbar slot {}", slot_id).into());
+        }
+        if slot_id != self.held_slot {
+            self.connection.send(Packet::HeldItemChange { slot_id }).await?;
+            self.held_slot = slot_id;
+        }
+        Ok(())
+    }
+
+    /// Clicks a window slot, returns the action number the server will confirm
+    pub async fn click_window(
+        &mut self,
+        window_id: u8,
+        slot: u16,
+        button: u8,
+        mode: u8,
+        item: Slot) -> Result<u16, Box<dyn Error>>
+    {
+        let action = self.next_action;
+        // Action numbers come back as i16, keep them in its positive range
+        self.next_action = if action >= i16::MAX as u16 { 1 } else { action + 1 };
+        self.pending_clicks.insert(action, (window_id, slot));
+        self.connection.send(Packet::ClickWindow {
+            window_id,
+            slot,
+            button,
+            action,
+            mode,
+            item
+        }).await?;
+        Ok(action)
+    }
+
+    /// Enchants the item in the open table with option 0, 1 or 2
+    pub async fn enchant(&mut self, window_id: u8, option: u8) -> Result<(), Box<dyn Error>> {
+        let Some(window) = self.enchanting.as_ref().filter(|w| w.window_id == window_id) else {
+            return Err(format!("No enchantment table open as window {}", window_id).into());
+        };
+        let Some(cost) = window.costs.get(option as usize).copied() else {
+            return Err(format!("No enchant option {}", option + 1).into());
+        };
+        if cost <= 0 {
+            return Err("Nothing to enchant".into());
+        }
+        // Creative players don't pay levels
+        if self.game_mode != 1 && self.level < cost {
+            return Err(format!("Needs level {}, at {}", cost, self.level).into());
+        }
+        self.connection.send(Packet::EnchantItem {
+            window_id,
+            enchantement: option
+        }).await
+    }
+
+    /// Closes whatever window is open other than the inventory
+    pub async fn close_window(&mut self) -> Result<(), Box<dyn Error>> {
+        let Some(window) = self.enchanting.take() else {
+            return Ok(());
+        };
+        self.connection.send(Packet::CloseWindow { window_id: window.window_id }).await
+    }
+
+    async fn confirm_transaction(&mut self, window_id: u8, action_number: i16, is_accepted: bool) -> Result<(), Box<dyn Error>> {
+        let Some((_, slot)) = self.pending_clicks.remove(&(action_number as u16)) else {
+            return Ok(());
+        };
+        if !is_accepted {
+            // The server holds the window until the rejection is acknowledged, then resends its contents
+            log::warning!("{}: click on slot {} of window {} rejected", self.name, slot, window_id);
+            self.connection.send(Packet::ConfirmTransaction {
+                window_id,
+                action_number,
+                is_accepted: true
+            }).await?;
+        }
+        Ok(())
+    }
+
+    fn set_hotbar_slot(&mut self, slot: i16, item: &Slot) {
+        if !(HOTBAR_START..HOTBAR_START + 9).contains(&slot) {
+            return;
+        }
+        self.hotbar[(slot - HOTBAR_START) as usize] = match item {
+            Slot::Empty => None,
+            Slot::Item { id, count, damage } => Some((*id, *count, *damage)),
+            Slot::ItemNbt { id, count, damage, .. } => Some((*id, *count, *damage)),
+        };
+    }
+
+    async fn handle_packet(&mut self, ctx: &mut GlobalContext, inbound: Packet) -> Result<(), Box<dyn Error>> {
         match inbound {
             Packet::SpawnPosition { x, y, z } => {
                 log::info!("Spawn is at {} {} {}", x, y, z);
                 self.connection.send(Packet::ClientSettings {
                     locale: "en_US".to_string(),
-                    difficulty: 2,
                     view_distance: 0,
-                    show_cape: true,
-                    chat_flags: 8
-                }).await.unwrap();
+                    chat_flags: 8,
+                    difficulty: 2,
+                    show_cape: true
+                }).await?;
            }
+            Packet::LoginRequest { entity_id, dimension, game_mode, .. } => {
+                self.eid = entity_id;
+                self.set_game_mode(game_mode as u8);
+                self.dimension = dimension as i32;
+                if self.is_focused {
+                    ctx.set_dimension(self.dimension);
+                }
+            },
+            Packet::Respawn { dim, game_mode, .. } => {
+                log::info!("{} respawned in dimension {}", self.name, dim);
+                self.dimension = dim;
+                // A saved camera means nothing in another dimension
+                self.camera = None;
+                self.set_game_mode(game_mode);
+                self.effects.clear();
+                self.enchanting = None;
+                // The new entity starts out standing
+                self.sneaking = false;
+                self.sprinting = false;
+                self.wake();
+                if self.is_focused {
+                    // The other players still need the terrain around them, it's
+                    // only dropped when the dimension on screen changes
+                    ctx.set_dimension(dim);
+                    ctx.mode = GameState::World;
+                    ctx.set_cam(self.camera_pos());
+                }
+            },
+            // Only the dimension on screen gets its terrain tracked
+            Packet::ChunkData { .. }
+            | Packet::ChunkDataBulk { .. }
+            | Packet::BlockChange { .. }
+            | Packet::MultiBlockChange { .. }
+            | Packet::UpdateSign { .. }
+            | Packet::BlockBreakAnimation { .. }
+            | Packet::BlockAction { .. }
+            | Packet::Explosion { .. }
+            | Packet::SpawnGlobalEntity { .. }
+            | Packet::NamedSoundEffect { .. }
+            | Packet::SoundOrParticleEffect { .. } if self.dimension != ctx.dimension => {},
             Packet::ChunkData { chunk_data } => {
                 ctx.world.set_chunk(chunk_data);
             },
             Packet::ChunkDataBulk { column_data } =>  {
-                ctx.world.set_chunk_bulk(&column_data);
+                ctx.world.set_chunk_bulk(column_data);
             },
             Packet::BlockChange { x, y, z, block_type, block_meta } => {
                 ctx.world.set_block(x, z, y, block_type, block_meta);
@@ -194,44 +749,176 @@ impl Player {
             Packet::MultiBlockChange { change_data } => {
                 ctx.world.set_block_multiple(&change_data);
             },
+            Packet::Explosion { x, y, z, block_offsets, .. } => {
+                ctx.world.explode((x, y, z), &block_offsets);
+                let pos = (x as i32, y as i32, z as i32);
+                ctx.events.push(EventKind::Explosion, String::from("explosion"), pos, ctx.tick);
+            },
+            // Lightning is the only global entity, it has no destroy packet so it's never tracked
+            Packet::SpawnGlobalEntity { etype: 1, x, y, z, .. } => {
+                let pos = (x.div_euclid(32), y.div_euclid(32), z.div_euclid(32));
+                ctx.events.push(EventKind::Lightning, String::from("lightning"), pos, ctx.tick);
+            },
+            // Named sound positions are in eighths of a block
+            Packet::NamedSoundEffect { name, x, y, z, .. } => {
+                let pos = (x.div_euclid(8), y.div_euclid(8), z.div_euclid(8));
+                ctx.events.push(EventKind::from_sound(&name), name, pos, ctx.tick);
+            },
+            Packet::SoundOrParticleEffect { effect_id, x, y, z, .. } => {
+                let kind = EventKind::from_effect(effect_id);
+                let text = match kind {
+                    EventKind::Door => String::from("door"),
+                    EventKind::BlockBreak => String::from("block broken"),
+                    _ => format!("effect {}", effect_id)
+                };
+                ctx.events.push(kind, text, (x, y as i32, z), ctx.tick);
+            },
+            Packet::ItemData { item_type: 358, item_id, text } => {
+                ctx.maps.update(item_id, &text);
+            },
+            Packet::BlockAction { x, y, z, hb, lb, block_id } => {
+                ctx.world.set_block_action((x, y as i32, z), block_id, hb, lb, ctx.tick);
+            },
+            Packet::BlockBreakAnimation { x, y, z, destroy_stage, .. } => {
+                ctx.world.set_break_stage((x, y, z), destroy_stage, ctx.tick);
+            },
+            Packet::UpdateSign { x, y, z, text_1, text_2, text_3, text_4 } => {
+                ctx.world.set_sign((x, y as i32, z), [
+                    plain_text(&text_1),
+                    plain_text(&text_2),
+                    plain_text(&text_3),
+                    plain_text(&text_4),
+                ]);
+            },
+            Packet::TimeUpdate { age, time } => {
+                ctx.age = age;
+                ctx.time = time;
+            },
+            Packet::PluginMessage { channel, data } => {
+                self.plugin_message(ctx, &channel, &data);
+            },
+            Packet::IncrementStat { stat_id, amount } => {
+                *self.stats.entry(stat_id).or_insert(0) += amount as i64;
+            },
+            // Every player gets the same list, applying it again changes nothing
+            Packet::PlayerListItem { name, online, pink: ping } => {
+                if online {
+                    ctx.online.insert(name, ping);
+                } else {
+                    ctx.online.remove(&name);
+                }
+            },
+            Packet::ChangeGameState { reason, game_mode } => {
+                // Weather doesn't get anyone out of bed
+                if reason != 1 && reason != 2 {
+                    self.wake();
+                }
+                match reason {
+                    0 => log::info!("{} has no bed to respawn at", self.name),
+                    1 | 2 if self.dimension == ctx.dimension => ctx.set_raining(reason == 1),
+                    3 => self.set_game_mode(game_mode),
+                    4 => log::info!("{} is watching the credits", self.name),
+                    _ => {}
+                }
+            },
+            Packet::UseBed { eid, x, y, z, .. } if eid == self.eid => {
+                log::info!("{} is sleeping at {} {} {}", self.name, x, y, z);
+                self.sleeping = Some((x, y as i32, z));
+            },
+            Packet::UseBed { eid, x, y, z, .. } => {
+                if self.is_focused {
+                    let name = ctx.entities.entities.iter()
+                        .find(|e| e.id == eid)
+                        .map_or("unknown", |e| e.display_name());
+                    log::info!("Entity {} ({}) is sleeping at {} {} {}", eid, name, x, y, z);
+                }
+            },
             Packet::UpdateHealth { health, food, saturation} => {
                 log::info!("HP: {}, food: {}/{}", health, food, saturation);
                 self.hp = health;
                 self.food = food;
                 self.saturation = saturation;
-                if self.hp <= 0 {
-                    log::info!("{} died! Respawning...", self.name);
-                    self.connection.send(Packet::ClientStatuses {
-                        payload: 1
-                    }).await.unwrap();
+                if self.hp <= 0 && !self.dead {
+                    self.on_death(ctx.tick);
                 }
+                self.dead = self.hp <= 0;
+            },
+            Packet::SetExperience { bar, level, .. } => {
+                self.xp_bar = bar;
+                self.level = level;
             },
             Packet::PlayerPositionAndLook { x, y, stance, z, yaw, pitch, on_ground } => {
                 self.pos_update = true;
                 self.pos = (x, stance, z);
                 self.stance = stance + 0.3;
-                log::info!("Is focused: {}", self.is_focused);
-                if self.is_focused && let GameState::World = ctx.mode {
-                    log::info!("Snapped camera to my pos");
-                    ctx.set_cam(self.camera_pos());
-                }
+                self.fall_speed = 1;
+                // Getting out of bed puts us next to it
+                self.wake();
                 log::info!("Forced pos to: {:?}:{}", self.pos, self.stance);
                 self.connection.send(Packet::PlayerPositionAndLook {
                     x, stance: y, y: stance, z, yaw, pitch, on_ground
-                }).await.unwrap();
+                }).await?;
+            },
+            // Window 0 is the player's own inventory
+            Packet::SetWindowItems { window_id: 0, slots } => {
+                for (i, item) in slots.iter().enumerate() {
+                    self.set_hotbar_slot(i as i16, item);
+                }
+            },
+            Packet::SetSlot { window_id: 0, slot, item } => {
+                self.set_hotbar_slot(slot, &item);
+            },
+            Packet::OpenWindow { window_id, inv_type: ENCHANT_WINDOW, .. } => {
+                self.enchanting = Some(EnchantWindow { window_id, costs: [0; 3] });
+            },
+            Packet::OpenWindow { .. } => {
+                self.enchanting = None;
+            },
+            Packet::UpdateWindowProperty { window_id, property, value } => {
+                if let Some(window) = self.enchanting.as_mut().filter(|w| w.window_id == window_id) {
+                    if let Some(cost) = window.costs.get_mut(property as usize) {
+                        *cost = value;
+                    }
+                }
+            },
+            Packet::CloseWindow { window_id } => {
+                if self.enchanting.as_ref().is_some_and(|w| w.window_id == window_id) {
+                    self.enchanting = None;
+                }
+            },
+            Packet::ConfirmTransaction { window_id, action_number, is_accepted } => {
+                self.confirm_transaction(window_id, action_number, is_accepted).await?;
+            },
+            Packet::HeldItemChange { slot_id } => {
+                self.held_slot = slot_id;
+            },
+            Packet::EntityEffect { eid, effect_id, amplifier, duration } if eid == self.eid => {
+                self.effects.retain(|e| e.id != effect_id);
+                self.effects.push(Effect { id: effect_id, amplifier, remaining: duration });
+            },
+            Packet::RemoveEntityEffect { eid, effect_id } if eid == self.eid => {
+                self.effects.retain(|e| e.id != effect_id);
             },
             Packet::Disconnect { reason } => {
+                let reason = plain_text(&reason);
                 log::warning!("Player {} disconnected: {}", self.name, reason);
+                ctx.kicks.push((self.name.clone(), reason));
                 self.stop = true;
             },
             Packet::SpawnObject {eid, ..} => {
                 self.known_entities.insert(eid);
                 ctx.entities.handle_packet(inbound, self.id).await;
             },
-            Packet::SpawnMob {eid, ..} => {
+            Packet::SpawnMob {eid, ..}
+            | Packet::SpawnExperienceOrb {eid, ..}
+            | Packet::SpawnPainting {eid, ..} => {
                 self.known_entities.insert(eid);
                 ctx.entities.handle_packet(inbound, self.id).await;
             },
+            Packet::CollectItem { collected, .. } => {
+                self.known_entities.remove(&collected);
+                ctx.entities.handle_packet(inbound, self.id).await;
+            },
             Packet::EntityDestroy { ids } => {
                 for eid in &ids {
                     self.known_entities.remove(&eid);
@@ -242,5 +929,157 @@ impl Player {
                 ctx.entities.handle_packet(inbound, self.id).await;
             }
         }
+        Ok(())
+    }
+}
+
+/// Resolves a step into the actual movement, going up or down a block when needed.
+/// Returns None if the step is blocked
+fn step_delta(world: &World, from: (i32, i32, i32), delta: (i32, i32, i32)) -> Option<(i32, i32, i32)> {
+    if delta.0 == 0 && delta.2 == 0 {
+        return Some(delta);
+    }
+    let next = pos_add(from, delta);
+    // if lower target block is solid, check for two above and ascend if possible
+    if world.is_solid(next) {
+        let bottom = pos_add(next, (0, 1, 0));
+        let top = pos_add(bottom, (0, 1, 0));
+        if world.is_solid(bottom) || world.is_solid(top) {
+            return None;
+        }
+        return Some(pos_add(delta, (0, 1, 0)));
+    }
+    // if not, check if block below is not solid too and descent
+    if !world.is_solid(pos_add(next, (0, -1, 0))) && !world.is_solid(pos_add(next, (0, 1, 0))) {
+        return Some(pos_add(delta, (0, -1, 0)));
+    }
+    Some(delta)
+}
+
+fn has_clearance(world: &World, pos: (i32, i32, i32)) -> bool {
+    !world.is_solid(pos) && !world.is_solid(pos_add(pos, (0, 1, 0)))
+}
+
+/// Breadth-first search over walkable steps, None if the target isn't reached within the node budget
+fn find_path(
+    world: &World,
+    from: (i32, i32, i32),
+    to: (i32, i32, i32))
+    -> Option<VecDeque<(i32, i32, i32)>>
+{
+    let mut came_from = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    came_from.insert(from, from);
+    while let Some(pos) = queue.pop_front() {
+        if pos == to {
+            let mut path = VecDeque::new();
+            let mut current = pos;
+            while current != from {
+                path.push_front(current);
+                current = came_from[&current];
+            }
+            return Some(path);
+        }
+        if came_from.len() > MAX_PATH_NODES {
+            return None;
+        }
+        for direction in DIRECTIONS {
+            let next = match step_delta(world, pos, direction) {
+                Some(delta) => pos_add(pos, delta),
+                None => continue
+            };
+            if came_from.contains_key(&next) || !has_clearance(world, next) {
+                continue;
+            }
+            // diagonal steps need both blocks they squeeze between to be clear
+            if direction.0 != 0 && direction.2 != 0
+                && (!has_clearance(world, pos_add(pos, (direction.0, 0, 0)))
+                    || !has_clearance(world, pos_add(pos, (0, 0, direction.2))))
+            {
+                continue;
+            }
+            came_from.insert(next, pos);
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stopped_position_loop_terminates() {
+        let (connection, _inbound, _server) = Connection::mock().await;
+        let player = Player::with_connection(connection, "bot".to_string()).await;
+        let (stop, handle) = {
+            let mut player = player.write().await;
+            (Arc::clone(&player.pos_update_stop), player.pos_update_loop.take().unwrap())
+        };
+        stop.store(true, Ordering::Relaxed);
+        // Finishing rather than being cancelled means the loop saw the flag by itself
+        let finished = tokio::time::timeout(Duration::from_secs(1), handle).await;
+        assert!(matches!(finished, Ok(Ok(()))));
+    }
+
+    #[tokio::test]
+    async fn shutdown_ends_the_position_loop() {
+        let (connection, _inbound, _server) = Connection::mock().await;
+        let player = Player::with_connection(connection, "bot".to_string()).await;
+        let mut player = player.write().await;
+        player.shutdown();
+        assert!(player.stop);
+        assert!(player.pos_update_loop.is_none());
+        assert!(player.pos_update_stop.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn send_error_stops_only_that_player() {
+        let mut ctx = GlobalContext::init(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources")).unwrap();
+        let (connection, inbound, _server) = Connection::mock().await;
+        let player = Player::with_connection(connection, "bot".to_string()).await;
+        let mut player = player.write().await;
+        player.connection.break_writes().await;
+        // Answering SpawnPosition sends ClientSettings
+        inbound.send(Packet::SpawnPosition { x: 0, y: 64, z: 0 }).await.unwrap();
+        assert!(player.tick(&mut ctx, Duration::from_millis(50)).await);
+        assert!(player.stop);
+        assert!(player.pos_update_loop.is_none());
+        assert!(!ctx.stop);
+    }
+
+    #[test]
+    fn stepping_into_an_unloaded_column() {
+        let mut ctx = GlobalContext::init(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources")).unwrap();
+        for x in 0..16 {
+            ctx.world.set_block(x, 0, 63, 1, 0);
+        }
+        // Along the floor inside the loaded column
+        assert_eq!(step_delta(&ctx.world, (14, 64, 0), (1, 0, 0)), Some((1, 0, 0)));
+        // Unloaded reads as open, so the step past the edge goes down rather than panicking
+        assert_eq!(step_delta(&ctx.world, (15, 64, 0), (1, 0, 0)), Some((1, -1, 0)));
+        assert_eq!(step_delta(&ctx.world, (0, 64, 0), (-1, 0, -1)), Some((-1, -1, -1)));
+        assert!(has_clearance(&ctx.world, (16, 64, 0)));
+    }
+
+    #[tokio::test]
+    async fn respawning_in_the_same_dimension_keeps_the_terrain() {
+        let mut ctx = GlobalContext::init(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources")).unwrap();
+        let (connection, _inbound, _server) = Connection::mock().await;
+        let focused = Player::with_connection(connection, "focused".to_string()).await;
+        let (connection, _other_inbound, _other_server) = Connection::mock().await;
+        let other = Player::with_connection(connection, "other".to_string()).await;
+        ctx.players = vec![Arc::clone(&focused), Arc::clone(&other)];
+        // Both stand in the same column
+        ctx.world.set_block(8, 8, 63, 1, 0);
+        for player in &ctx.players {
+            player.write().await.pos = (8.5, 64.0, 8.5);
+        }
+        let mut focused = focused.write().await;
+        focused.is_focused = true;
+        let respawn = Packet::Respawn { dim: 0, difficulty: 1, game_mode: 0, height: 256, level_type: "default".to_string() };
+        focused.handle_packet(&mut ctx, respawn).await.unwrap();
+        assert!(ctx.world.is_loaded(0, 0));
     }
 }
@@ -0,0 +1,82 @@
+use crate::world::World;
+
+// IncrementStat ids past these bases carry a block or item id
+const MINED_BASE: i32 = 0x1000000;
+const CRAFTED_BASE: i32 = 0x1010000;
+const USED_BASE: i32 = 0x1020000;
+const BROKEN_BASE: i32 = 0x1030000;
+const ACHIEVEMENT_BASE: i32 = 0x500000;
+// Item ids below this are blocks
+const BLOCK_ITEMS: i32 = 256;
+
+const GENERAL: [(i32, &str); 25] = [
+    (1000, "Games started"),
+    (1001, "Worlds created"),
+    (1002, "Games loaded"),
+    (1003, "Multiplayer joins"),
+    (1004, "Games quit"),
+    (1100, "Time played"),
+    (2000, "Distance walked"),
+    (2001, "Distance swum"),
+    (2002, "Distance fallen"),
+    (2003, "Distance climbed"),
+    (2004, "Distance flown"),
+    (2005, "Distance dove"),
+    (2006, "Distance by minecart"),
+    (2007, "Distance by boat"),
+    (2008, "Distance by pig"),
+    (2010, "Jumps"),
+    (2011, "Items dropped"),
+    (2020, "Damage dealt"),
+    (2021, "Damage taken"),
+    (2022, "Deaths"),
+    (2023, "Mob kills"),
+    (2024, "Player kills"),
+    (2025, "Fish caught"),
+    (2026, "Animals bred"),
+    (2027, "Junk fished"),
+];
+
+const ACHIEVEMENTS: [&str; 27] = [
+    "Taking Inventory", "Getting Wood", "Benchmarking", "Time to Mine!", "Hot Topic",
+    "Acquire Hardware", "Time to Farm!", "Bake Bread", "The Lie", "Getting an Upgrade",
+    "Delicious Fish", "On A Rail", "Time to Strike!", "Monster Hunter", "Cow Tipper",
+    "When Pigs Fly", "Sniper Duel", "DIAMONDS!", "We Need to Go Deeper", "Return to Sender",
+    "Into Fire", "Local Brewery", "The End?", "The End.", "Enchanter", "Overkill", "Librarian",
+];
+
+fn item_name(id: i32, world: &World) -> String {
+    match id {
+        0..BLOCK_ITEMS => world.block_name(id as u16),
+        _ => format!("item {}", id)
+    }
+}
+
+/// Readable name of a statistic, block ids are resolved through the block data
+pub fn stat_name(id: i32, world: &World) -> String {
+    if let Some((_, name)) = GENERAL.iter().find(|(stat, _)| *stat == id) {
+        return name.to_string();
+    }
+    match id {
+        BROKEN_BASE.. => format!("{} broken", item_name(id - BROKEN_BASE, world)),
+        USED_BASE.. => format!("{} used", item_name(id - USED_BASE, world)),
+        CRAFTED_BASE.. => format!("{} crafted", item_name(id - CRAFTED_BASE, world)),
+        MINED_BASE.. => format!("{} mined", item_name(id - MINED_BASE, world)),
+        ACHIEVEMENT_BASE.. => match ACHIEVEMENTS.get((id - ACHIEVEMENT_BASE) as usize) {
+            Some(name) => format!("Achievement: {}", name),
+            None => format!("Achievement {}", id - ACHIEVEMENT_BASE)
+        },
+        _ => format!("stat {}", id)
+    }
+}
+
+/// Distances come in centimeters and play time in ticks
+pub fn stat_value(id: i32, value: i64) -> String {
+    match id {
+        1100 => format!("{}h {:02}m", value / 72000, value / 1200 % 60),
+        2000..=2008 => format!("{:.1} m", value as f64 / 100.),
+        // Health is counted in half hearts
+        2020 | 2021 => format!("{:.1} hearts", value as f64 / 2.),
+        _ => value.to_string()
+    }
+}
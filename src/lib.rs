@@ -0,0 +1,16 @@
+//! Minecraft 1.5.2 bot client. The mc-tui binary draws a terminal UI on top of it,
+//! embedders drive the bots through [`Client`]
+
+mod buffered_reader;
+mod nbt;
+mod util;
+mod client;
+pub mod packets;
+pub mod net;
+pub mod world;
+pub mod game;
+pub mod ui;
+pub mod log;
+pub mod config;
+
+pub use client::Client;
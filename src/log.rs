@@ -54,12 +54,15 @@ pub fn lines<'a>(n: usize, level: LogLevel) -> Vec<Line<'a>> {
 pub fn log(line: &str, level: LogLevel) {
     let time = Local::now().format("%H:%M:%S%.3f").to_string();
     let line = format!("[{}] {}", time, line);
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open("log.txt")
-        .unwrap();
-    writeln!(file, "{}", line).unwrap();
+    // Tests only keep the in-memory tail, there's no log.txt set up for them
+    if !cfg!(test) {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .append(true)
+            .open("log.txt")
+            .unwrap();
+        writeln!(file, "{}", line).unwrap();
+    }
     let mut log = LOG.lock().unwrap();
     log.push((line, level));
     if log.len() > LOG_TAIL {
@@ -69,74 +72,80 @@ pub fn log(line: &str, level: LogLevel) {
 
 // TODO who macroes the macros
 
+#[macro_export]
 macro_rules! warning{
     ($str:ident) => {{
-        crate::log::log(&$str, crate::log::LogLevel::Warn);
+        $crate::log::log(&$str, $crate::log::LogLevel::Warn);
     }};
 
     ($fmt_str:literal) => {{
-        crate::log::log($fmt_str, crate::log::LogLevel::Warn);
+        $crate::log::log($fmt_str, $crate::log::LogLevel::Warn);
     }};
 
     ($fmt_str:literal, $($args:expr),*) => {{
-        crate::log::log(&format!($fmt_str, $($args),*), crate::log::LogLevel::Warn);
+        $crate::log::log(&format!($fmt_str, $($args),*), $crate::log::LogLevel::Warn);
     }};
 }
 
+#[macro_export]
 macro_rules! error{
     ($str:ident) => {{
-        crate::log::log(&$str, crate::log::LogLevel::Error);
+        $crate::log::log(&$str, $crate::log::LogLevel::Error);
     }};
 
     ($fmt_str:literal) => {{
-        crate::log::log($fmt_str, crate::log::LogLevel::Error);
+        $crate::log::log($fmt_str, $crate::log::LogLevel::Error);
     }};
 
     ($fmt_str:literal, $($args:expr),*) => {{
-        crate::log::log(&format!($fmt_str, $($args),*), crate::log::LogLevel::Error);
+        $crate::log::log(&format!($fmt_str, $($args),*), $crate::log::LogLevel::Error);
     }};
 }
 
+#[macro_export]
 macro_rules! info{
     ($str:ident) => {{
-        crate::log::log(&$str, crate::log::LogLevel::Info);
+        $crate::log::log(&$str, $crate::log::LogLevel::Info);
     }};
 
     ($fmt_str:literal) => {{
-        crate::log::log($fmt_str, crate::log::LogLevel::Info);
+        $crate::log::log($fmt_str, $crate::log::LogLevel::Info);
     }};
 
     ($fmt_str:literal, $($args:expr),*) => {{
-        crate::log::log(&format!($fmt_str, $($args),*), crate::log::LogLevel::Info);
+        $crate::log::log(&format!($fmt_str, $($args),*), $crate::log::LogLevel::Info);
     }};
 }
 
+#[macro_export]
 macro_rules! debug{
     ($str:ident) => {{
-        crate::log::log(&$str, crate::log::LogLevel::Debug);
+        $crate::log::log(&$str, $crate::log::LogLevel::Debug);
     }};
 
     ($fmt_str:literal) => {{
-        crate::log::log($fmt_str, crate::log::LogLevel::Debug);
+        $crate::log::log($fmt_str, $crate::log::LogLevel::Debug);
     }};
 
     ($fmt_str:literal, $($args:expr),*) => {{
-        crate::log::log(&format!($fmt_str, $($args),*), crate::log::LogLevel::Debug);
+        $crate::log::log(&format!($fmt_str, $($args),*), $crate::log::LogLevel::Debug);
     }};
 }
 
+#[macro_export]
 macro_rules! trace{
     ($str:ident) => {{
-        crate::log::log(&$str, crate::log::LogLevel::Trace);
+        $crate::log::log(&$str, $crate::log::LogLevel::Trace);
     }};
 
     ($fmt_str:literal) => {{
-        crate::log::log($fmt_str, crate::log::LogLevel::Trace);
+        $crate::log::log($fmt_str, $crate::log::LogLevel::Trace);
     }};
 
     ($fmt_str:literal, $($args:expr),*) => {{
-        crate::log::log(&format!($fmt_str, $($args),*), crate::log::LogLevel::Trace);
+        $crate::log::log(&format!($fmt_str, $($args),*), $crate::log::LogLevel::Trace);
     }};
 }
 
-pub(crate) use {trace, debug, info, warning, error};
+// Exported at the crate root, re-exported here so they're used as log::info! and so on
+pub use crate::{trace, debug, info, warning, error};
@@ -1,16 +1,3 @@
-#![feature(int_roundings)]
-#![feature(let_chains)]
-
-mod buffered_reader;
-mod packets;
-mod nbt;
-mod net;
-mod util;
-mod world;
-mod game;
-mod ui;
-mod log;
-
 use ratatui::{
     layout::{Layout, Constraint, Flex},
     prelude::Direction,
@@ -20,60 +7,81 @@ use ratatui::{
         List, ListDirection
     },
 };
-use tokio::time::{interval, Duration};
-use std::path::PathBuf;
+use tokio::time::{interval, Duration, Instant};
 use std::error::Error;
 use std::sync::Arc;
 
+use mc_tui::{config, log, ui, Client};
 use ui::UiState;
 
+// Animations step once per this long whatever the draw rate, so they keep their speed
+const ANIMATION_STEP: Duration = Duration::from_millis(16);
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     log::info!("Application started");
-    let mut global_ctx = game::GlobalContext::init(PathBuf::from("resources"));
-    for i in 1..9 {
-        let player = game::Player::start("localhost", 25565, format!("UristMc_{}", i)).await?;
-        global_ctx.add_player(player, false).await;
+    let config = config::Config::load()?;
+    let mut client = Client::start(&config).await?;
+
+    // Without a terminal the game runs off commands read from stdin
+    if config.headless {
+        client.context_mut().read_stdin();
+        client.run().await;
+        return Ok(());
     }
-    global_ctx.set_active_player(0).await;
 
     let ui_state = UiState::init();
+    ui_state.set_entity_range(ui::EntityRange {
+        add_radius: config.entity_range,
+        remove_radius: config.entity_remove_range,
+        circle: config.entity_circle,
+    });
+    ui_state.set_palette(Arc::clone(&client.context().palette));
+    restore_on_panic(ratatui::restore);
 
-    let draw_join = draw_loop(Arc::clone(&ui_state));
-    let game_join = game_loop(ui_state,  global_ctx);
+    client.context_mut().read_keys();
+    let draw_join = draw_loop(Arc::clone(&ui_state), config.draw_rate, config.colors);
+    let game_join = tokio::task::spawn(async move {
+        client.run_rendering(&ui_state).await;
+    });
     tokio::join!(game_join, draw_join).0.unwrap();
     Ok(())
 }
 
-fn game_loop(
-    ui_state: Arc<UiState>,
-    ctx: game::GlobalContext,) 
-    -> tokio::task::JoinHandle<()> 
-{
-    let mut ctx = ctx;
-    let mut interval = interval(Duration::from_millis(50));
-    tokio::task::spawn(async move {
-        loop {
-            if ctx.stop {
-                break;
-            }
-            ctx.tick().await;
-            ctx.update_render(&ui_state).await;
-            interval.tick().await;
-        }
-        ui_state.stop();
-    })
+/// Puts the terminal back before the panic message gets printed
+fn restore_on_panic(restore: fn()) {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        hook(info);
+    }));
+}
+
+/// Restores the terminal whenever the draw loop ends, however it ends
+struct TerminalGuard(fn());
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        (self.0)();
+    }
 }
 
-fn draw_loop(ui_state: Arc<UiState>) -> tokio::task::JoinHandle<()> {
+fn draw_loop(ui_state: Arc<UiState>, draw_rate: u32, colors: ui::ColorSupport) -> tokio::task::JoinHandle<()> {
     let mut terminal = ratatui::init();
-    let mut interval = interval(Duration::from_millis(16));
+    let guard = TerminalGuard(ratatui::restore);
+    let mut interval = interval(Duration::from_secs(1) / draw_rate);
     let main_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints(vec![
             Constraint::Percentage(75),
             Constraint::Percentage(25),
         ]);
+    let log_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Percentage(70),
+            Constraint::Percentage(30),
+        ]);
     let center_layout = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(vec![
@@ -85,7 +93,7 @@ fn draw_loop(ui_state: Arc<UiState>) -> tokio::task::JoinHandle<()> {
         .direction(Direction::Vertical)
         .constraints(vec![
             Constraint::Fill(1),
-            Constraint::Length(3)
+            Constraint::Length(5)
         ])
         .flex(Flex::End);
     let bar_layout = Layout::default()
@@ -93,50 +101,160 @@ fn draw_loop(ui_state: Arc<UiState>) -> tokio::task::JoinHandle<()> {
         .constraints(vec![
             Constraint::Length(1),
             Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ]);
+    let status_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Fill(1),
+            Constraint::Length(16),
+        ]);
+    let split_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Fill(1),
+            Constraint::Fill(1),
         ]);
     let world_widget = ui::WorldWidget::new();
-    let mut tick = 0;
+    let mut world_state = ui::WorldWidgetState::init();
+    let mut split_state = ui::WorldWidgetState::init();
+    let started = Instant::now();
     tokio::task::spawn(async move {
+        let _guard = guard;
         loop {
-            if tick == std::usize::MAX {
-                tick = 0;
-            }
-            tick += 1;
+            let tick = (started.elapsed().as_millis() / ANIMATION_STEP.as_millis()) as usize;
             if ui_state.is_stop() {
                 break;
             }
-            let block = Block::bordered()
+            let snapshot = ui_state.snapshot();
+            let palette = ui_state.palette();
+            let border_color = palette.color("border");
+            let mut block = Block::bordered()
                 .border_type(BorderType::Rounded)
-                .border_style(Style::default().fg(Color::Rgb(194,255,102)));
+                .border_style(Style::default().fg(border_color));
+            if let Some(prompt) = snapshot.prompt.as_ref() {
+                block = block.title(format!(" {}_ ", prompt));
+            }
             let bar_block = Block::bordered()
                 .borders(Borders::ALL & !Borders::BOTTOM)
                 .border_type(BorderType::Rounded)
-                .border_style(Style::default().fg(Color::Rgb(194,255,102)));
+                .border_style(Style::default().fg(border_color))
+                .title(format!(" {} ", snapshot.player_name))
+                .title(ui::effects_line(&snapshot.effects).right_aligned());
             let log_widget = List::new(log::lines(16, log::LogLevel::Info))
                 .block(block)
                 .direction(ListDirection::BottomToTop);
+            let events_block = Block::bordered()
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(border_color))
+                .title(" Events ");
+            let events_widget = List::new(snapshot.events.clone())
+                .block(events_block)
+                .direction(ListDirection::BottomToTop);
             {
-                let food_bar = ui::BarWidget::construct(ui_state.food_bar.read().await.clone());
-                let hp_bar = ui::BarWidget::construct(ui_state.hp_bar.read().await.clone());
-                let world_state = &mut ui_state.world_state.write().await;
-                let entity_state = ui_state.entity_state.read().await;
+                let food_bar = ui::BarWidget::construct(snapshot.food_bar.clone());
+                let hp_bar = ui::BarWidget::construct(snapshot.hp_bar.clone());
+                let xp_bar = ui::BarWidget::construct(snapshot.xp_bar.clone());
+                let weather = ui::WeatherWidget::new(snapshot.status.raining, tick);
+                let status = ui::StatusWidget::construct(snapshot.status.clone(), Arc::clone(&palette));
+                let clock = ui::ClockWidget::construct(snapshot.clock, Arc::clone(&palette));
+                let disconnect = ui::DisconnectWidget::construct(snapshot.kicks.clone(), Arc::clone(&palette));
+                let player_list = ui::PlayerListWidget::construct(snapshot.online.clone(), Arc::clone(&palette));
+                let examine = ui::ExamineWidget::construct(snapshot.examine.clone());
+                let stats = ui::StatsWidget::construct(snapshot.stats.clone(), Arc::clone(&palette));
+                let metrics = ui::MetricsWidget::construct(snapshot.metrics.clone());
+                let map_item = ui::MapItemWidget::construct(snapshot.map_item.clone(), Arc::clone(&palette));
+                let enchant = ui::EnchantWidget::construct(snapshot.enchanting, Arc::clone(&palette));
+                let entity_state = &snapshot.entities;
+                world_state.set_map(&snapshot.map);
+                if let Some(split) = snapshot.split.as_ref() {
+                    split_state.set_map(&split.map);
+                }
                 terminal.draw(|frame| {
                     let layout = main_layout.split(frame.area());
+                    // The main map keeps the overlays, the split pane only shows terrain and players
+                    let map_area = match snapshot.split.as_ref() {
+                        Some(split) => {
+                            let panes = split_layout.split(layout[0]);
+                            frame.render_stateful_widget_ref(&world_widget, panes[1], &mut split_state);
+                            let title = format!(" {} ", split.title);
+                            frame.buffer_mut().set_stringn(
+                                panes[1].x,
+                                panes[1].y,
+                                &title,
+                                panes[1].width as usize,
+                                Style::default().fg(Color::Black).bg(border_color));
+                            panes[0]
+                        },
+                        None => layout[0]
+                    };
                     let bar_area = bottom_layout.split(
                         center_layout.split(layout[0])[1])[1];
                     let inner_bar_area = bar_block.inner(bar_area);
                     let inner_bar_areas = bar_layout.split(inner_bar_area);
-                    let entity_widget = ui::EntityOverlayWidget::new(&entity_state, tick);
-                    frame.render_stateful_widget_ref(&world_widget, layout[0], world_state);
-                    frame.render_widget_ref(&entity_widget, layout[0]);
-                    frame.render_widget(log_widget.clone(), layout[1]);
+                    let entity_widget = ui::EntityOverlayWidget::new(entity_state, tick);
+                    frame.render_stateful_widget_ref(&world_widget, map_area, &mut world_state);
+                    frame.render_widget_ref(&weather, layout[0]);
+                    frame.render_widget_ref(&entity_widget, map_area);
+                    frame.render_widget_ref(&player_list, layout[0]);
+                    frame.render_widget_ref(&stats, layout[0]);
+                    frame.render_widget_ref(&examine, map_area);
+                    frame.render_widget_ref(&map_item, map_area);
+                    frame.render_widget_ref(&enchant, map_area);
+                    frame.render_widget_ref(&metrics, layout[0]);
+                    frame.render_widget_ref(&disconnect, layout[0]);
+                    let log_areas = log_layout.split(layout[1]);
+                    frame.render_widget(log_widget.clone(), log_areas[0]);
+                    frame.render_widget(events_widget.clone(), log_areas[1]);
                     frame.render_widget_ref(bar_block, bar_area);
                     frame.render_widget_ref(&hp_bar, inner_bar_areas[0]);
                     frame.render_widget_ref(&food_bar, inner_bar_areas[1]);
+                    frame.render_widget_ref(&xp_bar, inner_bar_areas[2]);
+                    let status_areas = status_layout.split(inner_bar_areas[3]);
+                    frame.render_widget_ref(&status, status_areas[0]);
+                    frame.render_widget_ref(&clock, status_areas[1]);
+                    if colors == ui::ColorSupport::Xterm256 {
+                        ui::quantize_buffer(frame.buffer_mut());
+                    }
                 }).map_err(|e| format!("Draw call failed: {}", e)).unwrap();
+                if world_state.resized {
+                    world_state.resized = false;
+                    ui_state.set_slice_size(0, world_state.slice_size);
+                }
+                if split_state.resized {
+                    split_state.resized = false;
+                    ui_state.set_slice_size(1, split_state.slice_size);
+                }
             }
             interval.tick().await;
         }
-        ratatui::restore();
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Stands in for ratatui::restore, there's no terminal to put back in tests
+    static RESTORES: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_restore() {
+        RESTORES.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // One test for both, the panic hook is global to the test binary
+    #[test]
+    fn forced_panic_restores_the_terminal() {
+        restore_on_panic(count_restore);
+        let panicked = std::thread::spawn(|| {
+            let _guard = TerminalGuard(count_restore);
+            panic!("forced");
+        }).join();
+        let _ = std::panic::take_hook();
+        assert!(panicked.is_err());
+        // Once from the hook, once from the guard unwinding
+        assert_eq!(RESTORES.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,10 +1,13 @@
 use std::error::Error;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::Mutex;
 use tokio::net::{TcpStream, tcp::OwnedWriteHalf};
 use tokio::io::AsyncWriteExt;
-use crate::packets::{Packet, write, try_read, read};
+use tokio::time::{timeout, sleep, Duration};
+use crate::packets::{Packet, write, try_read};
 use crate::log;
 use crate::buffered_reader::BufferedReader;
 
@@ -12,8 +15,103 @@ use openssl::rsa::{Rsa, Padding};
 use openssl::symm::{Cipher, Mode, Crypter};
 use openssl::rand::rand_bytes;
 
+pub const PROTOCOL_VERSION: u8 = 61;
+// How long a server gets to answer the server list ping
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+// Wait before the first retry, doubled for every one after
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How hard to try reaching the server before giving up on a bot
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectPolicy {
+    // For the TCP connect and the login handshake together
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+/// What a server answers to the legacy server list ping
+#[derive(Debug)]
+pub struct ServerStatus {
+    // Only sent by 1.4 and newer servers
+    pub protocol: Option<u8>,
+    pub version: Option<String>,
+    pub motd: String,
+    pub players: u32,
+    pub max_players: u32,
+}
+
+impl ServerStatus {
+    /// 1.4+ servers answer "§1\0protocol\0version\0motd\0players\0max",
+    /// older ones "motd§players§max"
+    fn parse(response: &str) -> Result<Self, Box<dyn Error>> {
+        let bad = || format!("Bad server list ping response: {:?}", response);
+        if let Some(fields) = response.strip_prefix("§1\0") {
+            let fields: Vec<&str> = fields.split('\0').collect();
+            let [protocol, version, motd, players, max_players] = fields[..] else {
+                return Err(bad().into());
+            };
+            return Ok(Self {
+                protocol: protocol.parse().ok(),
+                version: Some(version.to_string()),
+                motd: motd.to_string(),
+                players: players.parse().map_err(|_| bad())?,
+                max_players: max_players.parse().map_err(|_| bad())?,
+            });
+        }
+        // The motd itself may contain §, the counts never do
+        let mut fields = response.rsplitn(3, '§');
+        let (Some(max_players), Some(players), Some(motd)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(bad().into());
+        };
+        Ok(Self {
+            protocol: None,
+            version: None,
+            motd: motd.to_string(),
+            players: players.parse().map_err(|_| bad())?,
+            max_players: max_players.parse().map_err(|_| bad())?,
+        })
+    }
+}
+
+/// Runs on the connection's reader task for every inbound packet, in order and before the game
+/// loop sees it. Reader tasks of different bots run concurrently on tokio's worker threads, so an
+/// observer shared between bots can be called from several threads at once. It also holds up
+/// reading for its bot while it runs, keep it short and don't block.
+pub type PacketObserver = Arc<dyn Fn(&Packet) + Send + Sync>;
+
+/// Observers of one connection. Cloning shares the list, so observers added later
+/// are seen by the reader task too. With none registered a packet costs one atomic load
+#[derive(Clone, Default)]
+pub struct Observers {
+    any: Arc<AtomicBool>,
+    list: Arc<RwLock<Vec<PacketObserver>>>,
+}
+
+impl Observers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&self, observer: PacketObserver) {
+        self.list.write().unwrap().push(observer);
+        self.any.store(true, Ordering::Release);
+    }
+
+    fn notify(&self, packet: &Packet) {
+        if !self.any.load(Ordering::Acquire) {
+            return;
+        }
+        for observer in self.list.read().unwrap().iter() {
+            observer(packet);
+        }
+    }
+}
+
 pub struct Connection {
     inbound: Receiver<Packet>,
+    observers: Observers,
+    // Packets read off the socket so far, bumped by the reader task
+    received: Arc<AtomicU64>,
     write: Mutex<OwnedWriteHalf>,
     encryption: bool,
     encrypter: Option<Mutex<Crypter>>,
@@ -50,10 +148,19 @@ impl Connection {
         }
     }
 
-    pub async fn recv(&mut self, buffer: &mut Vec<Packet>) {
-        if !self.inbound.is_empty() {
-            self.inbound.recv_many(buffer, 1000).await;
-        }
+    /// Registers an observer for every packet from now on, see PacketObserver
+    pub fn observe(&self, observer: impl Fn(&Packet) + Send + Sync + 'static) {
+        self.observers.add(Arc::new(observer));
+    }
+
+    /// Count of packets read off the socket, handled or not, for metrics
+    pub fn received_counter(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.received)
+    }
+
+    /// Next packet already read off the socket, if any
+    pub fn try_recv(&mut self) -> Option<Packet> {
+        self.inbound.try_recv().ok()
     }
 
     async fn enable_encryption(
@@ -98,13 +205,66 @@ impl Connection {
         Ok(())
     }
 
-    pub async fn connect_offline(host: &str, port: i32, username: &str) -> Result<Connection, Box<dyn Error>> {
+    /// Asks the server for its status without logging in, the server hangs up after answering
+    pub async fn ping(host: &str, port: i32) -> Result<ServerStatus, Box<dyn Error>> {
+        let address = format!("{}:{}", host, port);
+        let ping = async {
+            let (reader, mut writer) = TcpStream::connect(&address).await?.into_split();
+            writer.write_all(&write(Packet::ServerListPing { magic: 1 })).await?;
+            let mut buf_reader = BufferedReader::from_reader(reader);
+            match try_read(&mut buf_reader).await.map_err(|e| e.to_string())? {
+                Packet::Disconnect { reason } => ServerStatus::parse(&reason),
+                _ => Err("Wrong packet after server list ping - expected Disconnect".into())
+            }
+        };
+        timeout(PING_TIMEOUT, ping).await
+            .map_err(|_| format!("{} didn't answer within {}s", address, PING_TIMEOUT.as_secs()))?
+    }
+
+    /// Connects and logs in, retrying with backoff as the policy allows.
+    /// The observers see the login packets too
+    pub async fn connect(
+        host: &str,
+        port: i32,
+        username: &str,
+        policy: ConnectPolicy,
+        observers: Observers)
+        -> Result<Connection, Box<dyn Error>>
+    {
+        let attempts = policy.retries + 1;
+        for attempt in 1..=attempts {
+            log::info!("{} connecting to {}:{}, attempt {}/{}", username, host, port, attempt, attempts);
+            let connecting = Self::connect_offline(host, port, username, observers.clone());
+            let error = match timeout(policy.timeout, connecting).await {
+                Ok(Ok(connection)) => return Ok(connection),
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => format!("timed out after {}s", policy.timeout.as_secs_f32())
+            };
+            if attempt == attempts {
+                return Err(format!("{} gave up after {} attempts: {}", username, attempts, error).into());
+            }
+            let delay = RETRY_BACKOFF * 2u32.saturating_pow(attempt - 1);
+            log::warning!("{} failed to connect: {}, retrying in {}s", username, error, delay.as_secs());
+            sleep(delay).await;
+        }
+        unreachable!()
+    }
+
+    pub async fn connect_offline(
+        host: &str,
+        port: i32,
+        username: &str,
+        observers: Observers)
+        -> Result<Connection, Box<dyn Error>>
+    {
         let address = format!("{}:{}", host, port);
         let (reader, writer) = TcpStream::connect(&address).await?.into_split();
         log::info!("Connected to {}", address);
         let( tx, rx ) = tokio::sync::mpsc::channel::<Packet>(1000);
         let mut connection = Connection {
             inbound: rx, 
+            observers: observers.clone(),
+            received: Arc::new(AtomicU64::new(0)),
             write: Mutex::new(writer),
             encryption: false,
             encrypter: None,
@@ -112,7 +272,7 @@ impl Connection {
         };
 
         connection.send(Packet::Handshake {
-            protocol_version: 61,
+            protocol_version: PROTOCOL_VERSION,
             host: host.to_owned(),
             username: username.to_owned(),
             port: port
@@ -120,13 +280,17 @@ impl Connection {
 
         let mut buf_reader = BufferedReader::from_reader(reader);
 
-        if let Packet::EncryptionKeyRequest{pbkey, verify_token, ..} = read(&mut buf_reader).await {
+        let packet = try_read(&mut buf_reader).await.map_err(|e| e.to_string())?;
+        observers.notify(&packet);
+        if let Packet::EncryptionKeyRequest{pbkey, verify_token, ..} = packet {
             connection.enable_encryption(pbkey.as_ref(), verify_token.as_ref(), &mut buf_reader).await?;
         } else {
             return Err("Wrong packet after handshake - expected EncryptionKeyRequest".into())
         }
 
-        if let Packet::EncryptionKeyResponse{shared_secret, verify_token} = read(&mut buf_reader).await {
+        let packet = try_read(&mut buf_reader).await.map_err(|e| e.to_string())?;
+        observers.notify(&packet);
+        if let Packet::EncryptionKeyResponse{shared_secret, verify_token} = packet {
             if shared_secret.len() != 0 || verify_token.len() != 0 {
                 log::warning!("EncryptionKeyRespons wasn't empty - is something wrong?");
             }
@@ -136,6 +300,7 @@ impl Connection {
             return Err("Wrong packet after handshake - expected empty EncryptionKeyResponse".into())
         };
 
+        let received = connection.received_counter();
         connection.sender_loop = Some(tokio::task::spawn( async move {
             loop {
                 match try_read(&mut buf_reader).await {
@@ -144,6 +309,8 @@ impl Connection {
                         break;
                     },
                     Ok(packet) => {
+                        received.fetch_add(1, Ordering::Relaxed);
+                        observers.notify(&packet);
                         if let Err(_) = tx.send(packet).await {
                             log::error!("Error in receiver loop, channel closed!");
                             break;
@@ -154,5 +321,74 @@ impl Connection {
         }));
         Ok(connection)
     }
+
+    /// Logged in connection to a local socket without a server behind it. Packets put in the
+    /// sender are received as if read off the socket, the stream is the server's end
+    #[cfg(test)]
+    pub async fn mock() -> (Connection, tokio::sync::mpsc::Sender<Packet>, TcpStream) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let (_reader, writer) = client.into_split();
+        let (tx, rx) = tokio::sync::mpsc::channel::<Packet>(100);
+        let connection = Connection {
+            inbound: rx,
+            observers: Observers::new(),
+            received: Arc::new(AtomicU64::new(0)),
+            write: Mutex::new(writer),
+            encryption: false,
+            encrypter: None,
+            sender_loop: None
+        };
+        (connection, tx, server)
+    }
+
+    /// Closes the writing side so every send from now on fails
+    #[cfg(test)]
+    pub async fn break_writes(&self) {
+        self.write.lock().await.shutdown().await.unwrap();
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn notify_calls_every_observer_in_order() {
+        let observers = Observers::new();
+        let seen = Arc::new(Mutex::new(vec![]));
+        for name in ["first", "second"] {
+            let seen = Arc::clone(&seen);
+            observers.add(Arc::new(move |packet: &Packet| seen.lock().unwrap().push((name, format!("{:?}", packet)))));
+        }
+        observers.notify(&Packet::KeepAlive { keep_alive_id: 3 });
+        assert_eq!(*seen.lock().unwrap(), [
+            ("first", "KeepAlive { keep_alive_id: 3 }".to_string()),
+            ("second", "KeepAlive { keep_alive_id: 3 }".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn notify_without_observers_does_nothing() {
+        let observers = Observers::new();
+        observers.notify(&Packet::KeepAlive { keep_alive_id: 3 });
+        // Slipped in behind the flag's back, only the atomic load should happen
+        observers.list.write().unwrap().push(Arc::new(|_: &Packet| panic!("list walked without observers")));
+        observers.notify(&Packet::KeepAlive { keep_alive_id: 3 });
+    }
+
+    // The reader task holds a clone made before any observer is added
+    #[test]
+    fn clones_see_observers_added_later() {
+        let observers = Observers::new();
+        let reader_side = observers.clone();
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = Arc::clone(&calls);
+        observers.add(Arc::new(move |_: &Packet| { counted.fetch_add(1, Ordering::Relaxed); }));
+        reader_side.notify(&Packet::ServerListPing { magic: 1 });
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
@@ -1,6 +1,5 @@
 use std::error::Error;
 
-use crate::log;
 use crate::buffered_reader::BufferedReader;
 use crate::nbt::NbtData;
 
@@ -17,7 +16,7 @@ async fn read_nbt_data(data: &mut BufferedReader) -> Result<Option<NbtData>, Box
     Ok(Some(NbtData::from_bytes(&data.read_bytes(nbt_length as usize).await?[..])))
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Slot {
     Empty,
     Item{id: i16, count: i8, damage: i16},
@@ -44,7 +43,7 @@ async fn read_slot(data: &mut BufferedReader) -> Result<Slot, Box<dyn Error + Se
     })
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Metadata {
     on_fire: bool,
     crouching: bool,
@@ -53,10 +52,22 @@ pub struct Metadata {
     acting: bool,
     invisible: bool,
     name: Option<String>,
-    unknown: Vec<u8>
+    // Fields not decoded above by id, kept as read so they can be written back
+    unknown: Vec<(u8, MetadataValue)>
     // TODO other metadata
 }
 
+#[derive(Debug, PartialEq)]
+pub enum MetadataValue {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Float(f32),
+    String(String),
+    Slot(Slot),
+    Position(i32, i32, i32),
+}
+
 async fn read_metadata(data: &mut BufferedReader) -> Result<Metadata, Box<dyn Error + Send + Sync>> {
     let mut metadata = Metadata {
         on_fire: false,
@@ -92,25 +103,24 @@ async fn read_metadata(data: &mut BufferedReader) -> Result<Metadata, Box<dyn Er
             metadata.name = Some(data.read_string().await?);
             continue;
         }
-        metadata.unknown.push(id);
-        match data_type {
-            0 => {data.read_byte().await?;},
-            1 => {data.read_short().await?;},
-            2 => {data.read_int().await?;},
-            3 => {data.read_float().await?;},
-            4 => {data.read_string().await?;},
-            5 => {read_slot(data).await?;},
-            6 => {
-                let _x = data.read_int().await?;
-                let _y = data.read_int().await?;
-                let _z = data.read_int().await?;
-            },
+        let value = match data_type {
+            0 => MetadataValue::Byte(data.read_byte().await?),
+            1 => MetadataValue::Short(data.read_short().await?),
+            2 => MetadataValue::Int(data.read_int().await?),
+            3 => MetadataValue::Float(data.read_float().await?),
+            4 => MetadataValue::String(data.read_string().await?),
+            5 => MetadataValue::Slot(read_slot(data).await?),
+            6 => MetadataValue::Position(
+                data.read_int().await?,
+                data.read_int().await?,
+                data.read_int().await?),
             _ => panic!("Unknown entity metadata field type: {data_type}")
-        }
+        };
+        metadata.unknown.push((id, value));
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ObjectData {
     integer: i32,
     dx: Option<i16>,
@@ -136,7 +146,7 @@ async fn read_object_data(data: &mut BufferedReader) -> Result<ObjectData, Box<d
     })
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct MultiBlockChangeData {
     pub x: i32,
     pub z: i32,
@@ -156,7 +166,7 @@ async fn read_multi_block_change_data(data: &mut BufferedReader) -> Result<Multi
     })
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ChunkMetainfo {
     pub x: i32,
     pub z: i32,
@@ -164,7 +174,7 @@ pub struct ChunkMetainfo {
     pub add: u16
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ChunkDataBulk {
     pub column_count: u16,
     pub has_skylight: bool,
@@ -172,7 +182,7 @@ pub struct ChunkDataBulk {
     pub metainfo: Vec::<ChunkMetainfo>
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ChunkData {
     pub ground_up_continuous: bool,
     pub compressed: Box<[u8]>,
@@ -225,7 +235,7 @@ async fn read_chunk_data_bulk(data: &mut BufferedReader) -> Result<ChunkDataBulk
     })
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct BlockOffsetRecords {
     pub offsets: Vec<(i8, i8, i8)>,
     pub dx: f32,
@@ -348,7 +358,8 @@ macro_rules! write_field {
         $vec.push($field);
     };
     ($vec: ident, $field: ident, String) => {
-        let length = $field.len() as i16;
+        // In UTF-16 code units, not bytes
+        let length = $field.encode_utf16().count() as i16;
         write_field!($vec, length, i16);
         $field.encode_utf16().flat_map(|i| i.to_be_bytes()).for_each(|x| $vec.push(x));
     };
@@ -365,10 +376,9 @@ macro_rules! write_field {
         }
     };
     ($vec: ident, $field: ident, NbtData) => {
-        let len = $field.len() as i32;
-        write_field!($vec, len, i32);
-        let bytes = $field.to_bytes();
-        write_field!($vec, bytes, Bytes);
+        let len = $field.len() as i16;
+        write_field!($vec, len, i16);
+        $vec.extend_from_slice(&$field.to_bytes());
     };
     ($vec: ident, $field: ident, Slot) => {
         match $field {
@@ -380,6 +390,9 @@ macro_rules! write_field {
                 write_field!($vec, id, i16);
                 write_field!($vec, count, i8);
                 write_field!($vec, damage, i16);
+                // No NBT
+                let len = -1i16;
+                write_field!($vec, len, i16);
             }
             Slot::ItemNbt {id, count, damage, nbt} => {
                 write_field!($vec, id, i16);
@@ -405,26 +418,90 @@ macro_rules! write_field {
         panic!("object data serialization is not supported");
     };
     ($vec: ident, $field: ident, Metadata) => {
-        panic!("metadata serialization is not supported");
+        write_metadata(&mut $vec, &$field);
     };
     ($vec: ident, $field: ident, VecSlot) => {
-        panic!("vector serialization is not supported");
+        let len = $field.len() as u16;
+        write_field!($vec, len, u16);
+        for slot in $field {
+            write_field!($vec, slot, Slot);
+        }
     };
     ($vec: ident, $field: ident, Veci32) => {
-        panic!("vector serialization is not supported");
+        let len = $field.len() as u8;
+        write_field!($vec, len, u8);
+        for value in $field {
+            write_field!($vec, value, i32);
+        }
     };
     ($vec: ident, $field: ident, VecString) => {
-        panic!("vector serialization is not supported");
+        let len = $field.len() as u8;
+        write_field!($vec, len, u8);
+        for value in $field {
+            write_field!($vec, value, String);
+        }
     };
     ($vec: ident, $field: ident, $type: ty) => {
         $field.to_be_bytes().into_iter().for_each(|x| $vec.push(x));
     };
 }
 
+// Flags and name go first, the other fields follow in the order they were read
+fn write_metadata(out: &mut Vec<u8>, metadata: &Metadata) {
+    let mut flags = 0u8;
+    if metadata.on_fire { flags |= 0x01; }
+    if metadata.crouching { flags |= 0x02; }
+    if metadata.riding { flags |= 0x04; }
+    if metadata.sprinting { flags |= 0x08; }
+    if metadata.acting { flags |= 0x10; }
+    if metadata.invisible { flags |= 0x20; }
+    out.push(0x00);
+    out.push(flags);
+    if let Some(name) = metadata.name.as_ref() {
+        out.push((4 << 5) | 5);
+        write_field!(out, name, String);
+    }
+    for &(id, ref value) in metadata.unknown.iter() {
+        match value {
+            MetadataValue::Byte(value) => {
+                out.push(id);
+                write_field!(out, value, i8);
+            },
+            MetadataValue::Short(value) => {
+                out.push((1 << 5) | id);
+                write_field!(out, value, i16);
+            },
+            MetadataValue::Int(value) => {
+                out.push((2 << 5) | id);
+                write_field!(out, value, i32);
+            },
+            MetadataValue::Float(value) => {
+                out.push((3 << 5) | id);
+                write_field!(out, value, f32);
+            },
+            MetadataValue::String(value) => {
+                out.push((4 << 5) | id);
+                write_field!(out, value, String);
+            },
+            MetadataValue::Slot(value) => {
+                out.push((5 << 5) | id);
+                write_field!(out, value, Slot);
+            },
+            MetadataValue::Position(x, y, z) => {
+                out.push((6 << 5) | id);
+                write_field!(out, x, i32);
+                write_field!(out, y, i32);
+                write_field!(out, z, i32);
+            },
+        }
+    }
+    out.push(0x7F);
+}
+
 macro_rules! protocol {
     ($($packet_type: ident <$packet_id: literal> { $($field: ident: $field_type: tt),+ }),+) => {
         // TODO optimize packet size?
-        #[derive(Debug)]
+        #[derive(Debug, PartialEq)]
         pub enum Packet {
             $(
                 $packet_type {
@@ -435,17 +512,6 @@ macro_rules! protocol {
             )*
         }
 
-        pub async fn read(reader: &mut BufferedReader) -> Packet {
-            match try_read(reader).await {
-                Ok(packet) => {
-                    return packet;
-                },
-                Err(e) => {
-                    log::error!("Error while reading packet: {}", e.to_string());
-                    panic!("Error while reading packet: {}", e.to_string());
-                }
-            }
-        }
         pub async fn try_read(reader: &mut BufferedReader) -> Result<Packet, Box<dyn Error + Send + Sync>> {
             let id = reader.read_ubyte().await?;
             match id {
@@ -929,3 +995,212 @@ protocol! (
         reason: String
     }
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut out = (value.encode_utf16().count() as i16).to_be_bytes().to_vec();
+        value.encode_utf16().for_each(|c| out.extend(c.to_be_bytes()));
+        out
+    }
+
+    // Packet isn't Clone, so the expected value is built again from the same closure.
+    // BufferedReader keeps its buffers inline, a few of those overflow a test thread's stack
+    async fn round_trip(packet: impl Fn() -> Packet) {
+        let mut reader = Box::new(BufferedReader::from_bytes(write(packet())));
+        assert_eq!(try_read(&mut reader).await.unwrap(), packet());
+        assert!(reader.read_ubyte().await.is_err(), "bytes left over after {:?}", packet());
+    }
+
+    #[tokio::test]
+    async fn metadata_round_trip() {
+        // Flags and name first, in the order write_metadata puts them
+        let mut bytes = vec![0x00, 0x22, (4 << 5) | 5];
+        bytes.extend(string("Steve"));
+        bytes.extend([16, 0xFE]);
+        bytes.push((1 << 5) | 12);
+        bytes.extend(300i16.to_be_bytes());
+        bytes.push((2 << 5) | 8);
+        bytes.extend((-70000i32).to_be_bytes());
+        bytes.push((3 << 5) | 6);
+        bytes.extend(17.5f32.to_be_bytes());
+        bytes.push((4 << 5) | 18);
+        bytes.extend(string("wither"));
+        bytes.push((5 << 5) | 10);
+        bytes.extend((-1i16).to_be_bytes());
+        bytes.push((6 << 5) | 17);
+        [1i32, -2, 300].iter().for_each(|v| bytes.extend(v.to_be_bytes()));
+        bytes.push(0x7F);
+
+        let metadata = read_metadata(&mut BufferedReader::from_bytes(bytes.clone())).await.unwrap();
+        assert!(metadata.crouching && metadata.invisible && !metadata.on_fire);
+        assert_eq!(metadata.name.as_deref(), Some("Steve"));
+        assert_eq!(metadata.unknown, vec![
+            (16, MetadataValue::Byte(-2)),
+            (12, MetadataValue::Short(300)),
+            (8, MetadataValue::Int(-70000)),
+            (6, MetadataValue::Float(17.5)),
+            (18, MetadataValue::String("wither".to_string())),
+            (10, MetadataValue::Slot(Slot::Empty)),
+            (17, MetadataValue::Position(1, -2, 300)),
+        ]);
+
+        let mut written = vec![];
+        write_metadata(&mut written, &metadata);
+        assert_eq!(written, bytes);
+    }
+
+    #[tokio::test]
+    async fn slot_vec_round_trip() {
+        round_trip(|| Packet::SetWindowItems { window_id: 0, slots: vec![] }).await;
+        round_trip(|| Packet::SetWindowItems {
+            window_id: 1,
+            slots: vec![
+                Slot::Empty,
+                Slot::Item { id: 1, count: 64, damage: 0 },
+                Slot::ItemNbt { id: 276, count: 1, damage: 12, nbt: NbtData::from_bytes(&[0x1F, 0x8B, 0x08, 0x00]) },
+                Slot::Item { id: 35, count: 3, damage: 14 },
+            ]
+        }).await;
+    }
+
+    #[tokio::test]
+    async fn int_vec_round_trip() {
+        round_trip(|| Packet::EntityDestroy { ids: vec![] }).await;
+        round_trip(|| Packet::EntityDestroy { ids: vec![7, -1, i32::MAX] }).await;
+    }
+
+    #[tokio::test]
+    async fn string_vec_round_trip() {
+        let teams = |players: Vec<String>| Packet::Teams {
+            name: "red".to_string(),
+            mode: 3,
+            display_name: String::new(),
+            prefix: String::new(),
+            suffix: String::new(),
+            firendly_fire: 0,
+            player_count: players.len() as u16,
+            players
+        };
+        round_trip(|| teams(vec![])).await;
+        round_trip(|| teams(vec!["Steve".to_string(), String::new(), "§cAlex".to_string()])).await;
+    }
+
+    fn metadata() -> Metadata {
+        Metadata {
+            on_fire: true,
+            crouching: false,
+            riding: false,
+            sprinting: true,
+            acting: false,
+            invisible: false,
+            name: Some("Alex".to_string()),
+            unknown: vec![(16, MetadataValue::Byte(3)), (10, MetadataValue::Slot(Slot::Item { id: 264, count: 2, damage: 0 }))]
+        }
+    }
+
+    fn item() -> Slot {
+        Slot::ItemNbt { id: 387, count: 1, damage: 0, nbt: NbtData::from_bytes(&[0x1F, 0x8B, 0x08, 0x00, 0x00]) }
+    }
+
+    // ChunkData, ChunkDataBulk, MultiBlockChange, Explosion and SpawnObject carry fields
+    // that can't be written, see write_field
+    #[tokio::test]
+    async fn every_writable_packet_round_trips() {
+        let text = || "§eHello, мир".to_string();
+        round_trip(|| Packet::KeepAlive { keep_alive_id: -12345 }).await;
+        round_trip(|| Packet::LoginRequest { entity_id: 42, level_type: "default".to_string(), game_mode: 1, dimension: -1, difficulty: 2, unused: 0, max_players: 20 }).await;
+        round_trip(|| Packet::Handshake { protocol_version: 61, username: "bot".to_string(), host: "localhost".to_string(), port: 25565 }).await;
+        round_trip(|| Packet::ChatMessage { message: text() }).await;
+        round_trip(|| Packet::TimeUpdate { age: i64::MAX, time: -6000 }).await;
+        round_trip(|| Packet::EntityEquipment { eid: 3, slot: 4, item: item() }).await;
+        round_trip(|| Packet::SpawnPosition { x: -100, y: 64, z: 100 }).await;
+        round_trip(|| Packet::UseEntity { user: 1, target: 2, mouse_button: true }).await;
+        round_trip(|| Packet::UpdateHealth { health: 20, food: 17, saturation: 4.5 }).await;
+        round_trip(|| Packet::Respawn { dim: 1, difficulty: 3, game_mode: 0, height: 256, level_type: "flat".to_string() }).await;
+        round_trip(|| Packet::Player { on_ground: false }).await;
+        round_trip(|| Packet::PlayerPosition { x: 0.5, y: 64.0, stance: 65.62, z: -0.5, on_ground: true }).await;
+        round_trip(|| Packet::PlayerLook { yaw: -90.0, pitch: 45.5, on_ground: true }).await;
+        round_trip(|| Packet::PlayerPositionAndLook { x: 1.25, y: 70.0, stance: 71.62, z: -3.75, yaw: 180.0, pitch: -10.0, on_ground: false }).await;
+        round_trip(|| Packet::PlayerDigging { status: 2, x: -7, y: 255, z: 9, face: 5 }).await;
+        round_trip(|| Packet::PlayerBlockPlacement { x: -1, y: 0xFF, z: -1, dir: 0xFF, item: Slot::Item { id: 1, count: 5, damage: 0 }, cur_x: 8, cur_y: 16, cur_z: 0 }).await;
+        round_trip(|| Packet::HeldItemChange { slot_id: 8 }).await;
+        round_trip(|| Packet::UseBed { eid: 5, unknown: 0, x: 10, y: 64, z: -10 }).await;
+        round_trip(|| Packet::Animation { eid: 5, anim: 1 }).await;
+        round_trip(|| Packet::EntityAction { eid: 5, action: 3 }).await;
+        round_trip(|| Packet::SpawnNamedEntity { eid: 9, name: "Alex".to_string(), x: 32, y: 2048, z: -32, yaw: 128, pitch: 0, item: 276, metadata: metadata() }).await;
+        round_trip(|| Packet::CollectItem { collected: 11, collector: 9 }).await;
+        round_trip(|| Packet::SpawnMob { eid: 12, mob_type: 50, x: 0, y: 2048, z: 0, pitch: -5, head_pitch: 10, yaw: 64, dx: -100, dy: 0, dz: 8000, metadata: metadata() }).await;
+        round_trip(|| Packet::SpawnPainting { eid: 13, title: "Kebab".to_string(), x: 1, y: 65, z: 1, dir: 2 }).await;
+        round_trip(|| Packet::SpawnExperienceOrb { eid: 14, x: 3, y: 4, z: 5, count: 17 }).await;
+        round_trip(|| Packet::EntityVelocity { eid: 12, dx: -3, dy: 400, dz: 0 }).await;
+        round_trip(|| Packet::EntityDestroy { ids: vec![11, 12] }).await;
+        round_trip(|| Packet::Entity { eid: 12 }).await;
+        round_trip(|| Packet::EntityRelativeMove { eid: 12, dx: -128, dy: 127, dz: 0 }).await;
+        round_trip(|| Packet::EntityLook { eid: 12, yaw: 64, pitch: -64 }).await;
+        round_trip(|| Packet::EntityLookAndRelativeMove { eid: 12, dx: 1, dy: -1, dz: 2, yaw: 3, pitch: -3 }).await;
+        round_trip(|| Packet::EntityTeleport { eid: 12, x: -320, y: 2080, z: 96, yaw: 0, pitch: 1 }).await;
+        round_trip(|| Packet::EntityHeadLook { eid: 12, yaw: -100 }).await;
+        round_trip(|| Packet::EntityStatus { eid: 12, status: 2 }).await;
+        round_trip(|| Packet::EntityAttach { eid: 9, vehicle_eid: -1 }).await;
+        round_trip(|| Packet::EntityMetadata { eid: 9, metadata: metadata() }).await;
+        round_trip(|| Packet::EntityEffect { eid: 9, effect_id: 1, amplifier: 2, duration: 600 }).await;
+        round_trip(|| Packet::RemoveEntityEffect { eid: 9, effect_id: 1 }).await;
+        round_trip(|| Packet::SetExperience { bar: 0.25, level: 30, total: 1395 }).await;
+        round_trip(|| Packet::BlockChange { x: -17, y: 70, z: 33, block_type: 4095, block_meta: 15 }).await;
+        round_trip(|| Packet::BlockAction { x: 4, y: 64, z: 4, hb: 1, lb: 2, block_id: 54 }).await;
+        round_trip(|| Packet::BlockBreakAnimation { eid: 9, x: 4, y: 64, z: 4, destroy_stage: 7 }).await;
+        round_trip(|| Packet::SoundOrParticleEffect { effect_id: 1003, x: 4, y: 64, z: 4, data: 0, absolute: false }).await;
+        round_trip(|| Packet::NamedSoundEffect { name: "random.click".to_string(), x: 32, y: 512, z: 32, volume: 1.0, pitch: 63 }).await;
+        round_trip(|| Packet::Particle { name: "heart".to_string(), x: 0.5, y: 65.0, z: 0.5, dx: 0.25, dy: 0.0, dz: 0.25, speed: 1.0, count: 7 }).await;
+        round_trip(|| Packet::ChangeGameState { reason: 3, game_mode: 1 }).await;
+        round_trip(|| Packet::SpawnGlobalEntity { eid: 20, etype: 1, x: 320, y: 2048, z: -320 }).await;
+        round_trip(|| Packet::OpenWindow { window_id: 2, inv_type: 0, title: "Chest".to_string(), slots: 27, use_title: true }).await;
+        round_trip(|| Packet::CloseWindow { window_id: 2 }).await;
+        round_trip(|| Packet::ClickWindow { window_id: 2, slot: 36, button: 1, action: 7, mode: 0, item: Slot::Empty }).await;
+        round_trip(|| Packet::SetSlot { window_id: -1, slot: -1, item: item() }).await;
+        round_trip(|| Packet::SetWindowItems { window_id: 0, slots: vec![Slot::Empty, item()] }).await;
+        round_trip(|| Packet::UpdateWindowProperty { window_id: 3, property: 2, value: 30 }).await;
+        round_trip(|| Packet::ConfirmTransaction { window_id: 2, action_number: 7, is_accepted: false }).await;
+        round_trip(|| Packet::CreativeInventoryAction { slot: 36, item: Slot::Item { id: 2, count: 64, damage: 0 } }).await;
+        round_trip(|| Packet::EnchantItem { window_id: 3, enchantement: 2 }).await;
+        round_trip(|| Packet::UpdateSign { x: 1, y: 64, z: 1, text_1: text(), text_2: String::new(), text_3: "line 3".to_string(), text_4: "4".to_string() }).await;
+        round_trip(|| Packet::ItemData { item_type: 358, item_id: 0, text: Box::from([1u8, 2, 3].as_slice()) }).await;
+        round_trip(|| Packet::UpdateTileEntity { x: 1, y: 64, z: 1, action: 1, nbt: NbtData::from_bytes(&[0x1F, 0x8B]) }).await;
+        round_trip(|| Packet::IncrementStat { stat_id: 1004, amount: 1 }).await;
+        round_trip(|| Packet::PlayerListItem { name: "Alex".to_string(), online: true, pink: 150 }).await;
+        round_trip(|| Packet::PlayerAbilities { flags: 0x0D, flying_speed: 12, walking_speed: 25 }).await;
+        round_trip(|| Packet::TabComplete { text: "/gam".to_string() }).await;
+        round_trip(|| Packet::ClientSettings { locale: "en_US".to_string(), view_distance: 2, chat_flags: 8, difficulty: 1, show_cape: true }).await;
+        round_trip(|| Packet::ClientStatuses { payload: 1 }).await;
+        round_trip(|| Packet::ScoreboardObjective { name: "deaths".to_string(), value: "Deaths".to_string(), cr: 0 }).await;
+        round_trip(|| Packet::UpdateScore { item_name: "Alex".to_string(), ur: 0, score_name: "deaths".to_string(), value: -3 }).await;
+        round_trip(|| Packet::DisplayScoreboard { pos: 1, name: "deaths".to_string() }).await;
+        round_trip(|| Packet::Teams { name: "blue".to_string(), mode: 0, display_name: "Blue".to_string(), prefix: "§9".to_string(), suffix: String::new(), firendly_fire: 1, player_count: 1, players: vec!["Alex".to_string()] }).await;
+        round_trip(|| Packet::PluginMessage { channel: "MC|Brand".to_string(), data: Box::from(b"vanilla".as_slice()) }).await;
+        round_trip(|| Packet::EncryptionKeyResponse { shared_secret: Box::from([]), verify_token: Box::from([9u8; 4].as_slice()) }).await;
+        round_trip(|| Packet::EncryptionKeyRequest { server_id: "-".to_string(), pbkey: Box::from([0x30u8; 162].as_slice()), verify_token: Box::from([1u8, 2, 3, 4].as_slice()) }).await;
+        round_trip(|| Packet::ServerListPing { magic: 1 }).await;
+        round_trip(|| Packet::Disconnect { reason: text() }).await;
+    }
+
+    // What the bots send after spawning, in the order the 1.5.2 server reads it
+    #[test]
+    fn client_settings_layout() {
+        let bytes = write(Packet::ClientSettings {
+            locale: "en_US".to_string(),
+            view_distance: 0,
+            chat_flags: 8,
+            difficulty: 2,
+            show_cape: true
+        });
+        let mut expected = vec![0xCC];
+        expected.extend(string("en_US"));
+        expected.extend([0, 8, 2, 1]);
+        assert_eq!(bytes, expected);
+        assert_eq!(&bytes[1..3], &[0, 5]);
+        assert_eq!(&bytes[3..13], &[0, b'e', 0, b'n', 0, b'_', 0, b'U', 0, b'S']);
+    }
+}
@@ -17,7 +17,9 @@ pub enum BarWidgetDirection {
 pub enum BarWidgetMode {
     NoText,
     Value,
-    ValueWithMaxValue
+    ValueWithMaxValue,
+    // Filled by the given 0.0-1.0 fraction instead of value/max, labeled with the value
+    Fraction(f32)
 }
 
 pub struct BarWidget {
@@ -38,13 +40,23 @@ impl WidgetRef for &BarWidget {
             BarWidgetMode::NoText => String::new(),
             BarWidgetMode::Value => self.state.value.to_string(),
             BarWidgetMode::ValueWithMaxValue => format!("{}/{}", self.state.value, self.state.max_value),
+            BarWidgetMode::Fraction(_) => self.state.value.to_string(),
+        };
+        let value = match self.state.mode {
+            BarWidgetMode::Fraction(fraction) => fraction.clamp(0., 1.) as f64 * self.state.max_value as f64,
+            _ => self.state.value as f64
         };
         let charcount = text.chars().count() as u16;
         let has_text = charcount <= area.width;
 
         match self.state.direction {
             BarWidgetDirection::Horizontal => {
-                let bar_length = area.width - charcount;
+                // Too narrow for the label, give the whole width to the bar
+                let label_width = if has_text {charcount} else {0};
+                let bar_length = area.width - label_width;
+                if bar_length == 0 {
+                    return;
+                }
                 let division = self.state.max_value as f64 / bar_length as f64;
                 let subdivision = division as f64 / HORIZONTAL.len() as f64;
                 if has_text {
@@ -55,12 +67,12 @@ impl WidgetRef for &BarWidget {
                     }
                 }
 
-                for i in 0..area.width-charcount {
+                for i in 0..bar_length {
                     for j in 0..area.height {
-                        let cell = &mut buf[(i+charcount+area.x, j+area.y)];
-                        if division * (i as f64 + 1.0) > self.state.value as f64{
-                            let index = (self.state.value as f64 - (i as f64) * division) / subdivision;
-                            cell.set_char(HORIZONTAL[index as usize]);
+                        let cell = &mut buf[(i+label_width+area.x, j+area.y)];
+                        if division * (i as f64 + 1.0) > value {
+                            let index = ((value - (i as f64) * division) / subdivision).max(0.) as usize;
+                            cell.set_char(HORIZONTAL[index.min(HORIZONTAL.len() - 1)]);
                         } else {
                             cell.set_char(FULL_BLOCK);
                         }
@@ -70,23 +82,30 @@ impl WidgetRef for &BarWidget {
             },
 
             BarWidgetDirection::Vertical => {
+                // Label takes the top row, the bar fills the rest from the bottom up
+                let has_text = has_text && charcount > 0 && area.height > 1;
                 let bar_length = area.height - if has_text {1} else {0};
+                if bar_length == 0 {
+                    return;
+                }
                 let division = self.state.max_value as f64 / bar_length as f64;
                 let subdivision = division as f64 / VERTICAL.len() as f64;
                 if has_text {
+                    let offset = (area.width - charcount) / 2;
                     for (i, character) in text.chars().enumerate() {
-                        let cell = &mut buf[(area.width >> 1 - charcount >> 1 + i, 0)];
+                        let cell = &mut buf[(area.x + offset + i as u16, area.y)];
                         cell.set_char(character);
                         cell.fg = self.state.color;
                     }
                 }
 
-                for j in charcount-1..area.height {
+                for k in 0..bar_length {
+                    let j = area.y + area.height - 1 - k;
                     for i in 0..area.width {
-                        let cell = &mut buf[(i, j)];
-                        if division * (i as f64 + 1.0) > self.state.value as f64{
-                            let index = (self.state.value as f64 - (i as f64) * division) / subdivision;
-                            cell.set_char(VERTICAL[index as usize]);
+                        let cell = &mut buf[(area.x + i, j)];
+                        if division * (k as f64 + 1.0) > value {
+                            let index = ((value - (k as f64) * division) / subdivision).max(0.) as usize;
+                            cell.set_char(VERTICAL[index.min(VERTICAL.len() - 1)]);
                         } else {
                             cell.set_char(FULL_BLOCK);
                         }
@@ -106,3 +125,49 @@ pub struct BarWidgetState {
     pub value: u16,
     pub max_value: u16,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(mode: BarWidgetMode, direction: BarWidgetDirection, value: u16, max_value: u16, area: Rect) -> Vec<String> {
+        let widget = BarWidget::construct(BarWidgetState { mode, color: Color::Red, direction, value, max_value });
+        let mut buf = Buffer::empty(area);
+        (&widget).render_ref(area, &mut buf);
+        (area.top()..area.bottom())
+            .map(|y| (area.left()..area.right()).map(|x| buf[(x, y)].symbol()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn vertical_bar_fills_from_the_bottom() {
+        let area = Rect::new(0, 0, 1, 4);
+        let bar = |value| render(BarWidgetMode::NoText, BarWidgetDirection::Vertical, value, 8, area);
+        assert_eq!(bar(8), ["█", "█", "█", "█"]);
+        assert_eq!(bar(4), [" ", " ", "█", "█"]);
+        assert_eq!(bar(3), [" ", " ", "▄", "█"]);
+        assert_eq!(bar(0), [" ", " ", " ", " "]);
+    }
+
+    #[test]
+    fn vertical_bar_label_on_top() {
+        let rows = render(BarWidgetMode::Value, BarWidgetDirection::Vertical, 5, 10, Rect::new(2, 1, 3, 6));
+        assert_eq!(rows, [" 5 ", "   ", "   ", "▄▄▄", "███", "███"]);
+        // One row is all the bar gets, the label goes
+        let rows = render(BarWidgetMode::Value, BarWidgetDirection::Vertical, 10, 10, Rect::new(0, 0, 2, 1));
+        assert_eq!(rows, ["██"]);
+    }
+
+    #[test]
+    fn three_wide_horizontal_bar() {
+        let area = Rect::new(0, 0, 3, 1);
+        let bar = |mode, value, max| render(mode, BarWidgetDirection::Horizontal, value, max, area);
+        assert_eq!(bar(BarWidgetMode::Value, 1, 2), ["1█ "]);
+        // "30/60" doesn't fit, the bar gets the whole width
+        assert_eq!(bar(BarWidgetMode::ValueWithMaxValue, 30, 60), ["█▌ "]);
+        assert_eq!(bar(BarWidgetMode::ValueWithMaxValue, 60, 60), ["███"]);
+        // The label fits exactly and leaves no room for a bar
+        assert_eq!(bar(BarWidgetMode::ValueWithMaxValue, 3, 6), ["   "]);
+        assert_eq!(bar(BarWidgetMode::Fraction(2.0), 7, 9), ["7██"]);
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use ratatui::widgets::WidgetRef;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+
+use super::Palette;
+
+const TICKS_PER_DAY: i64 = 24000;
+// Time 0 is sunrise, 06:00
+const DAWN_HOUR: i64 = 6;
+
+/// In-game time of day and day counter, e.g. "Day 3 18:30"
+pub struct ClockWidget {
+    age: i64,
+    time: i64,
+    palette: Arc<Palette>
+}
+
+impl ClockWidget {
+    pub fn construct((age, time): (i64, i64), palette: Arc<Palette>) -> ClockWidget {
+        Self {
+            age,
+            time,
+            palette
+        }
+    }
+}
+
+impl WidgetRef for &ClockWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        // Negative time means the daylight cycle is stopped at that time
+        let time = self.time.abs().rem_euclid(TICKS_PER_DAY);
+        let hours = (time / 1000 + DAWN_HOUR) % 24;
+        let minutes = time % 1000 * 60 / 1000;
+        let text = format!("Day {} {:02}:{:02}", self.age / TICKS_PER_DAY, hours, minutes);
+        let width = (text.chars().count() as u16).min(area.width);
+        buf.set_stringn(
+            area.x + area.width - width,
+            area.y,
+            text,
+            width as usize,
+            Style::default().fg(self.palette.color("border")));
+    }
+}
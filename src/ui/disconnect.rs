@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+use ratatui::widgets::{Block, BorderType, Clear, Widget, WidgetRef};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+
+use super::Palette;
+
+/// Lists players kicked by the server, drawn over the middle of the map
+pub struct DisconnectWidget {
+    kicks: Vec<(String, String)>,
+    palette: Arc<Palette>
+}
+
+impl DisconnectWidget {
+    pub fn construct(kicks: Vec<(String, String)>, palette: Arc<Palette>) -> DisconnectWidget {
+        Self {
+            kicks,
+            palette
+        }
+    }
+}
+
+impl WidgetRef for &DisconnectWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        if self.kicks.is_empty() {
+            return;
+        }
+        // Same red as health
+        let color = self.palette.color("hp");
+        let lines: Vec<String> = self.kicks.iter()
+            .map(|(name, reason)| format!("{}: {}", name, reason))
+            .collect();
+        let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16;
+        let width = (longest + 4).max(24).min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height
+        };
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(color))
+            .title(" Disconnected ")
+            .title_bottom(" Enter to dismiss ");
+        let inner = block.inner(popup);
+        Clear.render(popup, buf);
+        block.render_ref(popup, buf);
+        for (i, line) in lines.iter().take(inner.height as usize).enumerate() {
+            buf.set_stringn(
+                inner.x + 1,
+                inner.y + i as u16,
+                line,
+                inner.width.saturating_sub(1) as usize,
+                Style::default().fg(color));
+        }
+    }
+}
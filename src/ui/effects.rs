@@ -0,0 +1,44 @@
+use ratatui::style::{Style, Color};
+use ratatui::text::{Line, Span};
+
+use crate::game::Effect;
+
+pub const POISON: u8 = 19;
+pub const WITHER: u8 = 20;
+
+fn effect_symbol(id: u8) -> (&'static str, Color) {
+    match id {
+        1 => ("Spd", Color::Rgb(124, 175, 198)),
+        2 => ("Slw", Color::Rgb(90, 108, 129)),
+        3 => ("Hst", Color::Rgb(217, 192, 67)),
+        4 => ("Fat", Color::Rgb(74, 66, 23)),
+        5 => ("Str", Color::Rgb(147, 36, 35)),
+        8 => ("Jmp", Color::Rgb(34, 255, 76)),
+        9 => ("Nau", Color::Rgb(85, 29, 74)),
+        10 => ("Reg", Color::Rgb(205, 92, 171)),
+        11 => ("Res", Color::Rgb(153, 69, 58)),
+        12 => ("FRs", Color::Rgb(228, 154, 58)),
+        13 => ("Wtr", Color::Rgb(46, 82, 153)),
+        14 => ("Inv", Color::Rgb(127, 131, 146)),
+        15 => ("Bln", Color::Rgb(31, 31, 35)),
+        16 => ("NVs", Color::Rgb(31, 31, 161)),
+        17 => ("Hun", Color::Rgb(88, 118, 83)),
+        18 => ("Wea", Color::Rgb(72, 77, 72)),
+        POISON => ("Psn", Color::Rgb(78, 147, 49)),
+        WITHER => ("Wth", Color::Rgb(53, 42, 39)),
+        _ => ("???", Color::Gray),
+    }
+}
+
+/// Short colored tags for every active effect, e.g. "Psn2 0:12"
+pub fn effects_line(effects: &[Effect]) -> Line<'static> {
+    let mut spans = vec![];
+    for effect in effects {
+        let (symbol, color) = effect_symbol(effect.id);
+        let seconds = effect.remaining / 20;
+        spans.push(Span::styled(
+            format!(" {}{} {}:{:02} ", symbol, effect.amplifier + 1, seconds / 60, seconds % 60),
+            Style::default().fg(color)));
+    }
+    Line::from(spans)
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use ratatui::widgets::{Block, BorderType, Clear, Widget, WidgetRef};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Style, Color};
+
+use super::Palette;
+
+const TOO_COSTLY_COLOR: Color = Color::Rgb(120, 120, 120);
+
+/// Options of the enchantment table the active player has open, with their level costs.
+/// Picked with `/enchant <1-3>`
+pub struct EnchantWidget {
+    // Cost of every option and the player's level
+    window: Option<([i16; 3], i16)>,
+    palette: Arc<Palette>
+}
+
+impl EnchantWidget {
+    pub fn construct(window: Option<([i16; 3], i16)>, palette: Arc<Palette>) -> EnchantWidget {
+        Self {
+            window,
+            palette
+        }
+    }
+}
+
+impl WidgetRef for &EnchantWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let Some((costs, level)) = self.window else {
+            return;
+        };
+        let color = self.palette.color("border");
+        let width = 26.min(area.width);
+        let height = 5.min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height
+        };
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(color))
+            .title(" Enchant ");
+        let inner = block.inner(popup);
+        Clear.render(popup, buf);
+        block.render_ref(popup, buf);
+        for (i, cost) in costs.iter().enumerate().take(inner.height as usize) {
+            let y = inner.y + i as u16;
+            let (text, fg) = match *cost {
+                0 => ("no item".to_string(), TOO_COSTLY_COLOR),
+                cost if cost > level => (format!("level {}", cost), TOO_COSTLY_COLOR),
+                // Levels come out of the xp bar, so affordable ones share its color
+                cost => (format!("level {}", cost), self.palette.color("xp"))
+            };
+            buf.set_stringn(inner.x + 1, y, format!("{}", i + 1), inner.width.saturating_sub(1) as usize,
+                Style::default().fg(color));
+            let text_x = (inner.x + inner.width).saturating_sub(text.chars().count() as u16 + 1).max(inner.x);
+            buf.set_string(text_x, y, text, Style::default().fg(fg));
+        }
+    }
+}
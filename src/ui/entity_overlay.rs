@@ -1,10 +1,40 @@
-use std::collections::HashSet;
+use std::collections::{HashSet, HashMap};
 use ratatui::style::Color;
 use ratatui::widgets::WidgetRef;
 use ratatui::buffer::Buffer;
 use ratatui::layout::{Rect, Position};
 
 use crate::game::Entity;
+use super::{RenderMode, ascii_glyph};
+
+// How far off the slice plane entities still show up in the side view
+const SIDE_VIEW_RANGE: i32 = 8;
+// Game ticks a pickup or an explosion stays highlighted
+pub const PICKUP_FLASH_TICKS: u64 = 10;
+pub const PICKUP_FLASH_COLOR: Color = Color::Rgb(142, 142, 0);
+pub const EXPLOSION_FLASH_TICKS: u64 = 20;
+pub const EXPLOSION_FLASH_COLOR: Color = Color::Rgb(255, 120, 0);
+pub const PARTICLE_FLASH_TICKS: u64 = 4;
+pub const PARTICLE_FLASH_COLOR: Color = Color::Rgb(180, 180, 220);
+pub const LIGHTNING_TICKS: u64 = 10;
+const LIGHTNING_COLOR: Color = Color::Rgb(255, 255, 200);
+const LIGHTNING_FLASH_COLOR: Color = Color::Rgb(120, 140, 255);
+const CRACK_COLOR: Color = Color::Rgb(200, 200, 200);
+pub const ANIMATION_TICKS: u64 = 4;
+const ARMORED_COLOR: Color = Color::Rgb(90, 90, 110);
+
+// Sprite shown in place of an entity while it plays an animation
+fn animation_sprite(anim: u8) -> Option<(char, Color)> {
+    match anim {
+        1 => Some(('/', Color::Rgb(255, 255, 255))),
+        2 => Some(('*', Color::Rgb(255, 60, 60))),
+        5 => Some(('o', Color::Rgb(200, 160, 100))),
+        6 | 7 => Some(('+', Color::Rgb(120, 200, 255))),
+        _ => None
+    }
+}
+
+static CRACKS: [char; 3] = ['░', '▒', '▓'];
 
 static ROLLING: [char; 4] = [
     '\\', '|', '/', '-'
@@ -24,10 +54,23 @@ impl<'a> EntityOverlayWidget<'a> {
     }
 }
 
+#[derive(Clone)]
 pub struct EntityOverlayState {
     pub cells: Vec<EntityCell>,
     pub visible: HashSet<i32>,
-    pub camera: (i32, i32, i32)
+    pub camera: (i32, i32, i32),
+    pub zoom: i32,
+    pub render_mode: RenderMode,
+    // Highlighted positions, the game tick they fade at and their color
+    pub flashes: Vec<((i32, i32, i32), u64, Color)>,
+    // Blocks being mined with their destroy stage
+    pub breaking: Vec<((i32, i32, i32), u8)>,
+    // Lightning strikes and the game tick they fade at
+    pub lightning: Vec<((i32, i32, i32), u64)>,
+    // Entity id to its animation and the game tick it ends at
+    pub animations: HashMap<i32, (u8, u64)>,
+    // Draw sprites with ASCII stand-ins
+    pub ascii: bool,
 }
 
 impl EntityOverlayState {
@@ -36,6 +79,13 @@ impl EntityOverlayState {
             cells: vec![],
             camera: (0, 0, 0),
             visible: HashSet::new(),
+            zoom: 1,
+            render_mode: RenderMode::TopDown,
+            flashes: vec![],
+            breaking: vec![],
+            lightning: vec![],
+            animations: HashMap::new(),
+            ascii: false,
         }
     }
 
@@ -45,6 +95,7 @@ impl EntityOverlayState {
             id: entity.id,
             y: pos.1,
             frames: entity.sprites_or_default(),
+            armored: entity.is_armored(),
         };
         if let Some(cell) = self.cells.iter_mut().find(|c| c.x == pos.0 && c.z == pos.2) {
             entity_render.set_depth(pos.1, cam_depth);
@@ -74,7 +125,7 @@ impl EntityOverlayState {
                 if cell.entity_index != 0 {
                     cell.entity_index -= 1;
                 }
-                if let EntityCellState::Rolling = cell.state && cell.entities.len() == 1 {
+                if matches!(cell.state, EntityCellState::Rolling) && cell.entities.len() == 1 {
                     cell.state = EntityCellState::Entity;
                 }
             }
@@ -84,19 +135,41 @@ impl EntityOverlayState {
         }
     }
 
-    pub fn remove_cells(&mut self, to_remove: &mut Vec<usize>) {
-        to_remove.sort_unstable_by(|a, b| b.cmp(a));
-        for index in to_remove {
-            let cell = &self.cells[*index];
-            for entity in &cell.entities {
-                self.visible.remove(&entity.id);
+    /// Removes an entity from whichever cell it's drawn in
+    pub fn forget(&mut self, entity_id: i32) {
+        let cell = self.cells.iter()
+            .find(|c| c.entities.iter().any(|e| e.id == entity_id))
+            .map(|c| (c.x, 0, c.z));
+        match cell {
+            Some(pos) => self.remove(entity_id, pos),
+            None => {
+                self.visible.remove(&entity_id);
+            }
+        }
+    }
+
+    /// Drops every entity whose position `keep` rejects, and the cells left empty
+    pub fn retain_entities(&mut self, keep: impl Fn((i32, i32, i32)) -> bool) {
+        let EntityOverlayState { cells, visible, .. } = self;
+        for cell in cells.iter_mut() {
+            let (x, z) = (cell.x, cell.z);
+            cell.entities.retain(|entity| {
+                let kept = keep((x, entity.y, z));
+                if !kept {
+                    visible.remove(&entity.id);
+                }
+                kept
+            });
+            cell.entity_index = cell.entity_index.min(cell.entities.len().saturating_sub(1));
+            if matches!(cell.state, EntityCellState::Rolling) && cell.entities.len() < 2 {
+                cell.state = EntityCellState::Entity;
             }
-            self.cells.swap_remove(*index);
         }
+        cells.retain(|cell| !cell.entities.is_empty());
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EntityCell {
     pub x: i32,
     pub z: i32,
@@ -105,7 +178,7 @@ pub struct EntityCell {
     pub entities: Vec<EntityCellRender>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum EntityCellState {
     Entity,
     Rolling
@@ -113,11 +186,12 @@ pub enum EntityCellState {
 
 type EntityRender = (char, (u8, u8, u8), Option<(u8, u8, u8)>);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EntityCellRender {
     pub id: i32,
     pub y: i32,
-    pub frames: Vec<EntityRender>
+    pub frames: Vec<EntityRender>,
+    pub armored: bool,
 }
 
 impl EntityCellRender {
@@ -154,17 +228,77 @@ impl EntityCellRender {
 impl<'a> WidgetRef for &EntityOverlayWidget<'a> {
     fn render_ref(&self, area: Rect, buf: &mut Buffer) {
         let state = self.state;
+        let center = (area.width/2, area.height/2);
+        let glyph = |c: char| if state.ascii { ascii_glyph(c) } else { c };
+        for (pos, stage) in state.breaking.iter() {
+            let visible = match state.render_mode {
+                RenderMode::TopDown => (pos.1 - state.camera.1).abs() <= 1,
+                RenderMode::Side => pos.2 == state.camera.2,
+            };
+            if !visible {
+                continue;
+            }
+            let x = (pos.0 - state.camera.0).div_euclid(state.zoom) + center.0 as i32;
+            let y = match state.render_mode {
+                RenderMode::TopDown => (pos.2 - state.camera.2).div_euclid(state.zoom),
+                RenderMode::Side => (state.camera.1 - pos.1).div_euclid(state.zoom),
+            } + center.1 as i32;
+            if x < 0 || x >= area.width as i32 || y < 0 || y >= area.height as i32 {
+                continue;
+            }
+            if let Some(cell) = buf.cell_mut(Position {x: area.x + x as u16, y: area.y + y as u16}) {
+                cell.set_char(glyph(CRACKS[(*stage as usize * CRACKS.len() / 10).min(CRACKS.len() - 1)]));
+                cell.set_fg(CRACK_COLOR);
+            }
+        }
+        for (pos, _, color) in state.flashes.iter() {
+            let x = (pos.0 - state.camera.0).div_euclid(state.zoom) + center.0 as i32;
+            let y = match state.render_mode {
+                RenderMode::TopDown => (pos.2 - state.camera.2).div_euclid(state.zoom),
+                RenderMode::Side => (state.camera.1 - pos.1).div_euclid(state.zoom),
+            } + center.1 as i32;
+            if x < 0 || x >= area.width as i32 || y < 0 || y >= area.height as i32 {
+                continue;
+            }
+            if let Some(cell) = buf.cell_mut(Position {x: area.x + x as u16, y: area.y + y as u16}) {
+                cell.set_bg(*color);
+            }
+        }
+        // Bolts come down from the sky, so they show whatever the height
+        for (pos, _) in state.lightning.iter() {
+            let x = (pos.0 - state.camera.0).div_euclid(state.zoom) + center.0 as i32;
+            let y = match state.render_mode {
+                RenderMode::TopDown => (pos.2 - state.camera.2).div_euclid(state.zoom),
+                RenderMode::Side => (state.camera.1 - pos.1).div_euclid(state.zoom),
+            } + center.1 as i32;
+            if x < 0 || x >= area.width as i32 || y < 0 || y >= area.height as i32 {
+                continue;
+            }
+            if let Some(cell) = buf.cell_mut(Position {x: area.x + x as u16, y: area.y + y as u16}) {
+                cell.set_char(glyph('↯'));
+                cell.set_fg(LIGHTNING_COLOR);
+                cell.set_bg(LIGHTNING_FLASH_COLOR);
+            }
+        }
         if state.cells.is_empty() {
             return;
         }
-        let center = (area.width/2, area.height/2);
         for entity in state.cells.iter() {
-            let x = entity.x + center.0 as i32 - state.camera.0;
-            let y = entity.z + center.1 as i32 - state.camera.2;
-            if x < 0 || x > area.width as i32 || y < 0 || y > area.height as i32 {
+            let x = (entity.x - state.camera.0).div_euclid(state.zoom) + center.0 as i32;
+            let y = match state.render_mode {
+                RenderMode::TopDown => (entity.z - state.camera.2).div_euclid(state.zoom) + center.1 as i32,
+                RenderMode::Side => {
+                    if (entity.z - state.camera.2).abs() > SIDE_VIEW_RANGE {
+                        continue;
+                    }
+                    let height = entity.entities[entity.entity_index].y;
+                    (state.camera.1 - height).div_euclid(state.zoom) + center.1 as i32
+                }
+            };
+            if x < 0 || x >= area.width as i32 || y < 0 || y >= area.height as i32 {
                 continue;
             }
-            if let Some(cell) = buf.cell_mut(Position {x: x as u16, y: y as u16}) {
+            if let Some(cell) = buf.cell_mut(Position {x: area.x + x as u16, y: area.y + y as u16}) {
                 match entity.state {
                     EntityCellState::Rolling => {
                         cell.set_char(ROLLING[(self.tick % (ROLLING.len() * 4)) / 4]);
@@ -172,13 +306,26 @@ impl<'a> WidgetRef for &EntityOverlayWidget<'a> {
                     },
                     EntityCellState::Entity => {
                         let to_draw = &entity.entities[entity.entity_index];
-                        let entity_frame = ((self.tick % 120) as f64 / (120 as f64 / to_draw.frames.len() as f64)) as usize;
-                        let entity_render = &to_draw.frames[entity_frame as usize];
-                        cell.set_char(entity_render.0);
+                        let animation = state.animations.get(&to_draw.id)
+                            .and_then(|(anim, _)| animation_sprite(*anim));
+                        if let Some((sprite, color)) = animation {
+                            cell.set_char(sprite);
+                            cell.set_fg(color);
+                            continue;
+                        }
+                        let frame_count = to_draw.frames.len();
+                        if frame_count == 0 {
+                            continue;
+                        }
+                        let entity_frame = (self.tick % 120) * frame_count / 120 % frame_count;
+                        let entity_render = &to_draw.frames[entity_frame];
+                        cell.set_char(glyph(entity_render.0));
                         let color = entity_render.1;
                         cell.set_fg(Color::Rgb(color.0, color.1, color.2));
                         if let Some(color) = entity_render.2 {
                             cell.set_bg(Color::Rgb(color.0, color.1, color.2));
+                        } else if to_draw.armored {
+                            cell.set_bg(ARMORED_COLOR);
                         }
                     }
                 }
@@ -187,3 +334,75 @@ impl<'a> WidgetRef for &EntityOverlayWidget<'a> {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(id: i32, x: i32, z: i32, frames: Vec<EntityRender>) -> EntityCell {
+        EntityCell {
+            x,
+            z,
+            state: EntityCellState::Entity,
+            entity_index: 0,
+            entities: vec![EntityCellRender { id, y: 0, frames, armored: false }]
+        }
+    }
+
+    fn frames(glyphs: &str) -> Vec<EntityRender> {
+        glyphs.chars().map(|c| (c, (255, 255, 255), None)).collect()
+    }
+
+    fn render(state: &EntityOverlayState, tick: usize, area: Rect) -> Buffer {
+        let mut buf = Buffer::empty(area);
+        (&EntityOverlayWidget::new(state, tick)).render_ref(area, &mut buf);
+        buf
+    }
+
+    #[test]
+    fn every_frame_shows_over_a_cycle() {
+        let mut state = EntityOverlayState::init();
+        state.cells.push(cell(1, 0, 0, frames("abcdefg")));
+        state.cells.push(cell(2, 1, 0, frames("z")));
+        // Drawn as nothing rather than dividing by zero
+        state.cells.push(cell(3, 2, 0, vec![]));
+        let area = Rect::new(0, 0, 9, 9);
+        let mut seen = HashSet::new();
+        for tick in 0..240 {
+            let buf = render(&state, tick, area);
+            seen.insert(buf[(4, 4)].symbol().to_string());
+            assert_eq!(buf[(5, 4)].symbol(), "z");
+            assert_eq!(buf[(6, 4)].symbol(), " ");
+        }
+        let expected: HashSet<String> = "abcdefg".chars().map(String::from).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn entities_at_the_far_corner_are_drawn() {
+        let mut state = EntityOverlayState::init();
+        state.cells.push(cell(1, 4, 4, frames("a")));
+        state.cells.push(cell(2, -4, -4, frames("b")));
+        // One past the edge on either axis
+        state.cells.push(cell(3, 5, 4, frames("c")));
+        state.cells.push(cell(4, 4, 5, frames("d")));
+        let area = Rect::new(2, 3, 9, 9);
+        let buf = render(&state, 0, area);
+        assert_eq!(buf[(10, 11)].symbol(), "a");
+        assert_eq!(buf[(2, 3)].symbol(), "b");
+        let drawn = buf.content.iter().filter(|c| c.symbol() != " ").count();
+        assert_eq!(drawn, 2);
+
+        // Zoomed out cells cover the same blocks as the world widget's
+        state.zoom = 2;
+        state.cells.clear();
+        state.cells.push(cell(1, 9, 9, frames("a")));
+        state.cells.push(cell(2, -8, -8, frames("b")));
+        state.cells.push(cell(3, -9, 0, frames("c")));
+        let buf = render(&state, 0, area);
+        assert_eq!(buf[(10, 11)].symbol(), "a");
+        assert_eq!(buf[(2, 3)].symbol(), "b");
+        let drawn = buf.content.iter().filter(|c| c.symbol() != " ").count();
+        assert_eq!(drawn, 2);
+    }
+}
@@ -0,0 +1,56 @@
+use ratatui::widgets::{Block, BorderType, Clear, Widget, WidgetRef};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Style, Color};
+
+const COLOR: Color = Color::Rgb(230, 210, 150);
+
+/// Name and properties of the examined block, drawn in the top left corner of the map
+pub struct ExamineWidget {
+    lines: Option<Vec<String>>
+}
+
+impl ExamineWidget {
+    pub fn construct(lines: Option<Vec<String>>) -> ExamineWidget {
+        Self {
+            lines
+        }
+    }
+}
+
+impl WidgetRef for &ExamineWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let Some(lines) = self.lines.as_ref() else {
+            return;
+        };
+        let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16;
+        let width = (longest + 4).max(20).min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let popup = Rect {
+            x: area.x + 1.min(area.width - width),
+            y: area.y + 1.min(area.height - height),
+            width,
+            height
+        };
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(COLOR))
+            .title(" Examine ");
+        let inner = block.inner(popup);
+        Clear.render(popup, buf);
+        block.render_ref(popup, buf);
+        for (i, line) in lines.iter().take(inner.height as usize).enumerate() {
+            // Name first and brighter, properties under it
+            let style = match i {
+                0 => Style::default().fg(Color::White),
+                _ => Style::default().fg(COLOR)
+            };
+            buf.set_stringn(
+                inner.x + 1,
+                inner.y + i as u16,
+                line,
+                inner.width.saturating_sub(1) as usize,
+                style);
+        }
+    }
+}
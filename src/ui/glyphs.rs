@@ -0,0 +1,50 @@
+// ASCII stand-ins for the glyphs blocks and entities are drawn with, for fonts that lack them
+const ASCII: &[(char, char)] = &[
+    // Solid and shaded blocks
+    ('█', '#'), ('▓', '#'), ('▒', '%'), ('░', ':'), ('■', '#'), ('▣', '#'), ('▤', '='),
+    ('▞', '%'), ('▪', '.'), ('▬', '-'), ('▋', '|'), ('◆', '*'),
+    // Half blocks, stairs and doors
+    ('▀', '"'), ('▄', '_'), ('▌', '['), ('▐', ']'), ('▔', '-'), ('▁', '_'), ('▏', '|'), ('▕', '|'),
+    ('▟', 'J'), ('▛', 'P'), ('▙', 'L'), ('▜', '7'),
+    // Rails, wires, fences and walls
+    ('─', '-'), ('│', '|'), ('┌', '+'), ('┐', '+'), ('└', '+'), ('┘', '+'), ('├', '+'),
+    ('┤', '+'), ('┬', '+'), ('┴', '+'), ('┼', '+'), ('═', '='), ('║', 'H'), ('╔', '/'),
+    ('╗', '\\'), ('╚', '\\'), ('╝', '/'), ('╖', '+'), ('╘', '+'), ('╛', '+'), ('╜', '+'),
+    ('╞', '>'), ('╡', '<'), ('╥', 'v'), ('╨', '^'), ('•', '.'), ('∙', '.'),
+    // Arrows, flowing fluids and facings
+    ('←', '<'), ('↑', '^'), ('→', '>'), ('↓', 'v'), ('⇩', 'v'), ('≈', '~'), ('↤', '<'),
+    ('↥', '^'), ('↦', '>'), ('↧', 'v'), ('◂', '<'), ('▸', '>'), ('▴', '^'), ('▾', 'v'),
+    ('▼', 'V'), ('⍐', '^'), ('⍗', 'v'), ('⍇', '<'), ('⍈', '>'), ('⊢', '>'), ('⊣', '<'),
+    ('⊤', 'v'), ('⊥', '^'), ('○', 'o'), ('●', 'O'), ('◌', 'o'), ('◉', '@'),
+    // Chests, double chests and containers
+    ('⌺', 'C'), ('⌸', 'F'), ('⊏', '['), ('⊐', ']'), ('⊓', 'n'), ('⊔', 'u'), ('σ', 'a'),
+    // Plants and crops
+    ('ፑ', 'T'), ('⚘', 'r'), ('❀', 'y'), ('Ⱄ', 'm'), ('⸾', '$'), ('⊪', '|'), ('ψ', 'w'),
+    ('Ψ', 'W'), ('ɩ', 'i'), ('ϖ', 'P'),
+    // Everything else
+    ('༈', 'i'), ('‘', '\''), ('‼', '!'), ('Ж', 'X'), ('⍦', 'Y'), ('⎽', '_'), ('☼', '*'),
+    ('♪', 'n'), ('Ñ', 'N'), ('Λ', 'A'), ('↯', 'Z'),
+];
+
+/// ASCII glyph drawn in place of `c`, anything without an entry shows up as `?`
+pub fn ascii_glyph(c: char) -> char {
+    if c.is_ascii() {
+        return c;
+    }
+    ASCII.iter()
+        .find(|(glyph, _)| *glyph == c)
+        .map(|(_, ascii)| *ascii)
+        .unwrap_or('?')
+}
+
+/// Whether the locale promises UTF-8. An unset locale is taken as yes, Unicode being the default
+pub fn unicode_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            let value = value.to_lowercase();
+            value.contains("utf-8") || value.contains("utf8")
+        })
+        .unwrap_or(true)
+}
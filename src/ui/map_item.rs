@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use ratatui::widgets::{Block, BorderType, Clear, Widget, WidgetRef};
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Rect, Position};
+use ratatui::style::{Style, Color};
+
+use crate::game::{MapItem, MAP_SIZE};
+use super::Palette;
+
+// Parchment where nobody has explored yet
+const UNEXPLORED_COLOR: (u8, u8, u8) = (70, 60, 45);
+
+// Base map colors of 1.5.2, by id
+const BASE_COLORS: [(u8, u8, u8); 14] = [
+    (0, 0, 0), // nothing
+    (127, 178, 56), // grass
+    (247, 233, 163), // sand
+    (199, 199, 199), // cloth
+    (255, 0, 0), // tnt
+    (160, 160, 255), // ice
+    (167, 167, 167), // iron
+    (0, 124, 0), // foliage
+    (255, 255, 255), // snow
+    (164, 168, 184), // clay
+    (183, 106, 47), // dirt
+    (112, 112, 112), // stone
+    (64, 64, 255), // water
+    (104, 83, 50), // wood
+];
+
+/// Color of a map pixel, None for unexplored. The low bits shade the base color by height
+fn map_color(pixel: u8) -> Option<(u8, u8, u8)> {
+    let base = *BASE_COLORS.get((pixel / 4) as usize)?;
+    if pixel / 4 == 0 {
+        return None;
+    }
+    let shade = match pixel & 0x3 {
+        0 => 180,
+        1 => 220,
+        2 => 255,
+        _ => 135
+    };
+    let channel = |v: u8| (v as u16 * shade / 255) as u8;
+    Some((channel(base.0), channel(base.1), channel(base.2)))
+}
+
+/// Average color of the `size` square of pixels from `(x, y)`, leaving out unexplored ones
+fn average(map: &MapItem, x: usize, y: usize, size: usize) -> (u8, u8, u8) {
+    let mut sum = (0u32, 0u32, 0u32);
+    let mut count = 0;
+    for py in y..(y + size).min(MAP_SIZE) {
+        for px in x..(x + size).min(MAP_SIZE) {
+            if let Some(c) = map_color(map.pixel(px, py)) {
+                sum = (sum.0 + c.0 as u32, sum.1 + c.1 as u32, sum.2 + c.2 as u32);
+                count += 1;
+            }
+        }
+    }
+    match count {
+        0 => UNEXPLORED_COLOR,
+        _ => ((sum.0 / count) as u8, (sum.1 / count) as u8, (sum.2 / count) as u8)
+    }
+}
+
+/// Filled map opened with `/map`, downscaled to fit the screen. Every cell is two map rows,
+/// the upper half block in the top one's color over the bottom one's
+pub struct MapItemWidget {
+    map: Option<(i16, Arc<MapItem>)>,
+    palette: Arc<Palette>
+}
+
+impl MapItemWidget {
+    pub fn construct(map: Option<(i16, Arc<MapItem>)>, palette: Arc<Palette>) -> MapItemWidget {
+        Self {
+            map,
+            palette
+        }
+    }
+}
+
+impl WidgetRef for &MapItemWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let Some((id, map)) = self.map.as_ref() else {
+            return;
+        };
+        if area.width < 3 || area.height < 3 {
+            return;
+        }
+        // Map pixels per cell side, the same across and down to keep the map square
+        let fit_width = MAP_SIZE.div_ceil(area.width as usize - 2);
+        let fit_height = MAP_SIZE.div_ceil((area.height as usize - 2) * 2);
+        let step = fit_width.max(fit_height).max(1);
+        let cells = MAP_SIZE.div_ceil(step);
+        let width = cells as u16 + 2;
+        let height = cells.div_ceil(2) as u16 + 2;
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height
+        };
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(self.palette.color("border")))
+            .title(format!(" Map #{} (1:{}) ", id, 1 << map.scale.min(4)));
+        let inner = block.inner(popup);
+        Clear.render(popup, buf);
+        block.render_ref(popup, buf);
+        for cy in 0..inner.height as usize {
+            for cx in 0..inner.width as usize {
+                let (x, y) = (cx * step, cy * 2 * step);
+                let top = average(map, x, y, step);
+                let bottom = match y + step < MAP_SIZE {
+                    true => average(map, x, y + step, step),
+                    false => UNEXPLORED_COLOR
+                };
+                if let Some(cell) = buf.cell_mut(Position { x: inner.x + cx as u16, y: inner.y + cy as u16 }) {
+                    cell.set_char('▀')
+                        .set_fg(Color::Rgb(top.0, top.1, top.2))
+                        .set_bg(Color::Rgb(bottom.0, bottom.1, bottom.2));
+                }
+            }
+        }
+    }
+}
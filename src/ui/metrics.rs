@@ -0,0 +1,58 @@
+use ratatui::widgets::{Block, BorderType, Clear, Widget, WidgetRef};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Style, Color};
+
+const COLOR: Color = Color::Rgb(150, 200, 255);
+const VALUE_COLOR: Color = Color::Rgb(230, 230, 230);
+
+/// Debug counters, drawn in the top right corner of the map
+pub struct MetricsWidget {
+    lines: Option<Vec<(String, String)>>
+}
+
+impl MetricsWidget {
+    pub fn construct(lines: Option<Vec<(String, String)>>) -> MetricsWidget {
+        Self {
+            lines
+        }
+    }
+}
+
+impl WidgetRef for &MetricsWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let Some(lines) = self.lines.as_ref() else {
+            return;
+        };
+        let longest = lines.iter()
+            .map(|(label, value)| label.chars().count() + value.chars().count())
+            .max()
+            .unwrap_or(0) as u16;
+        let width = (longest + 6).max(20).min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let popup = Rect {
+            x: (area.x + area.width).saturating_sub(width + 1).max(area.x),
+            y: area.y + 1.min(area.height - height),
+            width,
+            height
+        };
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(COLOR))
+            .title(" Debug ");
+        let inner = block.inner(popup);
+        Clear.render(popup, buf);
+        block.render_ref(popup, buf);
+        for (i, (label, value)) in lines.iter().take(inner.height as usize).enumerate() {
+            let y = inner.y + i as u16;
+            buf.set_stringn(
+                inner.x + 1,
+                y,
+                label,
+                inner.width.saturating_sub(1) as usize,
+                Style::default().fg(COLOR));
+            let value_x = (inner.x + inner.width).saturating_sub(value.chars().count() as u16 + 1).max(inner.x);
+            buf.set_string(value_x, y, value, Style::default().fg(VALUE_COLOR));
+        }
+    }
+}
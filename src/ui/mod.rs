@@ -1,64 +1,261 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
+use ratatui::buffer::Buffer;
 use ratatui::style::Color;
-use tokio::sync::RwLock;
 
 mod world;
 mod bar;
 mod entity_overlay;
+mod status;
+mod disconnect;
+mod effects;
+mod weather;
+mod player_list;
+mod clock;
+mod examine;
+mod stats;
+mod metrics;
+mod palette;
+mod map_item;
+mod enchant;
+mod glyphs;
 
 use bar::{BarWidgetState, BarWidgetDirection, BarWidgetMode};
-use world::WorldWidgetState;
-use entity_overlay::{EntityCellState, EntityOverlayState};
+use world::MapSlice;
+use entity_overlay::{
+    EntityCellState, EntityOverlayState,
+    PICKUP_FLASH_TICKS, PICKUP_FLASH_COLOR, EXPLOSION_FLASH_TICKS, EXPLOSION_FLASH_COLOR,
+    PARTICLE_FLASH_TICKS, PARTICLE_FLASH_COLOR, LIGHTNING_TICKS, ANIMATION_TICKS
+};
 
 pub use {
     bar::BarWidget,
-    world::WorldWidget,
-    entity_overlay::EntityOverlayWidget
+    world::{WorldWidget, WorldWidgetState},
+    entity_overlay::EntityOverlayWidget,
+    status::{StatusWidget, StatusWidgetState},
+    disconnect::DisconnectWidget,
+    effects::effects_line,
+    weather::WeatherWidget,
+    player_list::PlayerListWidget,
+    clock::ClockWidget,
+    examine::ExamineWidget,
+    stats::StatsWidget,
+    metrics::MetricsWidget,
+    map_item::MapItemWidget,
+    enchant::EnchantWidget,
+    palette::Palette,
+    glyphs::{ascii_glyph, unicode_locale}
 };
 
-use crate::game::GlobalContext;
-use crate::util::{in_square, world_pos};
+use crate::game::{GlobalContext, Effect, Events, MapItem};
+use crate::util::{in_square, in_circle, world_pos, quantize_256};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    TopDown,
+    Side,
+}
+
+pub const DEFAULT_ENTITY_RANGE: i32 = 200;
+// Entities are only dropped this much further out than they're added
+pub const DEFAULT_ENTITY_RANGE_SLACK: i32 = 8;
+pub const DEFAULT_ENTITY_DEPTH: i32 = 7;
+
+// Used until the map widget reports its real size
+const DEFAULT_SLICE_SIZE: (u16, u16) = (300, 100);
+
+/// Colors the terminal can show
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSupport {
+    TrueColor,
+    Xterm256,
+}
+
+impl ColorSupport {
+    /// Terminals announce 24-bit color in COLORTERM, anything else gets the safe 256 colors
+    pub fn detect() -> Self {
+        match std::env::var("COLORTERM") {
+            Ok(value) if value == "truecolor" || value == "24bit" => ColorSupport::TrueColor,
+            _ => ColorSupport::Xterm256
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "truecolor" => Some(ColorSupport::TrueColor),
+            "256" => Some(ColorSupport::Xterm256),
+            _ => None
+        }
+    }
+}
+
+/// Swaps every RGB color in a drawn frame for its nearest xterm-256 one, for terminals
+/// that can't show RGB. Done last so blocks, entities and UI all go through it
+pub fn quantize_buffer(buf: &mut Buffer) {
+    let quantize = |color: &mut Color| {
+        if let Color::Rgb(r, g, b) = *color {
+            *color = quantize_256((r, g, b));
+        }
+    };
+    for cell in buf.content.iter_mut() {
+        quantize(&mut cell.fg);
+        quantize(&mut cell.bg);
+    }
+}
+
+/// Everything the draw loop needs for a frame. The game loop builds the next one
+/// over a tick and publishes it in one swap, so drawing never waits on game state
+#[derive(Clone)]
+pub struct Snapshot {
+    pub map: Option<Arc<MapSlice>>,
+    pub entities: EntityOverlayState,
+    pub hp_bar: BarWidgetState,
+    pub food_bar: BarWidgetState,
+    pub xp_bar: BarWidgetState,
+    pub prompt: Option<String>,
+    pub player_name: String,
+    pub status: StatusWidgetState,
+    pub kicks: Vec<(String, String)>,
+    // Examined block, shown until the next key in look mode
+    pub examine: Option<Vec<String>>,
+    pub effects: Vec<Effect>,
+    pub events: Vec<String>,
+    // Shown only while the tab list is toggled on
+    pub online: Option<Vec<(String, u16)>>,
+    // Active player's stats by name with their formatted value, while toggled on
+    pub stats: Option<Vec<(String, String)>>,
+    // Debug counters by label, while toggled on
+    pub metrics: Option<Vec<(String, String)>>,
+    // World age and time of day
+    pub clock: (i64, i64),
+    // Second map pane, only while split
+    pub split: Option<SplitPane>,
+    // Filled map opened with /map and its id
+    pub map_item: Option<(i16, Arc<MapItem>)>,
+    // Option costs of the open enchantment table and the active player's level
+    pub enchanting: Option<([i16; 3], i16)>,
+}
+
+/// Which player the split pane follows, worked out by the game loop
+pub struct SplitView {
+    pub title: String,
+    // None while that player is in another dimension than the one loaded
+    pub camera: Option<(i32, i32, i32)>,
+}
+
+/// Another player's surroundings, drawn next to the main map
+#[derive(Clone)]
+pub struct SplitPane {
+    pub title: String,
+    // None while that player is in another dimension than the one loaded
+    pub map: Option<Arc<MapSlice>>,
+}
+
+/// Which entities the overlay shows around the camera. Two radii keep entities on the edge
+/// from being added and removed on every camera move
+#[derive(Clone, Copy, Debug)]
+pub struct EntityRange {
+    pub add_radius: i32,
+    pub remove_radius: i32,
+    pub circle: bool,
+}
+
+impl EntityRange {
+    fn within(&self, point: (i32, i32, i32), camera: (i32, i32, i32), radius: i32, depth: i32) -> bool {
+        match self.circle {
+            true => in_circle(point, camera, radius, depth),
+            false => in_square(point, camera, radius, depth)
+        }
+    }
+
+    /// Close enough to start showing
+    pub fn adds(&self, point: (i32, i32, i32), camera: (i32, i32, i32), depth: i32) -> bool {
+        self.within(point, camera, self.add_radius, depth)
+    }
 
-const RENDER_RANGE: i32 = 200;
-const RENDER_DEPTH: i32 = 7;
+    /// Close enough to keep showing once shown
+    pub fn keeps(&self, point: (i32, i32, i32), camera: (i32, i32, i32), depth: i32) -> bool {
+        self.within(point, camera, self.remove_radius, depth)
+    }
+}
 
 pub struct UiState {
     render_stop: AtomicBool,
-    pub world_state: RwLock<WorldWidgetState>,
-    pub entity_state: RwLock<EntityOverlayState>,
-    pub hp_bar: RwLock<BarWidgetState>,
-    pub food_bar: RwLock<BarWidgetState>,
+    // Last published snapshot, the lock is only held to swap or clone the Arc
+    snapshot: Mutex<Arc<Snapshot>>,
+    // Only touched by the game loop
+    next: Mutex<Snapshot>,
+    // Size the main and split map panes want their slices in, reported by the draw loop
+    slice_size: Mutex<[(u16, u16); 2]>,
+    resized: AtomicBool,
+    // Camera the split pane was last rendered at
+    split_camera: Mutex<Option<(i32, i32, i32)>>,
+    entity_range: Mutex<EntityRange>,
+    palette: Mutex<Arc<Palette>>,
 }
 
 impl UiState {
     pub fn init() -> Arc<Self> {
-        let world_update = Arc::new(AtomicBool::new(true));
-        let world_state = RwLock::new(WorldWidgetState::init(Arc::clone(&world_update)));
-        let entity_state = RwLock::new(EntityOverlayState::init());
-
-        let hp_bar= RwLock::new(BarWidgetState {
-            color: Color::Rgb(255, 100, 100),
+        let palette = Palette::default();
+        let hp_bar = BarWidgetState {
+            color: palette.color("hp"),
             direction: BarWidgetDirection::Horizontal,
             mode: BarWidgetMode::ValueWithMaxValue,
             value: 0,
             max_value: 20 
-        });
+        };
 
-        let food_bar = RwLock::new(BarWidgetState {
-            color: Color::Rgb(52, 52, 209),
+        let food_bar = BarWidgetState {
+            color: palette.color("food"),
             direction: BarWidgetDirection::Horizontal,
             mode: BarWidgetMode::ValueWithMaxValue,
             value: 0,
             max_value: 20 
-        });
+        };
+
+        let xp_bar = BarWidgetState {
+            color: palette.color("xp"),
+            direction: BarWidgetDirection::Horizontal,
+            mode: BarWidgetMode::Fraction(0.),
+            value: 0,
+            max_value: 1
+        };
+
+        let snapshot = Snapshot {
+            map: None,
+            entities: EntityOverlayState::init(),
+            hp_bar,
+            food_bar,
+            xp_bar,
+            prompt: None,
+            player_name: String::new(),
+            status: StatusWidgetState::init(),
+            kicks: vec![],
+            examine: None,
+            effects: vec![],
+            events: vec![],
+            online: None,
+            stats: None,
+            metrics: None,
+            clock: (0, 0),
+            split: None,
+            map_item: None,
+            enchanting: None,
+        };
 
         Arc::new(Self {
             render_stop: AtomicBool::new(false),
-            entity_state,
-            world_state,
-            hp_bar,
-            food_bar
+            snapshot: Mutex::new(Arc::new(snapshot.clone())),
+            next: Mutex::new(snapshot),
+            slice_size: Mutex::new([(0, 0); 2]),
+            resized: AtomicBool::new(false),
+            split_camera: Mutex::new(None),
+            entity_range: Mutex::new(EntityRange {
+                add_radius: DEFAULT_ENTITY_RANGE,
+                remove_radius: DEFAULT_ENTITY_RANGE + DEFAULT_ENTITY_RANGE_SLACK,
+                circle: false,
+            }),
+            palette: Mutex::new(Arc::new(palette)),
         })
     }
 
@@ -66,34 +263,179 @@ impl UiState {
         self.render_stop.load(Ordering::Relaxed)
     }
 
-    pub async fn set_food(&self, value: u16) {
-        self.food_bar.write().await.value = value;
+    /// Latest published state for the draw loop
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        Arc::clone(&self.snapshot.lock().unwrap())
+    }
+
+    /// Makes everything set since the last publish visible to the draw loop
+    pub fn publish(&self) {
+        let snapshot = Arc::new(self.next.lock().unwrap().clone());
+        *self.snapshot.lock().unwrap() = snapshot;
+    }
+
+    pub fn set_food(&self, value: u16) {
+        self.next.lock().unwrap().food_bar.value = value;
+    }
+
+    pub fn set_hp(&self, value: u16) {
+        self.next.lock().unwrap().hp_bar.value = value;
+    }
+
+    pub fn set_xp(&self, fraction: f32, level: u16) {
+        let mut next = self.next.lock().unwrap();
+        let xp_bar = &mut next.xp_bar;
+        xp_bar.mode = BarWidgetMode::Fraction(fraction);
+        xp_bar.value = level;
     }
 
-    pub async fn set_hp(&self, value: u16) {
-        self.hp_bar.write().await.value = value;
+    pub fn set_player_name(&self, name: String) {
+        self.next.lock().unwrap().player_name = name;
     }
 
-    pub async fn update_entities(&self, ctx: &GlobalContext) {
+    pub fn set_status(&self, status: StatusWidgetState) {
+        self.next.lock().unwrap().status = status;
+    }
+
+    /// Colors the UI is drawn in, the bars are recolored right away
+    pub fn set_palette(&self, palette: Arc<Palette>) {
+        {
+            let mut next = self.next.lock().unwrap();
+            next.hp_bar.color = palette.color("hp");
+            next.food_bar.color = palette.color("food");
+            next.xp_bar.color = palette.color("xp");
+        }
+        *self.palette.lock().unwrap() = palette;
+    }
+
+    pub fn palette(&self) -> Arc<Palette> {
+        Arc::clone(&self.palette.lock().unwrap())
+    }
+
+    /// Poison and wither tint the health bar
+    pub fn set_effects(&self, effects: Vec<Effect>) {
+        let color = if effects.iter().any(|e| e.id == effects::WITHER) {
+            "hp_withered"
+        } else if effects.iter().any(|e| e.id == effects::POISON) {
+            "hp_poisoned"
+        } else {
+            "hp"
+        };
+        let color = self.palette().color(color);
+        let mut next = self.next.lock().unwrap();
+        next.hp_bar.color = color;
+        next.effects = effects;
+    }
+
+    /// Newest event first, like the log
+    pub fn set_events(&self, events: &Events) {
+        self.next.lock().unwrap().events = events.iter()
+            .rev()
+            .map(|e| format!("{} at {} {} {}", e.text, e.pos.0, e.pos.1, e.pos.2))
+            .collect();
+    }
+
+    pub fn set_kicks(&self, kicks: Vec<(String, String)>) {
+        self.next.lock().unwrap().kicks = kicks;
+    }
+
+    pub fn set_examine(&self, examine: Option<Vec<String>>) {
+        self.next.lock().unwrap().examine = examine;
+    }
+
+    pub fn set_online(&self, online: Option<Vec<(String, u16)>>) {
+        self.next.lock().unwrap().online = online;
+    }
+
+    pub fn set_stats(&self, stats: Option<Vec<(String, String)>>) {
+        self.next.lock().unwrap().stats = stats;
+    }
+
+    pub fn set_map_item(&self, map_item: Option<(i16, Arc<MapItem>)>) {
+        self.next.lock().unwrap().map_item = map_item;
+    }
+
+    pub fn set_enchanting(&self, enchanting: Option<([i16; 3], i16)>) {
+        self.next.lock().unwrap().enchanting = enchanting;
+    }
+
+    pub fn set_metrics(&self, metrics: Option<Vec<(String, String)>>) {
+        self.next.lock().unwrap().metrics = metrics;
+    }
+
+    pub fn set_clock(&self, age: i64, time: i64) {
+        self.next.lock().unwrap().clock = (age, time);
+    }
+
+    /// The remove radius is raised to the add radius if it's smaller
+    pub fn set_entity_range(&self, range: EntityRange) {
+        *self.entity_range.lock().unwrap() = EntityRange {
+            remove_radius: range.remove_radius.max(range.add_radius),
+            ..range
+        };
+    }
+
+    pub fn set_prompt(&self, prompt: Option<String>) {
+        self.next.lock().unwrap().prompt = prompt;
+    }
+
+    pub fn update_entities(&self, ctx: &GlobalContext) {
+        let range = *self.entity_range.lock().unwrap();
+        let mut next = self.next.lock().unwrap();
+        let entity_state = &mut next.entities;
+
         // Camera moved
         if ctx.camera_update {
-            self.entities_camera_moved(ctx).await;
+            entities_camera_moved(entity_state, ctx, &range);
         }
 
         // Some entities moved
         if ctx.entities.update {
-            self.entities_moved(ctx).await;
+            entities_moved(entity_state, ctx, &range);
         }
 
+        for eid in &ctx.entities.deleted {
+            entity_state.forget(*eid);
+        }
+        for pos in &ctx.entities.collected {
+            entity_state.flashes.push((*pos, ctx.tick + PICKUP_FLASH_TICKS, PICKUP_FLASH_COLOR));
+        }
+        for pos in &ctx.world.explosions {
+            entity_state.flashes.push((*pos, ctx.tick + EXPLOSION_FLASH_TICKS, EXPLOSION_FLASH_COLOR));
+        }
+        for pos in &ctx.events.particles {
+            entity_state.flashes.push((*pos, ctx.tick + PARTICLE_FLASH_TICKS, PARTICLE_FLASH_COLOR));
+        }
+        for pos in &ctx.events.lightning {
+            entity_state.lightning.push((*pos, ctx.tick + LIGHTNING_TICKS));
+        }
+        for entity in ctx.entities.entities.iter().filter(|e| ctx.entities.equipped.contains(&e.id)) {
+            let render = entity_state.cells.iter_mut()
+                .flat_map(|c| c.entities.iter_mut())
+                .find(|r| r.id == entity.id);
+            if let Some(render) = render {
+                render.armored = entity.is_armored();
+            }
+        }
+        for (eid, anim) in &ctx.entities.animations {
+            entity_state.animations.insert(*eid, (*anim, ctx.tick + ANIMATION_TICKS));
+        }
+        entity_state.animations.retain(|_, (_, until)| *until > ctx.tick);
+        if ctx.world.breaking_update {
+            entity_state.breaking = ctx.world.breaking.iter()
+                .map(|(pos, (stage, _))| (*pos, *stage))
+                .collect();
+        }
+        entity_state.flashes.retain(|(_, until, _)| *until > ctx.tick);
+        entity_state.lightning.retain(|(_, until)| *until > ctx.tick);
+
         if ctx.tick % 60 == 0 {
-            let mut entity_state = self.entity_state.write().await;
             for cell in &mut entity_state.cells {
                 if cell.entities.len() > 1 {
                     cell.state = EntityCellState::Rolling;
                 }
             }
         } else if (ctx.tick + 50) % 60 == 0 {
-            let mut entity_state = self.entity_state.write().await;
             for cell in &mut entity_state.cells {
                 cell.state = EntityCellState::Entity;
                 cell.entity_index = (cell.entity_index + 1) % cell.entities.len()
@@ -101,98 +443,162 @@ impl UiState {
         }
     }
 
-    async fn entities_moved(&self, ctx: &GlobalContext) {
-        let mut entity_state = self.entity_state.write().await;
-        let cam_depth = entity_state.camera.1;
-        for entity in &ctx.entities.entities { //TODO keep R/O references in a separate list?
-            if !ctx.entities.moved.contains(&entity.id) {
-                continue;
-            }
+    pub fn world_resized(&self) -> bool {
+        self.resized.load(Ordering::Relaxed)
+    }
 
-            // Entity spawned in 
-            if entity.new {
-                let pos = entity.world_pos();
-                if in_square(pos, ctx.camera, RENDER_RANGE, RENDER_DEPTH) &&
-                    !entity_state.visible.contains(&entity.id) 
-                {
-                    entity_state.add(entity, pos, cam_depth);
-                }
-                continue;
-            }
+    /// Called by the draw loop when a map pane changed size, 0 is the main one and 1 the split
+    pub fn set_slice_size(&self, pane: usize, size: (u16, u16)) {
+        self.slice_size.lock().unwrap()[pane] = size;
+        self.resized.store(true, Ordering::Relaxed);
+    }
 
-            let from = world_pos(entity.last_position);
-            let to = entity.world_pos();
+    fn slice_size(&self, pane: usize) -> (u16, u16) {
+        match self.slice_size.lock().unwrap()[pane] {
+            (0, _) | (_, 0) => DEFAULT_SLICE_SIZE,
+            size => size
+        }
+    }
 
-            // Entity moved for more than one block
-            if from != to {
-                if  in_square(from, ctx.camera, RENDER_RANGE, RENDER_DEPTH) &&
-                    entity_state.visible.contains(&entity.id) 
-                {
-                    entity_state.remove(entity.id, from); 
-                }
-                if in_square(to, ctx.camera, RENDER_RANGE, RENDER_DEPTH) &&
-                    !entity_state.visible.contains(&entity.id) 
-                {
-                    entity_state.add(entity, to, cam_depth);
-                }
-            }
+    /// Renders the split pane at `camera`, only when it moved or the world changed.
+    /// Has to run before update_world, which consumes the resize
+    pub async fn update_split(&self, ctx: &GlobalContext, view: Option<SplitView>) {
+        let Some(SplitView { title, camera }) = view else {
+            self.next.lock().unwrap().split = None;
+            *self.split_camera.lock().unwrap() = None;
+            return;
+        };
+        let moved = *self.split_camera.lock().unwrap() != camera;
+        let was_split = self.next.lock().unwrap().split.is_some();
+        let map = match camera {
+            Some(camera) if moved || !was_split || ctx.world.update || self.world_resized() => {
+                let (width, height) = self.slice_size(1);
+                let (slice, center) = ctx.world.get_slice_render(width, height, ctx.zoom, ctx.render_mode, camera, ctx).await;
+                Some(Arc::new(MapSlice {
+                    cells: slice,
+                    size: (width, height),
+                    camera: center
+                }))
+            },
+            Some(_) => self.next.lock().unwrap().split.as_ref().and_then(|split| split.map.clone()),
+            None => None
+        };
+        *self.split_camera.lock().unwrap() = camera;
+        self.next.lock().unwrap().split = Some(SplitPane { title, map });
+    }
 
-            // Height changed
-            if entity_state.visible.contains(&entity.id) && from.1 != to.1 {
-                if let Some(cell) = entity_state.cells.iter_mut().find(|c| c.x == to.0 && c.z == to.2) {
-                    if let Some(entity) = cell.entities.iter_mut().find(|e| e.id == entity.id){
-                        entity.set_depth(to.1, cam_depth);
-                    }
-                }
-            }
-        }
+    pub async fn update_world(&self, ctx: &GlobalContext) {
+        let (width, height) = self.slice_size(0);
+        self.resized.store(false, Ordering::Relaxed);
+        let (slice, camera) = ctx.world.get_slice_render(width, height, ctx.zoom, ctx.render_mode, ctx.camera, &ctx).await;
+        let mut next = self.next.lock().unwrap();
+        next.entities.zoom = ctx.zoom;
+        next.entities.render_mode = ctx.render_mode;
+        next.entities.ascii = ctx.ascii;
+        next.map = Some(Arc::new(MapSlice {
+            cells: slice,
+            size: (width, height),
+            camera
+        }));
     }
 
-    async fn entities_camera_moved(&self, ctx: &GlobalContext) {
-        let mut entity_state = self.entity_state.write().await;
-        entity_state.camera = ctx.camera;
-        let mut to_remove = vec![];
-        // Remove abscent entities
-        let EntityOverlayState { cells, visible, ..} = &mut *entity_state;
-        for (i, cell) in cells.iter().enumerate() {
-            if !in_square((cell.x, 0, cell.z), ctx.camera, RENDER_RANGE, RENDER_DEPTH) {
-                for entity in &cell.entities {
-                    visible.remove(&entity.id);
-                }
-                to_remove.push(i);
+    pub fn stop(&self) {
+        self.render_stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn entities_moved(entity_state: &mut EntityOverlayState, ctx: &GlobalContext, range: &EntityRange) {
+    let cam_depth = entity_state.camera.1;
+    for entity in &ctx.entities.entities { //TODO keep R/O references in a separate list?
+        if !ctx.entities.moved.contains(&entity.id) {
+            continue;
+        }
+
+        // Entity spawned in 
+        if entity.new {
+            let pos = entity.world_pos();
+            if range.adds(pos, ctx.camera, ctx.entity_depth) &&
+                !entity_state.visible.contains(&entity.id) 
+            {
+                entity_state.add(entity, pos, cam_depth);
             }
+            continue;
         }
 
-        entity_state.remove_cells(&mut to_remove);
+        let from = world_pos(entity.last_position);
+        let to = entity.world_pos();
 
-        for entity in &ctx.entities.entities {
-            if entity_state.visible.contains(&entity.id) {
-                continue;
+        // Entity moved for more than one block, shown ones stay until they leave the outer radius
+        if from != to {
+            let shown = entity_state.visible.contains(&entity.id);
+            if shown {
+                entity_state.remove(entity.id, from); 
             }
-            let pos = world_pos(entity.last_position);
-            if in_square(entity.world_pos(), ctx.camera, RENDER_RANGE, RENDER_DEPTH) {
-                entity_state.add(entity, pos, ctx.camera.1);
+            let show = match shown {
+                true => range.keeps(to, ctx.camera, ctx.entity_depth),
+                false => range.adds(to, ctx.camera, ctx.entity_depth)
+            };
+            if show {
+                entity_state.add(entity, to, cam_depth);
             }
         }
 
-        let cam_depth = entity_state.camera.1;
-        for cell in entity_state.cells.iter_mut() {
-            for entity in cell.entities.iter_mut() {
-                entity.set_depth(entity.y, cam_depth);
+        // Height changed
+        if entity_state.visible.contains(&entity.id) && from.1 != to.1 {
+            if let Some(cell) = entity_state.cells.iter_mut().find(|c| c.x == to.0 && c.z == to.2) {
+                if let Some(entity) = cell.entities.iter_mut().find(|e| e.id == entity.id){
+                    entity.set_depth(to.1, cam_depth);
+                }
             }
         }
     }
+}
 
-    pub async fn update_world(&self, ctx: &GlobalContext) {
-        let (slice, camera) = ctx.world.get_slice_render(300, 100, &ctx).await;
-        let mut world_state = self.world_state.write().await;
-        world_state.map_size = (300, 100);
-        world_state.map = Some(slice);
-        world_state.camera = camera;
-        world_state.update();
+fn entities_camera_moved(entity_state: &mut EntityOverlayState, ctx: &GlobalContext, range: &EntityRange) {
+    entity_state.camera = ctx.camera;
+    // Remove entities that left the outer radius, each at its own height
+    entity_state.retain_entities(|pos| range.keeps(pos, ctx.camera, ctx.entity_depth));
+
+    for entity in &ctx.entities.entities {
+        if entity_state.visible.contains(&entity.id) {
+            continue;
+        }
+        let pos = world_pos(entity.last_position);
+        if range.adds(entity.world_pos(), ctx.camera, ctx.entity_depth) {
+            entity_state.add(entity, pos, ctx.camera.1);
+        }
     }
 
-    pub fn stop(&self) {
-        self.render_stop.store(true, Ordering::Relaxed);
+    let cam_depth = entity_state.camera.1;
+    for cell in entity_state.cells.iter_mut() {
+        for entity in cell.entities.iter_mut() {
+            entity.set_depth(entity.y, cam_depth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::Packet;
+
+    #[tokio::test]
+    async fn collected_entities_leave_the_overlay() {
+        let mut ctx = GlobalContext::init(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources")).unwrap();
+        ctx.camera = (1, 64, -1);
+        let ui = UiState::init();
+        ctx.entities.handle_packet(Packet::SpawnExperienceOrb { eid: 7, x: 32, y: 64 * 32, z: -32, count: 1 }, 0).await;
+        ui.update_entities(&ctx);
+        ui.publish();
+        assert!(ui.snapshot().entities.visible.contains(&7));
+
+        ctx.entities.tick();
+        ctx.entities.handle_packet(Packet::CollectItem { collected: 7, collector: 1 }, 0).await;
+        ui.update_entities(&ctx);
+        ui.publish();
+        let entities = &ui.snapshot().entities;
+        assert!(!entities.visible.contains(&7));
+        assert!(entities.cells.iter().all(|c| c.entities.iter().all(|e| e.id != 7)));
+        assert_eq!(entities.flashes.len(), 1);
     }
 }
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use ratatui::style::Color;
+
+type Rgb = (u8, u8, u8);
+
+// Logical colors that can be overridden, with the color the client draws them in by default
+const NAMED: [(&str, Rgb); 16] = [
+    ("border", (194, 255, 102)),
+    ("hp", (255, 100, 100)),
+    ("hp_poisoned", (148, 132, 23)),
+    ("hp_withered", (80, 60, 60)),
+    ("food", (52, 52, 209)),
+    ("xp", (128, 255, 32)),
+    ("ping_good", (100, 220, 100)),
+    ("ping_fair", (220, 220, 80)),
+    ("ping_slow", (230, 150, 60)),
+    ("ping_bad", (230, 70, 70)),
+    ("grass", (10, 215, 10)),
+    ("leaves", (12, 223, 12)),
+    ("cactus", (50, 225, 50)),
+    ("water", (87, 151, 255)),
+    ("deep_water", (61, 64, 255)),
+    ("redstone_torch", (230, 10, 10)),
+];
+
+// Presets keep red and green cues apart, colors mostly from the Okabe-Ito set
+const DEUTERANOPIA: [(&str, Rgb); 11] = [
+    ("border", (86, 180, 233)),
+    ("hp", (213, 94, 0)),
+    ("hp_poisoned", (204, 121, 167)),
+    ("xp", (240, 228, 66)),
+    ("ping_good", (86, 180, 233)),
+    ("ping_fair", (240, 228, 66)),
+    ("ping_slow", (230, 159, 0)),
+    ("ping_bad", (213, 94, 0)),
+    ("grass", (0, 158, 115)),
+    ("leaves", (0, 120, 90)),
+    ("redstone_torch", (213, 94, 0)),
+];
+// Reds look dark without red cones, so they're moved toward orange and yellow
+const PROTANOPIA: [(&str, Rgb); 11] = [
+    ("border", (86, 180, 233)),
+    ("hp", (230, 159, 0)),
+    ("hp_poisoned", (86, 180, 233)),
+    ("xp", (240, 228, 66)),
+    ("ping_good", (86, 180, 233)),
+    ("ping_fair", (240, 228, 66)),
+    ("ping_slow", (230, 159, 0)),
+    ("ping_bad", (204, 121, 167)),
+    ("grass", (0, 158, 115)),
+    ("leaves", (0, 120, 90)),
+    ("redstone_torch", (240, 228, 66)),
+];
+
+/// Colors the client draws by name, the defaults from `NAMED` unless `palette.json` overrides some.
+/// The file picks an optional preset and overrides single colors by name, e.g.
+/// `{"preset": "deuteranopia", "colors": {"border": [255, 255, 255]}}`
+#[derive(Default)]
+pub struct Palette {
+    overrides: HashMap<&'static str, Rgb>,
+}
+
+impl Palette {
+    /// A missing file means the default colors
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut palette = Self::default();
+        if !path.is_file() {
+            return Ok(palette);
+        }
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+        let data = json::parse(&data[..])
+            .map_err(|e| format!("Bad {}: {}", path.display(), e))?;
+        match data["preset"].as_str() {
+            Some("deuteranopia") => palette.set_all(&DEUTERANOPIA),
+            Some("protanopia") => palette.set_all(&PROTANOPIA),
+            Some(preset) => return Err(format!("Unknown palette preset {}", preset).into()),
+            None => {}
+        }
+        for (name, value) in data["colors"].entries() {
+            let rgb: Vec<u8> = value.members().filter_map(|v| v.as_u8()).collect();
+            let &[r, g, b] = &rgb[..] else {
+                return Err(format!("Palette color {} should be [r, g, b]", name).into());
+            };
+            palette.set(name, (r, g, b))?;
+        }
+        Ok(palette)
+    }
+
+    fn set_all(&mut self, colors: &[(&str, Rgb)]) {
+        for (name, rgb) in colors {
+            // Presets only name colors from NAMED
+            let _ = self.set(name, *rgb);
+        }
+    }
+
+    pub(crate) fn set(&mut self, name: &str, rgb: Rgb) -> Result<(), Box<dyn Error>> {
+        let (name, _) = NAMED.iter()
+            .find(|(named, _)| *named == name)
+            .ok_or_else(|| format!("Unknown palette color {}", name))?;
+        self.overrides.insert(name, rgb);
+        Ok(())
+    }
+
+    /// Panics on a name missing from `NAMED`, callers only pass fixed names
+    pub fn rgb(&self, name: &str) -> Rgb {
+        if let Some(rgb) = self.overrides.get(name) {
+            return *rgb;
+        }
+        NAMED.iter()
+            .find(|(named, _)| *named == name)
+            .map(|(_, rgb)| *rgb)
+            .unwrap_or_else(|| panic!("Unknown palette color {}", name))
+    }
+
+    pub fn color(&self, name: &str) -> Color {
+        let (r, g, b) = self.rgb(name);
+        Color::Rgb(r, g, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_only_touch_their_name() {
+        let mut palette = Palette::default();
+        assert_eq!(palette.rgb("grass"), (10, 215, 10));
+        palette.set("grass", (1, 2, 3)).unwrap();
+        assert_eq!(palette.rgb("grass"), (1, 2, 3));
+        assert_eq!(palette.rgb("leaves"), (12, 223, 12));
+        assert!(palette.set("sky", (1, 2, 3)).is_err());
+    }
+
+    #[test]
+    fn every_preset_color_is_named() {
+        for (name, _) in DEUTERANOPIA.iter().chain(PROTANOPIA.iter()) {
+            assert!(NAMED.iter().any(|(named, _)| named == name), "{}", name);
+        }
+    }
+}
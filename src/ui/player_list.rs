@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use ratatui::widgets::{Block, BorderType, Clear, Widget, WidgetRef};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+
+use super::Palette;
+
+fn ping_color(ping: u16) -> &'static str {
+    match ping {
+        0..150 => "ping_good",
+        150..300 => "ping_fair",
+        300..600 => "ping_slow",
+        _ => "ping_bad",
+    }
+}
+
+/// Online players and their pings, like the vanilla tab list
+pub struct PlayerListWidget {
+    players: Option<Vec<(String, u16)>>,
+    palette: Arc<Palette>
+}
+
+impl PlayerListWidget {
+    pub fn construct(players: Option<Vec<(String, u16)>>, palette: Arc<Palette>) -> PlayerListWidget {
+        Self {
+            players,
+            palette
+        }
+    }
+}
+
+impl WidgetRef for &PlayerListWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let Some(players) = self.players.as_ref() else {
+            return;
+        };
+        let color = self.palette.color("border");
+        let longest = players.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0) as u16;
+        // name, a gap and up to 5 digits of ping plus "ms"
+        let width = (longest + 12).max(24).min(area.width);
+        let height = (players.len() as u16 + 2).min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + 1.min(area.height - height),
+            width,
+            height
+        };
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(color))
+            .title(format!(" Online: {} ", players.len()));
+        let inner = block.inner(popup);
+        Clear.render(popup, buf);
+        block.render_ref(popup, buf);
+        for (i, (name, ping)) in players.iter().take(inner.height as usize).enumerate() {
+            let y = inner.y + i as u16;
+            buf.set_stringn(
+                inner.x + 1,
+                y,
+                name,
+                inner.width.saturating_sub(1) as usize,
+                Style::default().fg(color));
+            let label = format!("{}ms", ping);
+            let label_x = (inner.x + inner.width).saturating_sub(label.len() as u16 + 1).max(inner.x);
+            buf.set_string(label_x, y, &label, Style::default().fg(self.palette.color(ping_color(*ping))));
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use ratatui::widgets::{Block, BorderType, Clear, Widget, WidgetRef};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Style, Color};
+
+use super::Palette;
+
+const VALUE_COLOR: Color = Color::Rgb(230, 230, 230);
+
+/// Statistics of the active player, name on the left and value on the right
+pub struct StatsWidget {
+    stats: Option<Vec<(String, String)>>,
+    palette: Arc<Palette>
+}
+
+impl StatsWidget {
+    pub fn construct(stats: Option<Vec<(String, String)>>, palette: Arc<Palette>) -> StatsWidget {
+        Self {
+            stats,
+            palette
+        }
+    }
+}
+
+impl WidgetRef for &StatsWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let Some(stats) = self.stats.as_ref() else {
+            return;
+        };
+        let color = self.palette.color("border");
+        let longest = stats.iter()
+            .map(|(name, value)| name.chars().count() + value.chars().count())
+            .max()
+            .unwrap_or(0) as u16;
+        // name, a gap of at least 3 and the value
+        let width = (longest + 7).max(24).min(area.width);
+        let height = (stats.len().max(1) as u16 + 2).min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + 1.min(area.height - height),
+            width,
+            height
+        };
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(color))
+            .title(" Stats ");
+        let inner = block.inner(popup);
+        Clear.render(popup, buf);
+        block.render_ref(popup, buf);
+        if stats.is_empty() {
+            buf.set_stringn(inner.x + 1, inner.y, "Nothing yet", inner.width.saturating_sub(1) as usize,
+                Style::default().fg(color));
+            return;
+        }
+        for (i, (name, value)) in stats.iter().take(inner.height as usize).enumerate() {
+            let y = inner.y + i as u16;
+            buf.set_stringn(
+                inner.x + 1,
+                y,
+                name,
+                inner.width.saturating_sub(1) as usize,
+                Style::default().fg(color));
+            let value_x = (inner.x + inner.width).saturating_sub(value.chars().count() as u16 + 1).max(inner.x);
+            buf.set_string(value_x, y, value, Style::default().fg(VALUE_COLOR));
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use ratatui::widgets::WidgetRef;
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+
+use super::Palette;
+
+pub struct StatusWidget {
+    state: StatusWidgetState,
+    palette: Arc<Palette>
+}
+
+impl StatusWidget {
+    pub fn construct(state: StatusWidgetState, palette: Arc<Palette>) -> StatusWidget {
+        Self {
+            state,
+            palette
+        }
+    }
+}
+
+impl WidgetRef for &StatusWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        let state = &self.state;
+        let (x, y, z) = state.player_pos;
+        let mut text = format!("{} | {} {} {} facing {}", state.mode, x, y, z, state.facing);
+        if let Some((x, y, z)) = state.camera {
+            text.push_str(&format!(" | cam {} {} {}", x, y, z));
+        }
+        if state.sneaking {
+            text.push_str(" | sneaking");
+        }
+        if state.sprinting {
+            text.push_str(" | sprinting");
+        }
+        if state.sleeping {
+            text.push_str(" | sleeping");
+        }
+        text.push_str(if state.raining { " | rain" } else { " | clear" });
+        if let Some(brand) = state.brand.as_ref() {
+            text.push_str(&format!(" | {}", brand));
+        }
+        buf.set_stringn(
+            area.x,
+            area.y,
+            text,
+            area.width as usize,
+            Style::default().fg(self.palette.color("border")));
+    }
+}
+
+#[derive(Clone)]
+pub struct StatusWidgetState {
+    pub mode: String,
+    pub player_pos: (i32, i32, i32),
+    pub facing: &'static str,
+    pub camera: Option<(i32, i32, i32)>,
+    pub raining: bool,
+    pub brand: Option<String>,
+    pub sneaking: bool,
+    pub sprinting: bool,
+    pub sleeping: bool,
+}
+
+impl StatusWidgetState {
+    pub fn init() -> Self {
+        Self {
+            mode: String::new(),
+            player_pos: (0, 0, 0),
+            facing: "",
+            camera: None,
+            raining: false,
+            brand: None,
+            sneaking: false,
+            sprinting: false,
+            sleeping: false,
+        }
+    }
+}
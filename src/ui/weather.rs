@@ -0,0 +1,58 @@
+use ratatui::style::Color;
+use ratatui::widgets::WidgetRef;
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Rect, Position};
+
+const RAIN_COLOR: Color = Color::Rgb(90, 110, 200);
+// Roughly one cell in this many shows a drop
+const RAIN_DENSITY: i64 = 37;
+// Draw frames per cell a drop falls
+const RAIN_SPEED: usize = 3;
+
+/// Tints the map blue and scatters falling drops over empty cells while it rains
+pub struct WeatherWidget {
+    raining: bool,
+    tick: usize
+}
+
+impl WeatherWidget {
+    pub fn new(raining: bool, tick: usize) -> Self {
+        Self {
+            raining,
+            tick
+        }
+    }
+}
+
+fn tint(color: Color) -> Color {
+    match color {
+        Color::Rgb(r, g, b) => Color::Rgb(
+            (r as u16 * 3 / 4) as u8,
+            (g as u16 * 3 / 4) as u8,
+            (b as u16 * 3 / 4 + 40).min(255) as u8),
+        Color::Reset => Color::Rgb(0, 0, 30),
+        other => other
+    }
+}
+
+impl WidgetRef for &WeatherWidget {
+    fn render_ref(&self, area: Rect, buf: &mut Buffer) {
+        if !self.raining {
+            return;
+        }
+        let fall = (self.tick / RAIN_SPEED) as i64;
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let Some(cell) = buf.cell_mut(Position { x, y }) else {
+                    continue;
+                };
+                cell.set_bg(tint(cell.bg));
+                let drop = (x as i64 * 31 + (y as i64 - fall) * 17).rem_euclid(RAIN_DENSITY) == 0;
+                if drop && cell.symbol() == " " {
+                    cell.set_char('\'');
+                    cell.set_fg(RAIN_COLOR);
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
 
 use ratatui::widgets::StatefulWidgetRef;
 use ratatui::buffer::{Buffer, Cell};
@@ -17,56 +16,142 @@ impl WorldWidget {
 impl StatefulWidgetRef for &WorldWidget {
     type State = WorldWidgetState;
     fn render_ref(&self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
-        if state.map.is_none() || state.map.as_ref().unwrap().len() == 0 {
+        if area != state.last_area {
+            // The slice was cut for the old size, ask for a new one
+            state.slice_size = (area.width, area.height);
+            state.resized = true;
+        }
+        if state.map.is_none() || state.map.as_ref().unwrap().cells.len() == 0 {
             return;
         }
-        if area == state.last_area && !state.update.load(Ordering::Relaxed) {
+        if area == state.last_area && !state.update {
             buf.merge(&state.last_buffer);
             return;
         }
-        let map = state.map.as_ref().unwrap();
+        let slice = state.map.as_ref().unwrap();
+        let map = &slice.cells;
+        let map_size = slice.size;
         let center = (area.width/2, area.height/2);
-        let x0 = state.camera.0 as i16 - center.0 as i16;
-        let y0 = state.camera.1 as i16 - center.1 as i16;
+        let x0 = slice.camera.0 as i16 - center.0 as i16;
+        let y0 = slice.camera.1 as i16 - center.1 as i16;
         for y in 0..area.height as i16 {
             for x in 0..area.width as i16 {
-                if let Some(cell) = buf.cell_mut(Position {x: x as u16, y: y as u16}) {
-                    if (x + x0) as u16 >= state.map_size.0 || (y + y0) as u16 >= state.map_size.1 {
+                if let Some(cell) = buf.cell_mut(Position {x: area.x + x as u16, y: area.y + y as u16}) {
+                    if (x + x0) as u16 >= map_size.0 || (y + y0) as u16 >= map_size.1 {
                         cell.set_char(' ');
                         continue;
                     }
-                    *cell = map[(x0+x + (y0+y)*state.map_size.0 as i16) as usize].clone();
+                    *cell = map[(x0+x + (y0+y)*map_size.0 as i16) as usize].clone();
                 }
             }
         }
-        state.update.store(false, Ordering::Relaxed);
-        state.last_buffer = buf.clone();
+        state.update = false;
+        let mut last_buffer = Buffer::empty(area);
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                last_buffer[(x, y)] = buf[(x, y)].clone();
+            }
+        }
+        state.last_buffer = last_buffer;
         state.last_area = area;
     }
 }
 
-pub struct WorldWidgetState {
-    pub map: Option<Box<[Cell]>>,
-    pub map_size: (u16, u16),
+/// A cut of the world rendered by the game loop, shared with the draw loop as is
+pub struct MapSlice {
+    pub cells: Box<[Cell]>,
+    pub size: (u16, u16),
     pub camera: (u16, u16),
-    pub update: Arc<AtomicBool>,
+}
+
+/// Owned by the draw loop
+pub struct WorldWidgetState {
+    pub map: Option<Arc<MapSlice>>,
+    pub update: bool,
     pub last_buffer: Buffer,
-    pub last_area: Rect
+    pub last_area: Rect,
+    // Size of the area last drawn into, the next slice should match it
+    pub slice_size: (u16, u16),
+    pub resized: bool,
 }
 
 impl WorldWidgetState {
-    pub fn init(update: Arc<AtomicBool>) -> WorldWidgetState {
+    pub fn init() -> WorldWidgetState {
         WorldWidgetState {
             map: None,
-            map_size: (0, 0),
-            camera: (0, 0),
+            update: false,
             last_buffer: Buffer::empty(Rect::ZERO),
             last_area: Rect::ZERO,
-            update
+            slice_size: (0, 0),
+            resized: false,
         }
     }
 
-    pub fn update(&self) {
-        self.update.store(true, Ordering::Relaxed);
+    /// Takes the latest slice, the buffer is only redrawn when it's a new one
+    pub fn set_map(&mut self, map: &Option<Arc<MapSlice>>) {
+        let same = match (&self.map, map) {
+            (Some(old), Some(new)) => Arc::ptr_eq(old, new),
+            (None, None) => true,
+            _ => false
+        };
+        if !same {
+            self.map = map.clone();
+            self.update = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(x: u16, y: u16) -> String {
+        char::from(b'a' + ((x + y * 10) % 26) as u8).to_string()
+    }
+
+    fn slice() -> Arc<MapSlice> {
+        let cells = (0..6).flat_map(|y| (0..10).map(move |x| {
+            let mut cell = Cell::default();
+            cell.set_symbol(&symbol(x, y));
+            cell
+        })).collect();
+        Arc::new(MapSlice { cells, size: (10, 6), camera: (5, 3) })
+    }
+
+    fn render(state: &mut WorldWidgetState, area: Rect) -> Buffer {
+        let mut buf = Buffer::empty(area);
+        (&WorldWidget::new()).render_ref(area, &mut buf, state);
+        buf
+    }
+
+    #[test]
+    fn resize_redraws_at_the_new_size() {
+        let mut state = WorldWidgetState::init();
+        state.set_map(&Some(slice()));
+        let area = Rect::new(0, 0, 10, 6);
+        render(&mut state, area);
+        assert_eq!(state.last_buffer.area, area);
+
+        // Same slice, smaller area: cut around the camera rather than merge the old buffer
+        let smaller = Rect::new(1, 1, 6, 4);
+        let buf = render(&mut state, smaller);
+        assert!(state.resized);
+        assert_eq!(state.slice_size, (6, 4));
+        assert_eq!(state.last_area, smaller);
+        assert_eq!(state.last_buffer.area, smaller);
+        for y in 0..4 {
+            for x in 0..6 {
+                assert_eq!(buf[(smaller.x + x, smaller.y + y)].symbol(), symbol(x + 2, y + 1));
+            }
+        }
+
+        // Bigger than the slice, the edges stay blank until a new slice comes
+        let bigger = Rect::new(0, 0, 12, 8);
+        let buf = render(&mut state, bigger);
+        assert_eq!(state.slice_size, (12, 8));
+        assert_eq!(state.last_buffer.area, bigger);
+        assert_eq!(buf[(0, 0)].symbol(), " ");
+        assert_eq!(buf[(1, 1)].symbol(), symbol(0, 0));
+        assert_eq!(buf[(11, 7)].symbol(), " ");
     }
 }
@@ -1,5 +1,10 @@
 use std::ops::{Add, Sub};
 
+use ratatui::style::Color;
+
+// Channel levels of the xterm-256 6x6x6 color cube
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
 pub fn pos_add<T>(
     a: (T, T, T),
     b: (T, T, T))
@@ -24,6 +29,11 @@ pub fn world_pos(pos: (f64, f64, f64)) -> (i32, i32, i32) {
     (pos.2 - 0.5).round() as i32)
 }
 
+// Coordinates of the chunk column containing the block, floored for negatives
+pub fn chunk_coords(x: i32, z: i32) -> (i32, i32) {
+    (x >> 4, z >> 4)
+}
+
 pub fn in_square(
     point: (i32, i32, i32),
     relative: (i32, i32, i32),
@@ -35,9 +45,122 @@ pub fn in_square(
     point.0 > -radius && point.0 < radius && point.2 > -radius && point.2 < radius && point.1 > -height && point.1 < height
 }
 
+/// Like in_square, but the horizontal range is a circle
+pub fn in_circle(
+    point: (i32, i32, i32),
+    relative: (i32, i32, i32),
+    radius: i32,
+    height: i32)
+    -> bool
+{
+    let point = (point.0 - relative.0, point.1 - relative.1, point.2 - relative.2);
+    point.0 * point.0 + point.2 * point.2 < radius * radius && point.1 > -height && point.1 < height
+}
+
 // Minecraft specific representation of fractional position as an integer
 pub fn from_abs_int<T>(pos: (T, T, T)) -> (f64, f64, f64) 
     where T: Into<f64>
 {
     (pos.0.into() / 32., pos.1.into() / 32., pos.2.into() / 32.,)
 }
+
+/// Flattens a chat message into plain text: JSON components are joined
+/// and legacy § formatting codes are dropped
+pub fn plain_text(raw: &str) -> String {
+    let text = if raw.trim_start().starts_with('{') {
+        match json::parse(raw) {
+            Ok(component) => {
+                let mut text = String::new();
+                component_text(&component, &mut text);
+                text
+            },
+            Err(_) => raw.to_string()
+        }
+    } else {
+        raw.to_string()
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn component_text(component: &json::JsonValue, out: &mut String) {
+    if let Some(text) = component.as_str() {
+        out.push_str(text);
+        return;
+    }
+    if let Some(text) = component["text"].as_str() {
+        out.push_str(text);
+    }
+    for extra in component["extra"].members() {
+        component_text(extra, out);
+    }
+}
+
+/// Hex dump of the first `limit` bytes, e.g. "01 ff 3a .. (120 bytes)"
+pub fn hex_preview(data: &[u8], limit: usize) -> String {
+    let mut preview: Vec<String> = data.iter().take(limit).map(|b| format!("{:02x}", b)).collect();
+    if data.len() > limit {
+        preview.push("..".to_string());
+    }
+    format!("{} ({} bytes)", preview.join(" "), data.len())
+}
+
+/// Nearest xterm-256 color, whichever of the color cube and the gray ramp is closer
+pub fn quantize_256(rgb: (u8, u8, u8)) -> Color {
+    let distance = |a: (u8, u8, u8)| {
+        let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+        d(a.0, rgb.0) + d(a.1, rgb.1) + d(a.2, rgb.2)
+    };
+    let level = |v: u8| CUBE_LEVELS.iter()
+        .enumerate()
+        .min_by_key(|(_, level)| (**level as i32 - v as i32).abs())
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+    // The ramp runs from 8 to 238 in steps of 10, past the cube's black and white
+    let average = (rgb.0 as u16 + rgb.1 as u16 + rgb.2 as u16) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + step * 10;
+    if distance((gray, gray, gray)) < distance(cube) {
+        Color::Indexed(232 + step)
+    } else {
+        Color::Indexed(16 + 36 * r as u8 + 6 * g as u8 + b as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_coords_floor_negatives() {
+        assert_eq!(chunk_coords(-1, -1), (-1, -1));
+        assert_eq!(chunk_coords(-16, -16), (-1, -1));
+        assert_eq!(chunk_coords(-17, -17), (-2, -2));
+        assert_eq!(chunk_coords(0, 15), (0, 0));
+        assert_eq!(chunk_coords(16, -16), (1, -1));
+    }
+
+    // What div_floor used to give, and the in-chunk offset the columns index with
+    #[test]
+    fn chunk_coords_agree_with_div_euclid() {
+        for x in -100..100 {
+            let (chunk_x, chunk_z) = chunk_coords(x, -x);
+            assert_eq!(chunk_x, x.div_euclid(16), "x {}", x);
+            assert_eq!(chunk_z, (-x).div_euclid(16), "z {}", -x);
+            assert_eq!(x & 0xF, x.rem_euclid(16), "x {}", x);
+            assert_eq!(chunk_x * 16 + (x & 0xF), x);
+        }
+        assert_eq!(chunk_coords(i32::MIN, i32::MAX), (i32::MIN.div_euclid(16), i32::MAX.div_euclid(16)));
+    }
+
+}
@@ -1,27 +1,51 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
 use std::path::PathBuf;
+use std::sync::Mutex;
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
 use miniz_oxide::inflate::decompress_to_vec_zlib;
+use tokio::sync::mpsc::{self, UnboundedSender, UnboundedReceiver};
 use ratatui::buffer::Cell;
 use ratatui::style::Color;
 
-use crate::util::pos_add;
+use crate::log;
+use crate::util::{pos_add, chunk_coords};
+use crate::ui::{RenderMode, ascii_glyph};
 use crate::game::{GlobalContext, GameState};
 use crate::packets::{
     ChunkData,
     ChunkDataBulk,
     ChunkMetainfo,
-    MultiBlockChangeData
+    MultiBlockChangeData,
+    BlockOffsetRecords
 };
 
 const BYTE_CHUNK: usize = 16*16*16;
 const HALFBYTE_CHUNK: usize = 16*16*16/2;
 
-const AIR_ALPHA: f64 = 0.24;
+// How much of the color is faded out at the deepest rendered level
+const AIR_FADE: f64 = 0.72;
 const AIR_COLOR: (u8, u8, u8) = (0, 0, 0);
+// Water this many blocks deep or more gets the full fade
+const MAX_WATER_DEPTH: i32 = 12;
+const WATER_FADE: f64 = 0.75;
+const DEEP_WATER_COLOR: (u8, u8, u8) = (5, 10, 60);
 
-const MAX_RENDER_DEPTH: i32 = 3;
+pub const DEFAULT_RENDER_DEPTH: i32 = 3;
+pub const MAX_RENDER_DEPTH: i32 = 16;
+const RENDER_CACHE_LIMIT: usize = 1 << 18;
+// Ticks a crack stays without a new animation packet
+const BREAK_TIMEOUT: u64 = 200;
+// Ticks a block action shows for. Pistons are settled by a block change by then,
+// chests stay open until closed or for as long as a client would keep one open unseen
+const NOTE_TIMEOUT: u64 = 10;
+const PISTON_TIMEOUT: u64 = 10;
+const CHEST_TIMEOUT: u64 = 6000;
+const OPEN_CHEST_COLOR: (u8, u8, u8) = (235, 190, 60);
 const LIGHT_ENABLED: bool = false;
 const DEPTH_ENABLED: bool = true;
+// Whether movement treats blocks in unloaded columns as walls or as open air
+const UNLOADED_SOLID: bool = false;
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
@@ -49,7 +73,7 @@ pub struct ChunkColumn {
 impl ChunkColumn {
     pub fn get_block(&self, pos: (i32, i32, i32)) -> Block {
         let y = pos.1 as usize;
-        if y > self.chunks.len()*16 {
+        if y >= self.chunks.len()*16 {
             return Block::AIR;
         }
         let x = (pos.0 & 0xF) as usize;
@@ -63,8 +87,8 @@ impl ChunkColumn {
 
     pub fn set_block(&mut self, pos: (i32, i32, i32), block: Block) {
         let y = pos.1 as usize;
-        if y > self.chunks.len()*16 {
-            eprintln!("invalid set_block");
+        if y >= self.chunks.len()*16 {
+            log::warning!("Invalid set_block at {:?}: column has {} chunks", pos, self.chunks.len());
             return
         }
         let x = (pos.0 & 0xF) as usize;
@@ -101,31 +125,81 @@ impl ChunkColumn {
     }
 }
 
-#[derive(Clone)]
 pub struct World {
     columns: HashMap::<(i32, i32), ChunkColumn>,
     block_info: Vec<&'static BlockInfo>,
-    pub update: bool
+    // Depth-rendered terrain per (x, z), along with the camera Y it was rendered from
+    render_cache: Mutex<HashMap<(i32, i32), (i32, BlockRender)>>,
+    signs: HashMap<(i32, i32, i32), [String; 4]>,
+    // Destroy stage of blocks being mined and the tick it's forgotten at
+    pub breaking: HashMap<(i32, i32, i32), (u8, u64)>,
+    pub breaking_update: bool,
+    // Explosion centers seen this tick
+    pub explosions: Vec<(i32, i32, i32)>,
+    // Latest BlockAction per position and the tick it's forgotten at
+    block_actions: HashMap<(i32, i32, i32), (BlockAction, u64)>,
+    pub update: bool,
+    // Chunk packets are decoded on blocking tasks and applied in arrival order
+    decode_tx: UnboundedSender<Decoded>,
+    decode_rx: UnboundedReceiver<Decoded>,
+    next_decode: u64,
+    next_apply: u64,
+    decoded: BTreeMap<u64, Decoded>,
+    // Columns of every decode in flight, and block changes waiting on them
+    decoding: HashMap<u64, Vec<(i32, i32)>>,
+    deferred: Vec<((i32, i32, i32), u16, u8)>,
+    // Blocks read through get_block, for checking the render cache
+    #[cfg(test)]
+    lookups: AtomicUsize,
+}
+
+// Columns decoded off the game loop, numbered in the order their packets came in
+struct Decoded {
+    seq: u64,
+    columns: Vec<ChunkColumn>,
+    error: Option<String>,
+    // Column the server dropped, sent as chunk data with no sections
+    unload: Option<(i32, i32)>,
 }
 
 impl World {
-    pub fn init(resources_root: PathBuf) -> Self {
-        World {
+    pub fn init(resources_root: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        let (decode_tx, decode_rx) = mpsc::unbounded_channel();
+        Ok(World {
             columns: HashMap::new(),
-            block_info: Self::parse_info(resources_root),
-            update: true
-        }
+            block_info: Self::parse_info(resources_root)?,
+            render_cache: Mutex::new(HashMap::new()),
+            signs: HashMap::new(),
+            breaking: HashMap::new(),
+            breaking_update: false,
+            explosions: vec![],
+            block_actions: HashMap::new(),
+            update: true,
+            decode_tx,
+            decode_rx,
+            next_decode: 0,
+            next_apply: 0,
+            decoded: BTreeMap::new(),
+            decoding: HashMap::new(),
+            deferred: vec![],
+            #[cfg(test)]
+            lookups: AtomicUsize::new(0),
+        })
     }
 
-    fn parse_info(resources_root: PathBuf) -> Vec<&'static BlockInfo>{
+    fn parse_info(resources_root: PathBuf) -> Result<Vec<&'static BlockInfo>, Box<dyn std::error::Error>> {
         let block_data_path = resources_root.join("block_data.json");
-        let block_data = json::parse(&std::fs::read_to_string(block_data_path).unwrap()[..]).unwrap();
-        block_data["data"]
+        let block_data = std::fs::read_to_string(&block_data_path)
+            .map_err(|e| format!("Can't read {}: {}", block_data_path.display(), e))?;
+        let block_data = json::parse(&block_data[..])
+            .map_err(|e| format!("Bad {}: {}", block_data_path.display(), e))?;
+        Ok(block_data["data"]
             .members()
             .map(|block| &*Box::leak(Box::new(BlockInfo { 
                 id: block["id"].as_u16().unwrap_or(std::u16::MAX),
+                name: block["name"].as_str().unwrap_or("").to_string(),
                 is_solid: block["isSolid"].as_bool().unwrap_or(false)
-            }))).collect()
+            }))).collect())
     }
 
     pub fn get_block_info(&self, pos: (i32, i32, i32)) -> Option<&'static BlockInfo> {
@@ -133,78 +207,443 @@ impl World {
         self.block_info.iter().find(|b| b.id == block_id).map(|e| *e)
     }
 
+    /// Solidity for movement, unknown ids are open and unloaded columns follow UNLOADED_SOLID
+    pub fn is_solid(&self, pos: (i32, i32, i32)) -> bool {
+        let (chunk_x, chunk_z) = chunk_coords(pos.0, pos.2);
+        if !self.is_loaded(chunk_x, chunk_z) {
+            return UNLOADED_SOLID;
+        }
+        self.get_block_info(pos).is_some_and(|b| b.is_solid)
+    }
+
+    pub fn block_name(&self, id: u16) -> String {
+        match self.block_info.iter().find(|b| b.id == id) {
+            Some(info) if !info.name.is_empty() => info.name.clone(),
+            _ => format!("unknown (id {})", id)
+        }
+    }
+
+    /// Lines for the examine panel: name, decoded metadata and sign text
+    pub fn describe_block(&self, pos: (i32, i32, i32)) -> Vec<String> {
+        let block = self.get_block(pos);
+        let mut lines = vec![
+            self.block_name(block.id),
+            format!("id {}:{} at {} {} {}", block.id, block.metadata, pos.0, pos.1, pos.2),
+        ];
+        lines.extend(block_properties(&block));
+        match self.get_sign(pos) {
+            Some(text) if block.is_sign() => lines.extend(text.iter()
+                .filter(|line| !line.is_empty())
+                .map(|line| format!("\"{}\"", line))),
+            _ => {}
+        }
+        if let Some(BlockAction::Chest { viewers }) = self.block_action(pos) {
+            lines.push(format!("open, {} looking inside", viewers));
+        }
+        lines
+    }
+
+    /// Renders a `width` x `height` slice around the camera, each cell covering `zoom` x `zoom` blocks.
+    /// Top-down slices span X-Z at the camera height, side slices span X-Y at the camera Z
     pub async fn get_slice_render(
         &self,
         width: u16,
         height: u16,
+        zoom: i32,
+        mode: RenderMode,
+        camera: (i32, i32, i32),
         ctx: &GlobalContext) -> (Box<[Cell]>, (u16, u16)) 
     {
-        let global_camera = ctx.camera;
+        let mut players = vec![];
+        for player in ctx.players.iter() {
+            players.push(player.read().await.world_pos());
+        }
         let mut render = vec![];
         for y in 0..height {
             for x in 0..width {
-                let pos = (global_camera.0 - (width/2) as i32 + x as i32, global_camera.1, global_camera.2 - (height/2) as i32 + y as i32);
-                render.push(self.get_block_render(pos, ctx).await);
+                let dx = (x as i32 - (width/2) as i32) * zoom;
+                let dy = (y as i32 - (height/2) as i32) * zoom;
+                let pos = match mode {
+                    RenderMode::TopDown => (camera.0 + dx, camera.1, camera.2 + dy),
+                    RenderMode::Side => (camera.0 + dx, camera.1 - dy, camera.2),
+                };
+                render.push(self.get_block_render(pos, zoom, mode, &players, ctx));
             }
         }
         (render.into_boxed_slice(), (width/2, height/2))
     }
 
-    pub async fn get_block_render(&self, pos: (i32, i32, i32), ctx: &GlobalContext) -> Cell {
+    /// Renders the cell whose region starts at `pos` and spans `zoom` blocks across the screen plane
+    pub fn get_block_render(
+        &self,
+        pos: (i32, i32, i32),
+        zoom: i32,
+        mode: RenderMode,
+        players: &[(i32, i32, i32)],
+        ctx: &GlobalContext) -> Cell
+    {
+        let in_region = |point: (i32, i32, i32)| {
+            if point.0 < pos.0 || point.0 >= pos.0 + zoom {
+                return false;
+            }
+            match mode {
+                RenderMode::TopDown => point.1 == pos.1 && point.2 >= pos.2 && point.2 < pos.2 + zoom,
+                RenderMode::Side => point.2 == pos.2 && point.1 <= pos.1 && point.1 > pos.1 - zoom,
+            }
+        };
         if let GameState::WorldLook = ctx.mode { // TODO move to separate render layer
-            if ctx.camera == pos && ctx.tick % 10 > 4 {
+            if in_region(ctx.camera) && ctx.tick % 10 > 4 {
                 return BlockRender::CURSOR.into();
             }
         }
-        for player in ctx.players.iter() { // TODO remove when players are added as entities
-            let world_pos = player.read().await.world_pos();
-            let world_pos_top = pos_add(world_pos, (0, 1, 0));
-            if pos == world_pos || pos == world_pos_top {
+        for world_pos in players.iter() { // TODO remove when players are added as entities
+            if in_region(*world_pos) || in_region(pos_add(*world_pos, (0, 1, 0))) {
                 return BlockRender::PLAYER.into();
             }
         }
+        // Never loaded, as opposed to loaded and empty
+        let (chunk_x, chunk_z) = chunk_coords(pos.0, pos.2);
+        if !self.is_loaded(chunk_x, chunk_z) {
+            return BlockRender::FOG.into();
+        }
+        match mode {
+            RenderMode::TopDown => self.get_terrain_render(pos, ctx).into(),
+            RenderMode::Side => self.get_depth_render(pos, (0, 0, 1), ctx).0.into(),
+        }
+    }
+
+    fn get_terrain_render(&self, pos: (i32, i32, i32), ctx: &GlobalContext) -> BlockRender {
+        if let Some((y, render)) = self.render_cache.lock().unwrap().get(&(pos.0, pos.2)) {
+            if *y == pos.1 {
+                return *render;
+            }
+        }
+        let (render, animated) = self.get_depth_render(pos, (0, -1, 0), ctx);
+        // Animated blocks change every tick, so they always go through the full walk
+        if !animated {
+            let mut cache = self.render_cache.lock().unwrap();
+            if cache.len() >= RENDER_CACHE_LIMIT {
+                cache.clear();
+            }
+            cache.insert((pos.0, pos.2), (pos.1, render));
+        }
+        render
+    }
+
+    /// Looks from `pos` along `step` through up to the render depth of air
+    fn get_depth_render(
+        &self,
+        pos: (i32, i32, i32),
+        step: (i32, i32, i32),
+        ctx: &GlobalContext) -> (BlockRender, bool)
+    {
+        let at_depth = |depth: i32| (pos.0 + step.0 * depth, pos.1 + step.1 * depth, pos.2 + step.2 * depth);
         let mut block = self.get_block(pos);
         if !DEPTH_ENABLED {
-            return to_render_block(&block, ctx).into();
+            return (self.render_block(&block, pos, ctx), block.is_animated());
         }
 
+        let max_depth = ctx.render_depth;
         let mut fg_depth = 0;
 
         while block.is_air() {
             fg_depth += 1;
-            if fg_depth > MAX_RENDER_DEPTH {
-                return BlockRender::VOID.into();
+            if fg_depth > max_depth {
+                return (BlockRender::VOID, false);
             }
-            block = self.get_block((pos.0, pos.1 - fg_depth, pos.2));
+            block = self.get_block(at_depth(fg_depth));
         }
 
-        let render_fg = to_render_block(&block, ctx);
+        let mut render_fg = self.render_block(&block, at_depth(fg_depth), ctx);
+        if block.is_water() {
+            let depth = self.water_depth(at_depth(fg_depth));
+            render_fg.fg = apply_water(render_fg.fg, depth);
+            render_fg.bg = render_fg.bg.map(|bg| apply_water(bg, depth));
+        }
         let mut bg_depth = fg_depth;
         let mut render_bg = render_fg;
         while render_bg.bg.is_none() {
             bg_depth += 1;
-            if bg_depth > MAX_RENDER_DEPTH {
+            if bg_depth > max_depth {
                 render_bg = BlockRender::VOID;
                 break;
             }
-            render_bg = to_render_block(&self.get_block((pos.0, pos.1 - bg_depth, pos.2)), ctx);
+            render_bg = self.render_block(&self.get_block(at_depth(bg_depth)), at_depth(bg_depth), ctx);
         }
 
-        BlockRender {
+        (BlockRender {
             character: render_fg.character,
-            fg: apply_air(render_fg.fg, fg_depth),
-            bg: Some(apply_air(render_bg.bg.unwrap(), bg_depth))
-        }.into()
+            fg: apply_air(render_fg.fg, fg_depth, max_depth),
+            bg: Some(apply_air(render_bg.bg.unwrap(), bg_depth, max_depth))
+        }, block.is_animated())
+    }
+
+    /// Render of the block at `pos`, changed by a block action going on there
+    fn render_block(&self, block: &Block, pos: (i32, i32, i32), ctx: &GlobalContext) -> BlockRender {
+        let mut render = to_render_block(block, ctx);
+        match self.block_action(pos) {
+            Some(BlockAction::Note { pitch, .. }) if block.id == 25 => {
+                render.character = '♪';
+                render.fg = note_color(pitch);
+            },
+            // The extended bit in the metadata only comes once the piston is done moving
+            Some(BlockAction::Piston { extending, direction }) if matches!(block.id, 29 | 33) => {
+                render = piston_render(block.id, direction, extending);
+            },
+            Some(BlockAction::Chest { .. }) if matches!(block.id, 54 | 130 | 146) => {
+                render.bg = Some(OPEN_CHEST_COLOR);
+            },
+            _ => {}
+        }
+        if block.id == 55 {
+            let connected = self.neighbor_connections(pos, |next| self.redstone_connects(pos, next));
+            render.character = connection_glyph(connected, '•');
+        }
+        if block.is_fence() || block.is_wall() {
+            let connected = self.neighbor_connections(pos, |next| self.fence_connects(block, next));
+            render.character = connection_glyph(connected, '•');
+        }
+        if block.is_door() {
+            render.character = self.door_glyph(block, pos);
+        }
+        if block.is_chest() {
+            // Double chests are two chests of the same kind side by side
+            let paired = self.neighbor_connections(pos, |next| self.get_block(next).id == block.id);
+            render.character = match paired {
+                [_, _, _, true] => '⊏',
+                [_, _, true, _] => '⊐',
+                [_, true, _, _] => '⊓',
+                [true, _, _, _] => '⊔',
+                _ => render.character
+            };
+        }
+        if block.is_water() || block.is_lava() {
+            render.character = self.fluid_glyph(block, pos);
+        }
+        if ctx.ascii {
+            render.character = ascii_glyph(render.character);
+        }
+        render
+    }
+
+    /// Sources stay calm, falling fluid points down and flowing fluid points
+    /// at the neighbor it runs to, the lowest one it can spread into
+    fn fluid_glyph(&self, fluid: &Block, pos: (i32, i32, i32)) -> char {
+        const ARROWS: [char; 4] = ['↑', '↓', '←', '→'];
+        let meta = fluid.metadata;
+        if meta & 0x8 != 0 {
+            return '⇩';
+        }
+        if meta == 0 {
+            return '≈';
+        }
+        // Fluid height is 8 at a source and one less per level, open space counts
+        // as empty or, if it's over a drop, lower still
+        let height = |next: (i32, i32, i32)| {
+            let block = self.get_block(next);
+            if block.is_water() && fluid.is_water() || block.is_lava() && fluid.is_lava() {
+                return Some(if block.metadata & 0x8 != 0 { 8 } else { 8 - block.metadata as i32 });
+            }
+            if block.is_water() || block.is_lava() || self.is_solid(next) {
+                return None;
+            }
+            match self.is_solid(pos_add(next, (0, -1, 0))) {
+                true => Some(0),
+                false => Some(-1),
+            }
+        };
+        let own = 8 - meta as i32;
+        [(0, 0, -1), (0, 0, 1), (-1, 0, 0), (1, 0, 0)].iter()
+            .zip(ARROWS)
+            .filter_map(|(step, arrow)| height(pos_add(pos, *step)).map(|h| (h, arrow)))
+            .filter(|(h, _)| *h < own)
+            .min_by_key(|(h, _)| *h)
+            .map(|(_, arrow)| arrow)
+            .unwrap_or('~')
+    }
+
+    /// Door panel on the side of the cell it covers, thick while closed and thin while open.
+    /// Facing and open state are kept in the lower half, the hinge side in the upper one
+    fn door_glyph(&self, block: &Block, pos: (i32, i32, i32)) -> char {
+        // No block_action check: 1.5.2 servers never send BlockAction for doors, that's only
+        // note blocks, pistons and chests. Opening one is a BlockChange of the lower half's
+        // metadata, plus SoundOrParticleEffect 1003 for the sound
+        let (lower, upper) = match block.metadata & 0x8 {
+            0 => (block.metadata, self.get_block(pos_add(pos, (0, 1, 0))).metadata),
+            _ => (self.get_block(pos_add(pos, (0, -1, 0))).metadata, block.metadata)
+        };
+        let open = lower & 0x4 != 0;
+        let mirrored = upper & 0x1 != 0;
+        // Sides are north, east, south, west, a closed door facing east covers the west side
+        // and opening turns it a quarter, one way or the other depending on the hinge
+        let closed_side = (lower as usize & 0x3) + 3;
+        let side = match (open, mirrored) {
+            (false, _) => closed_side,
+            (true, false) => closed_side + 1,
+            (true, true) => closed_side + 3,
+        } % 4;
+        match open {
+            false => ['▀', '▐', '▄', '▌'][side],
+            true => ['▔', '▕', '▁', '▏'][side],
+        }
+    }
+
+    /// Fences and walls join their own kind, fence gates and solid blocks
+    fn fence_connects(&self, fence: &Block, next: (i32, i32, i32)) -> bool {
+        let block = self.get_block(next);
+        if block.id == fence.id || block.is_fence_gate() {
+            return true;
+        }
+        !block.is_fence() && !block.is_wall() && self.is_solid(next)
+    }
+
+    /// Which of the north, south, west and east neighbors of `pos` pass `connects`
+    fn neighbor_connections(
+        &self,
+        pos: (i32, i32, i32),
+        connects: impl Fn((i32, i32, i32)) -> bool) -> [bool; 4]
+    {
+        [(0, 0, -1), (0, 0, 1), (-1, 0, 0), (1, 0, 0)].map(|step| connects(pos_add(pos, step)))
+    }
+
+    /// Wire connects to components next to it, and to wire a step up or down unless a block cuts it off
+    fn redstone_connects(&self, wire: (i32, i32, i32), next: (i32, i32, i32)) -> bool {
+        if is_redstone_component(&self.get_block(next)) {
+            return true;
+        }
+        let above = pos_add(next, (0, 1, 0));
+        let below = pos_add(next, (0, -1, 0));
+        (self.get_block(above).id == 55 && !self.is_solid(pos_add(wire, (0, 1, 0))))
+            || (self.get_block(below).id == 55 && !self.is_solid(next))
+    }
+
+    /// Water blocks stacked from `pos` downwards
+    fn water_depth(&self, pos: (i32, i32, i32)) -> i32 {
+        let mut depth = 0;
+        while depth < MAX_WATER_DEPTH && self.get_block((pos.0, pos.1 - depth, pos.2)).is_water() {
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Drops the cached render at x, z and its neighbors, whose glyphs can depend on it
+    fn invalidate_render(&mut self, x: i32, z: i32) {
+        let cache = self.render_cache.get_mut().unwrap();
+        for (dx, dz) in [(0, 0), (0, -1), (0, 1), (-1, 0), (1, 0)] {
+            cache.remove(&(x + dx, z + dz));
+        }
+    }
+
+    fn invalidate_render_column(&mut self, chunk_x: i32, chunk_z: i32) {
+        let cache = self.render_cache.get_mut().unwrap();
+        // One block into the neighboring columns too, for glyphs that connect across the border
+        for x in -1..17 {
+            for z in -1..17 {
+                cache.remove(&(chunk_x*16 + x, chunk_z*16 + z));
+            }
+        }
+    }
+
+    pub fn get_sign(&self, pos: (i32, i32, i32)) -> Option<&[String; 4]> {
+        self.signs.get(&pos)
+    }
+
+    pub fn set_sign(&mut self, pos: (i32, i32, i32), lines: [String; 4]) {
+        self.signs.insert(pos, lines);
+    }
+
+    /// Clears every block the explosion destroyed, offsets are relative to the truncated center
+    pub fn explode(&mut self, center: (f64, f64, f64), records: &BlockOffsetRecords) {
+        let center = (center.0 as i32, center.1 as i32, center.2 as i32);
+        for offset in records.offsets.iter() {
+            let (x, y, z) = pos_add(center, (offset.0 as i32, offset.1 as i32, offset.2 as i32));
+            if !(0..256).contains(&y) {
+                continue;
+            }
+            self.set_block(x, z, y as u8, 0, 0);
+        }
+        self.explosions.push(center);
+    }
+
+    /// Stages past 9 mean the digging was finished or cancelled
+    pub fn set_break_stage(&mut self, pos: (i32, i32, i32), stage: u8, tick: u64) {
+        if stage > 9 {
+            self.breaking.remove(&pos);
+        } else {
+            self.breaking.insert(pos, (stage, tick + BREAK_TIMEOUT));
+        }
+        self.breaking_update = true;
+    }
+
+    pub fn expire_breaks(&mut self, tick: u64) {
+        let before = self.breaking.len();
+        self.breaking.retain(|_, (_, until)| *until > tick);
+        if self.breaking.len() != before {
+            self.breaking_update = true;
+        }
+    }
+
+    /// Remembers a BlockAction for rendering, the meaning of the bytes depends on the block
+    pub fn set_block_action(&mut self, pos: (i32, i32, i32), block_id: i16, hb: u8, lb: u8, tick: u64) {
+        let (action, timeout) = match block_id {
+            25 => (BlockAction::Note { instrument: hb, pitch: lb }, NOTE_TIMEOUT),
+            29 | 33 => (BlockAction::Piston { extending: hb == 0, direction: lb }, PISTON_TIMEOUT),
+            54 | 130 | 146 => (BlockAction::Chest { viewers: lb }, CHEST_TIMEOUT),
+            _ => {
+                log::debug!("Unhandled block action {} {} for block {} at {:?}", hb, lb, block_id, pos);
+                return;
+            }
+        };
+        if let BlockAction::Chest { viewers: 0 } = action {
+            self.block_actions.remove(&pos);
+        } else {
+            self.block_actions.insert(pos, (action, tick + timeout));
+        }
+        self.invalidate_render(pos.0, pos.2);
+        self.update = true;
+    }
+
+    pub fn expire_block_actions(&mut self, tick: u64) {
+        let expired: Vec<(i32, i32, i32)> = self.block_actions.iter()
+            .filter(|(_, (_, until))| *until <= tick)
+            .map(|(pos, _)| *pos)
+            .collect();
+        for pos in expired {
+            self.block_actions.remove(&pos);
+            self.invalidate_render(pos.0, pos.2);
+            self.update = true;
+        }
+    }
+
+    pub fn block_action(&self, pos: (i32, i32, i32)) -> Option<BlockAction> {
+        self.block_actions.get(&pos).map(|(action, _)| *action)
+    }
+
+    fn clear_break(&mut self, pos: (i32, i32, i32)) {
+        if self.breaking.remove(&pos).is_some() {
+            self.breaking_update = true;
+        }
+    }
+
+    pub fn is_loaded(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        self.columns.contains_key(&(chunk_x, chunk_z))
+    }
+
+    /// Loaded columns and the 16x16x16 chunks present in them
+    pub fn loaded_chunks(&self) -> (usize, usize) {
+        let chunks = self.columns.values()
+            .map(|column| column.chunks.iter().flatten().count())
+            .sum();
+        (self.columns.len(), chunks)
     }
 
     pub fn get_block(&self, pos: (i32, i32, i32)) -> Block {
+        #[cfg(test)]
+        self.lookups.fetch_add(1, Ordering::Relaxed);
         if pos.1 < 0 {
             return Block::AIR; // Void ??
         }
-        let chunk_pos = (
-                pos.0 >> 4,
-                pos.2 >> 4
-            );
+        let chunk_pos = chunk_coords(pos.0, pos.2);
         if !self.columns.contains_key(&chunk_pos) {
             return Block::AIR;
         }
@@ -213,27 +652,137 @@ impl World {
     }
 
     pub fn set_chunk(&mut self, data: ChunkData) {
-        self.parse(
-            &decompress_to_vec_zlib(&data.compressed).unwrap()[..],
-            &[data.metainfo],
-            true,
-            data.ground_up_continuous);
+        let meta = &data.metainfo;
+        if data.ground_up_continuous && meta.primary == 0 {
+            // Nothing to decode, just keep its place in line
+            let seq = self.next_decode;
+            self.next_decode += 1;
+            self.decoded.insert(seq, Decoded {
+                seq,
+                columns: vec![],
+                error: None,
+                unload: Some((meta.x, meta.z))
+            });
+            return;
+        }
+        self.decode(data.compressed, vec![data.metainfo], true, data.ground_up_continuous);
+    }
+
+    pub fn set_chunk_bulk(&mut self, data: ChunkDataBulk) {
+        self.decode(data.compressed, data.metainfo, data.has_skylight, true);
+    }
+
+    /// Inflates and parses chunk data on a blocking task, see apply_decoded
+    fn decode(&mut self, compressed: Box<[u8]>, metadata: Vec<ChunkMetainfo>, skylight: bool, ground_up: bool) {
+        let seq = self.next_decode;
+        self.next_decode += 1;
+        self.decoding.insert(seq, metadata.iter().map(|meta| (meta.x, meta.z)).collect());
+        let tx = self.decode_tx.clone();
+        tokio::task::spawn_blocking(move || {
+            let decoded = match decompress_to_vec_zlib(&compressed) {
+                Ok(data) => {
+                    let (columns, error) = parse(&data[..], &metadata[..], skylight, ground_up);
+                    Decoded { seq, columns, error: error.map(|e| e.to_string()), unload: None }
+                },
+                Err(e) => Decoded {
+                    seq,
+                    columns: vec![],
+                    error: Some(format!("can't inflate: {:?}", e)),
+                    unload: None
+                }
+            };
+            // The world is gone if nobody is listening
+            let _ = tx.send(decoded);
+        });
+    }
+
+    /// Puts finished columns into the world, in the order their packets arrived
+    pub fn apply_decoded(&mut self) {
+        while let Ok(decoded) = self.decode_rx.try_recv() {
+            // Anything started before a clear is stale
+            if decoded.seq >= self.next_apply {
+                self.decoded.insert(decoded.seq, decoded);
+            }
+        }
+        while let Some(decoded) = self.decoded.remove(&self.next_apply) {
+            self.decoding.remove(&self.next_apply);
+            self.next_apply += 1;
+            if let Some(e) = decoded.error {
+                log::error!("Bad chunk data: {}", e);
+            }
+            if let Some((x, z)) = decoded.unload {
+                self.remove_column(x, z);
+            }
+            for column in decoded.columns {
+                let key = (column.x, column.z);
+                self.invalidate_render_column(key.0, key.1);
+                self.columns.insert(key, column);
+            }
+            self.update = true;
+        }
+        if self.deferred.is_empty() {
+            return;
+        }
+        for (pos, id, meta) in std::mem::take(&mut self.deferred) {
+            self.set_block(pos.0, pos.2, pos.1 as u8, id, meta);
+        }
+    }
+
+    /// Whether a chunk packet for this column is still being decoded
+    fn is_decoding(&self, column: (i32, i32)) -> bool {
+        self.decoding.values().any(|columns| columns.contains(&column))
+    }
+
+    pub fn clear_render_cache(&mut self) {
+        self.render_cache.get_mut().unwrap().clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.columns.clear();
+        // Decodes still running are dropped when they come back
+        self.next_apply = self.next_decode;
+        self.decoded.clear();
+        self.decoding.clear();
+        self.deferred.clear();
+        self.signs.clear();
+        self.block_actions.clear();
+        self.breaking.clear();
+        self.breaking_update = true;
+        self.render_cache.get_mut().unwrap().clear();
         self.update = true;
     }
 
-    pub fn set_chunk_bulk(&mut self, data: &ChunkDataBulk) {
-        self.parse(
-            &decompress_to_vec_zlib(&data.compressed).unwrap()[..],
-            &data.metainfo[..],
-            data.has_skylight,
-            true);
+    /// Drops every column further than `radius` chunks from all of the given positions,
+    /// returns how many were freed
+    pub fn unload_far(&mut self, centers: &[(i32, i32, i32)], radius: i32) -> usize {
+        let centers: Vec<(i32, i32)> = centers.iter()
+            .map(|pos| chunk_coords(pos.0, pos.2))
+            .collect();
+        let far: Vec<(i32, i32)> = self.columns.keys()
+            .filter(|(x, z)| !centers.iter()
+                .any(|center| (x - center.0).abs() <= radius && (z - center.1).abs() <= radius))
+            .cloned()
+            .collect();
+        for (x, z) in far.iter() {
+            self.remove_column(*x, *z);
+        }
+        far.len()
+    }
+
+    fn remove_column(&mut self, x: i32, z: i32) {
+        self.columns.remove(&(x, z));
+        self.invalidate_render_column(x, z);
+        self.signs.retain(|pos, _| chunk_coords(pos.0, pos.2) != (x, z));
+        self.block_actions.retain(|pos, _| chunk_coords(pos.0, pos.2) != (x, z));
         self.update = true;
     }
 
     pub fn set_block_multiple(&mut self, data: &MultiBlockChangeData) {
         let chunk_x = data.x;
         let chunk_z = data.z;
-        let column = self.columns.get_mut(&(chunk_x, chunk_z)).unwrap();
+        let decoding = self.is_decoding((chunk_x, chunk_z));
+        let column = self.columns.entry((chunk_x, chunk_z))
+            .or_insert_with(|| ChunkColumn::new(chunk_x, chunk_z));
         for i in 0..data.record_count {
             let i = (i*4) as usize;
             let a = data.bytes[i];
@@ -249,14 +798,30 @@ impl World {
             let mut block = Block::new();
             block.id = id;
             block.metadata = meta;
-            column.set_block((x as i32 + chunk_x*16, y as i32, z as i32 + chunk_z*16), block)
+            let pos = (x as i32 + chunk_x*16, y as i32, z as i32 + chunk_z*16);
+            if decoding {
+                self.deferred.push((pos, id, meta));
+                continue;
+            }
+            if !block.is_sign() {
+                self.signs.remove(&pos);
+            }
+            if self.breaking.remove(&pos).is_some() {
+                self.breaking_update = true;
+            }
+            column.set_block(pos, block)
         }
+        self.invalidate_render_column(chunk_x, chunk_z);
         self.update = true;
     }
 
     pub fn set_block(&mut self, x: i32, z: i32, y: u8, block_type: u16, block_meta: u8) {
-        let chunk_x = x.div_floor(16);
-        let chunk_z = z.div_floor(16);
+        let (chunk_x, chunk_z) = chunk_coords(x, z);
+        // The column would overwrite this once decoded
+        if self.is_decoding((chunk_x, chunk_z)) {
+            self.deferred.push(((x, y as i32, z), block_type, block_meta));
+            return;
+        }
         if !self.columns.contains_key(&(chunk_x, chunk_z)) {
             self.columns.insert((chunk_x, chunk_z), ChunkColumn::new(chunk_x, chunk_z));
         }
@@ -264,93 +829,155 @@ impl World {
         let mut block = Block::new();
         block.id = block_type;
         block.metadata = block_meta;
+        if !block.is_sign() {
+            self.signs.remove(&(x, y as i32, z));
+        }
         column.set_block((x, y as i32, z), block);
+        self.clear_break((x, y as i32, z));
+        self.invalidate_render(x, z);
         self.update = true;
     }
+}
 
-    pub fn parse(
-        &mut self,
-        chunk_data: &[u8],
-        metadata: &[ChunkMetainfo],
-        skylight: bool,
-        ground_up: bool
-    ) { 
-        let data_total = chunk_data.len();
-        let mut data_consumed = 0;
-        let data_iter = &mut chunk_data.into_iter();
-        for ChunkMetainfo {x, z, primary, add } in metadata {
-            let mut column = ChunkColumn::empty(*x, *z);
-            for y in 0..16 {
-                if primary & (1 << y) != 0 {
-                    let chunk = Chunk {
-                        y,
-                        blocks: [Block::new(); BYTE_CHUNK],
-                    };
-                    column.chunks.push(Some(chunk));
-                } else {
-                    column.chunks.push(None);
-                }
+/// Splits decompressed chunk data into columns. Columns read before an error are kept
+fn parse(
+    chunk_data: &[u8],
+    metadata: &[ChunkMetainfo],
+    skylight: bool,
+    ground_up: bool
+) -> (Vec<ChunkColumn>, Option<WorldError>) {
+    let mut columns = vec![];
+    let data_total = chunk_data.len();
+    let mut data_consumed = 0;
+    let data_iter = &mut chunk_data.into_iter();
+    for ChunkMetainfo {x, z, primary, add } in metadata {
+        // Columns are packed back to back, so a short one means the rest can't be located either
+        let sections = primary.count_ones() as usize;
+        let add_sections = (primary & add).count_ones() as usize;
+        let column_size = sections * (BYTE_CHUNK + HALFBYTE_CHUNK * if skylight {3} else {2})
+            + add_sections * HALFBYTE_CHUNK
+            + if ground_up {256} else {0};
+        if data_total - data_consumed < column_size {
+            let error = WorldError::Truncated {
+                column: (*x, *z),
+                expected: column_size,
+                available: data_total - data_consumed
+            };
+            return (columns, Some(error));
+        }
+        let mut column = ChunkColumn::empty(*x, *z);
+        for y in 0..16 {
+            if primary & (1 << y) != 0 {
+                let chunk = Chunk {
+                    y,
+                    blocks: [Block::new(); BYTE_CHUNK],
+                };
+                column.chunks.push(Some(chunk));
+            } else {
+                column.chunks.push(None);
             }
-            for chunk in column.chunks.iter_mut().filter(|c| c.is_some()).map(|c| c.as_mut().unwrap()) {
-                chunk.blocks.iter_mut().zip(data_iter.take(BYTE_CHUNK))
-                    .for_each(|(block, id)| block.id = *id as u16);
-                data_consumed += BYTE_CHUNK;
+        }
+        // Each column is a run of arrays, every array covering the sections set in
+        // `primary`, one after another, bottom section first:
+        // block ids, metadata, block light, sky light (only with skylight), the add
+        // nibbles (only sections also set in `add`), then 256 biome bytes (ground up only).
+        // Bulk packets always send whole columns, so every column carries its own biomes
+        for chunk in column.chunks.iter_mut().flatten() {
+            chunk.blocks.iter_mut().zip(data_iter.take(BYTE_CHUNK))
+                .for_each(|(block, id)| block.id = *id as u16);
+            data_consumed += BYTE_CHUNK;
         }
 
-        for chunk in column.chunks.iter_mut().filter(|c| c.is_some()).map(|c| c.as_mut().unwrap()) {
+        // Half-byte arrays hold the even block in the low nibble
+        for chunk in column.chunks.iter_mut().flatten() {
             chunk.blocks.chunks_mut(2).zip(data_iter.take(HALFBYTE_CHUNK))
                 .for_each(|(block, metadata)| {
                     block[0].metadata = metadata & 0x0F;
                     block[1].metadata = (metadata & 0xF0) >> 4;
                 });
             data_consumed += HALFBYTE_CHUNK;
-            }
+        }
 
-            for chunk in column.chunks.iter_mut().filter(|c| c.is_some()).map(|c| c.as_mut().unwrap()) {
+        for chunk in column.chunks.iter_mut().flatten() {
+            chunk.blocks.chunks_mut(2).zip(data_iter.take(HALFBYTE_CHUNK))
+                .for_each(|(block, light)| {
+                    block[0].light = light & 0x0F;
+                    block[1].light = (light & 0xF0) >> 4;
+                });
+            data_consumed += HALFBYTE_CHUNK;
+        }
+
+        if skylight {
+            for chunk in column.chunks.iter_mut().flatten() {
                 chunk.blocks.chunks_mut(2).zip(data_iter.take(HALFBYTE_CHUNK))
-                    .for_each(|(block, light)| {
-                        block[0].light = light & 0x0F;
-                        block[1].light = (light & 0xF0) >> 4;
+                    .for_each(|(block, skylight)| {
+                        block[0].skylit = skylight & 0x0F;
+                        block[1].skylit = (skylight & 0xF0) >> 4;
                     });
                 data_consumed += HALFBYTE_CHUNK;
             }
+        }
 
-            for chunk in column.chunks.iter_mut().filter(|c| c.is_some()).map(|c| c.as_mut().unwrap()) {
-                if skylight {
-                    data_consumed += HALFBYTE_CHUNK;
-                    chunk.blocks.chunks_mut(2).zip(data_iter.take(HALFBYTE_CHUNK))
-                        .for_each(|(block, skylight)| {
-                            block[0].skylit = skylight & 0x0F;
-                            block[1].skylit = (skylight & 0xF0) >> 4;
-                        });
-                }
+        // The add nibble is bits 8-11 of the block id
+        for chunk in column.chunks.iter_mut().flatten() {
+            if add & (1 << chunk.y) != 0 {
+                chunk.blocks.chunks_mut(2).zip(data_iter.take(HALFBYTE_CHUNK))
+                    .for_each(|(block, add_id)| {
+                        block[0].id |= ((add_id & 0x0F) as u16) << 8;
+                        block[1].id |= (((add_id & 0xF0) >> 4) as u16) << 8;
+                    });
+                data_consumed += HALFBYTE_CHUNK;
             }
+        }
 
-            for chunk in column.chunks.iter_mut().filter(|c| c.is_some()).map(|c| c.as_mut().unwrap()) {
-                if add & (1 << chunk.y) != 0 {
-                    data_consumed += HALFBYTE_CHUNK;
-                    chunk.blocks.chunks_mut(2).zip(data_iter.take(HALFBYTE_CHUNK))
-                        .for_each(|(block, add_id)| {
-                            block[0].id += (add_id & 0x0F) as u16;
-                            block[1].id += ((add_id& 0xF0) >> 4) as u16;
-                        });
-                }
-            }
+        if ground_up {
+            column.biome.iter_mut().zip(data_iter.take(256))
+                .for_each(|(biome, value)| *biome = *value);
+            data_consumed += 256;
+        }
+        columns.push(column);
+    }
+    if data_consumed != data_total {
+        return (columns, Some(WorldError::TrailingData(data_total - data_consumed)));
+    }
+    (columns, None)
+}
 
-            if ground_up {
-                data_consumed += 256;
-                column.biome.iter_mut().zip(data_iter.take(256))
-                    .for_each(|(biome, value)| *biome = *value)
-            }
-            self.columns.insert((*x, *z), column);
+#[derive(Debug)]
+pub enum WorldError {
+    Truncated {
+        column: (i32, i32),
+        expected: usize,
+        available: usize
+    },
+    TrailingData(usize),
+}
+
+impl std::fmt::Display for WorldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WorldError::Truncated { column, expected, available } =>
+                write!(f, "column {:?} needs {} bytes, only {} left", column, expected, available),
+            WorldError::TrailingData(count) =>
+                write!(f, "{} bytes left over after the last column", count),
         }
-        assert_eq!(data_total, data_consumed);
-        assert_eq!(data_iter.count(), 0);
     }
 }
 
-fn apply_air(color: (u8, u8, u8), depth: i32) -> (u8, u8, u8){
-    let alpha = AIR_ALPHA * depth as f64;
+impl std::error::Error for WorldError {}
+
+/// Darkens water by depth, a single block of water stays as is
+fn apply_water(color: (u8, u8, u8), depth: i32) -> (u8, u8, u8) {
+    let alpha = WATER_FADE * (depth - 1).max(0) as f64 / (MAX_WATER_DEPTH - 1) as f64;
+    (
+        (alpha * DEEP_WATER_COLOR.0 as f64 + (1.0 - alpha) * color.0 as f64) as u8,
+        (alpha * DEEP_WATER_COLOR.1 as f64 + (1.0 - alpha) * color.1 as f64) as u8,
+        (alpha * DEEP_WATER_COLOR.2 as f64 + (1.0 - alpha) * color.2 as f64) as u8,
+    )
+}
+
+fn apply_air(color: (u8, u8, u8), depth: i32, max_depth: i32) -> (u8, u8, u8){
+    let alpha = AIR_FADE * depth.min(max_depth) as f64 / max_depth as f64;
     (
         (alpha * AIR_COLOR.0 as f64 + (1.0 - alpha) * color.0 as f64) as u8,
         (alpha * AIR_COLOR.1 as f64 + (1.0 - alpha) * color.1 as f64) as u8,
@@ -358,6 +985,23 @@ fn apply_air(color: (u8, u8, u8), depth: i32) -> (u8, u8, u8){
     )
 }
 
+/// What a BlockAction packet last said about a block
+#[derive(Clone, Copy, Debug)]
+pub enum BlockAction {
+    // Pitch 0-24, two octaves up from F#
+    Note { instrument: u8, pitch: u8 },
+    Piston { extending: bool, direction: u8 },
+    // Only kept while someone has it open
+    Chest { viewers: u8 },
+}
+
+/// Note particle color, cycling through the hues over the two octaves
+fn note_color(pitch: u8) -> (u8, u8, u8) {
+    let hue = pitch as f64 / 24. * std::f64::consts::TAU;
+    let channel = |offset: f64| (((hue + offset).sin() * 0.65 + 0.35).clamp(0., 1.) * 255.) as u8;
+    (channel(0.), channel(std::f64::consts::TAU / 3.), channel(std::f64::consts::TAU * 2. / 3.))
+}
+
 // TODO separate block from its rendering?
 #[derive(Clone, Copy, Debug)]
 pub struct BlockRender {
@@ -405,6 +1049,12 @@ impl BlockRender {
         character: ' '
     };
 
+    pub const FOG: BlockRender = BlockRender {
+        fg: (58, 58, 64),
+        bg: Some((28, 28, 32)),
+        character: '░'
+    };
+
     pub const UNKNOWN: BlockRender = BlockRender {
         fg: (255, 0, 255),
         bg: Some((128, 0, 128)),
@@ -415,6 +1065,7 @@ impl BlockRender {
 #[derive(Debug)]
 pub struct BlockInfo {
     pub id: u16,
+    pub name: String,
     pub is_solid: bool,
 }
 
@@ -439,6 +1090,46 @@ impl Block {
         self.id == 0
     }
 
+    pub fn is_animated(&self) -> bool {
+        self.id == 51 || self.is_lava() // fire, lava
+    }
+
+    pub fn is_water(&self) -> bool {
+        self.id == 8 || self.id == 9 // flowing, still
+    }
+
+    pub fn is_lava(&self) -> bool {
+        self.id == 10 || self.id == 11 // flowing, still
+    }
+
+    pub fn is_bed(&self) -> bool {
+        self.id == 26
+    }
+
+    pub fn is_chest(&self) -> bool {
+        self.id == 54 || self.id == 146 // chest, trapped chest
+    }
+
+    pub fn is_door(&self) -> bool {
+        self.id == 64 || self.id == 71 // wooden, iron
+    }
+
+    pub fn is_fence(&self) -> bool {
+        self.id == 85 || self.id == 113 // wooden, nether brick
+    }
+
+    pub fn is_fence_gate(&self) -> bool {
+        self.id == 107
+    }
+
+    pub fn is_wall(&self) -> bool {
+        self.id == 139 // cobblestone and mossy
+    }
+
+    pub fn is_sign(&self) -> bool {
+        self.id == 63 || self.id == 68 // standing, wall
+    }
+
     const AIR: Block = Block {
         id: 0,
         metadata: 0,
@@ -447,6 +1138,89 @@ impl Block {
     };
 }
 
+const SLAB_STONES: [&str; 8] = [
+    "stone", "sandstone", "wooden", "cobblestone", "brick", "stone brick", "nether brick", "quartz"
+];
+const SLAB_WOODS: [&str; 4] = ["oak", "spruce", "birch", "jungle"];
+// Wool, carpet and stained clay colors by metadata
+const DYES: [(&str, (u8, u8, u8)); 16] = [
+    ("white", (234, 236, 237)),
+    ("orange", (240, 118, 19)),
+    ("magenta", (189, 68, 179)),
+    ("light blue", (58, 175, 217)),
+    ("yellow", (248, 197, 39)),
+    ("lime", (112, 185, 25)),
+    ("pink", (237, 141, 172)),
+    ("gray", (62, 68, 71)),
+    ("light gray", (142, 142, 134)),
+    ("cyan", (21, 137, 145)),
+    ("purple", (121, 42, 172)),
+    ("blue", (53, 57, 157)),
+    ("brown", (114, 71, 40)),
+    ("green", (84, 109, 27)),
+    ("red", (160, 39, 34)),
+    ("black", (20, 21, 25)),
+];
+const CLAY_COLOR: (u8, u8, u8) = (152, 94, 67);
+const FACES: [&str; 6] = ["down", "up", "north", "south", "west", "east"];
+const RAIL_SHAPES: [&str; 10] = [
+    "north-south", "east-west",
+    "ascending east", "ascending west", "ascending north", "ascending south",
+    "south-east curve", "south-west curve", "north-west curve", "north-east curve"
+];
+
+/// Decodes the metadata of blocks where it means something readable
+fn block_properties(block: &Block) -> Vec<String> {
+    let meta = block.metadata;
+    let half = |meta: u8| if meta & 0x8 == 0 { "lower half" } else { "upper half" };
+    match block.id {
+        43 => vec![format!("{} slabs", SLAB_STONES[(meta & 0x7) as usize])],
+        44 => vec![format!("{} slab", SLAB_STONES[(meta & 0x7) as usize]), half(meta).to_string()],
+        125 => vec![format!("{} slabs", SLAB_WOODS[(meta & 0x3) as usize])],
+        126 => vec![format!("{} slab", SLAB_WOODS[(meta & 0x3) as usize]), half(meta).to_string()],
+        66 => vec![RAIL_SHAPES.get(meta as usize).unwrap_or(&"bad shape").to_string()],
+        // Powered, detector and activator rails can't curve, the high bit is the power
+        27 | 28 | 157 => vec![
+            RAIL_SHAPES.get((meta & 0x7) as usize).filter(|_| meta & 0x7 < 6).unwrap_or(&"bad shape").to_string(),
+            if meta & 0x8 == 0 { "unpowered" } else { "powered" }.to_string()
+        ],
+        64 | 71 if meta & 0x8 != 0 => vec![
+            "upper half".to_string(),
+            if meta & 0x1 == 0 { "hinge on the left" } else { "hinge on the right" }.to_string()
+        ],
+        64 | 71 => vec![
+            "lower half".to_string(),
+            format!("facing {}", ["east", "south", "west", "north"][(meta & 0x3) as usize]),
+            if meta & 0x4 == 0 { "closed" } else { "open" }.to_string()
+        ],
+        29 | 33 => vec![
+            format!("facing {}", FACES.get((meta & 0x7) as usize).unwrap_or(&"nowhere")),
+            if meta & 0x8 == 0 { "retracted" } else { "extended" }.to_string()
+        ],
+        17 => vec![
+            format!("{} wood", SLAB_WOODS[(meta & 0x3) as usize]),
+            ["up-down", "east-west", "north-south", "bark only"][(meta >> 2 & 0x3) as usize].to_string()
+        ],
+        53 | 67 | 108 | 109 | 114 | 128 | 134..=136 | 156 => vec![
+            format!("ascending {}", ["east", "west", "south", "north"][(meta & 0x3) as usize]),
+            if meta & 0x4 == 0 { "right side up" } else { "upside-down" }.to_string()
+        ],
+        50 | 75 | 76 => vec![match meta {
+            1 => "on the west wall",
+            2 => "on the east wall",
+            3 => "on the north wall",
+            4 => "on the south wall",
+            _ => "on the floor"
+        }.to_string()],
+        35 | 159 | 171 => vec![DYES[(meta & 0xF) as usize].0.to_string()],
+        55 => vec![format!("power {}/15", meta)],
+        93 | 94 => vec![format!("delay {} ticks", (meta >> 2) + 1)],
+        8..=11 if meta & 0x8 != 0 => vec!["falling".to_string()],
+        8..=11 => vec![format!("level {}", 8 - meta)],
+        _ => vec![]
+    }
+}
+
 fn to_render_block(block: &Block, ctx: &GlobalContext) -> BlockRender {
     let render_dict = HashMap::<(u16, u8), BlockRender>::new();
     // TODO load from resources / blockinfo 
@@ -465,28 +1239,143 @@ fn color(r: u8, g: u8, b: u8) -> Option<(u8, u8, u8)> {
     Some((r, g, b))
 }
 
+/// Color the palette can override, by its name there
+fn named(ctx: &GlobalContext, name: &str) -> Option<(u8, u8, u8)> {
+    Some(ctx.palette.rgb(name))
+}
+
+/// Darker (below 1) or lighter shade of a color
+fn shade(c: (u8, u8, u8), factor: f64) -> (u8, u8, u8) {
+    let channel = |v: u8| (v as f64 * factor).min(255.) as u8;
+    (channel(c.0), channel(c.1), channel(c.2))
+}
+
+/// Stained clay is the dye color muted toward the plain clay
+fn stained_clay(meta: u8) -> (u8, u8, u8) {
+    let dye = DYES[(meta & 0xF) as usize].1;
+    let mix = |a: u8, b: u8| ((a as u16 + b as u16) / 2) as u8;
+    (mix(dye.0, CLAY_COLOR.0), mix(dye.1, CLAY_COLOR.1), mix(dye.2, CLAY_COLOR.2))
+}
+
+/// Torches on the floor stand upright, ones on a wall lean toward it.
+/// Metadata 1 to 4 is the side they point to: east, west, south and north
+fn torch_glyph(meta: u8) -> char {
+    match meta {
+        1 => '◂',
+        2 => '▸',
+        3 => '▴',
+        4 => '▾',
+        _ => '༈'
+    }
+}
+
+/// Stairs seen from above, facing in the low bits is the side they ascend toward.
+/// Right side up ones are the tall side and the step, upside-down ones only the tall side
+fn stair_glyph(meta: u8) -> char {
+    const UPRIGHT: [char; 4] = ['▟', '▛', '▙', '▜'];
+    const UPSIDE_DOWN: [char; 4] = ['▐', '▌', '▄', '▀'];
+    match meta & 0x4 {
+        0 => UPRIGHT[(meta & 0x3) as usize],
+        _ => UPSIDE_DOWN[(meta & 0x3) as usize]
+    }
+}
+
+/// Crop color going from sprout green at stage 0 to `ripe` at the last stage
+fn crop_color(stage: u8, last: u8, ripe: (u8, u8, u8)) -> Option<(u8, u8, u8)> {
+    const SPROUT: (u8, u8, u8) = (60, 190, 40);
+    let t = stage.min(last) as f64 / last as f64;
+    let mix = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * t) as u8;
+    color(mix(SPROUT.0, ripe.0), mix(SPROUT.1, ripe.1), mix(SPROUT.2, ripe.2))
+}
+
+/// Blocks redstone wire draws a connection to
+fn is_redstone_component(block: &Block) -> bool {
+    matches!(block.id,
+        55 // wire
+        | 28 // detector rail
+        | 69 | 77 | 143 // lever, buttons
+        | 70 | 72 | 147 | 148 // pressure plates
+        | 75 | 76 // torches
+        | 93 | 94 | 149 | 150 // repeaters, comparators
+        | 131 // tripwire hook
+        | 151 | 152) // daylight sensor, redstone block
+}
+
+/// Box-drawing glyph joining the connected sides in north, south, west, east order, north being up
+fn connection_glyph(connected: [bool; 4], isolated: char) -> char {
+    match connected {
+        [false, false, false, false] => isolated,
+        [_, _, false, false] => '│',
+        [false, false, _, _] => '─',
+        [true, false, true, false] => '┘',
+        [true, false, false, true] => '└',
+        [false, true, true, false] => '┐',
+        [false, true, false, true] => '┌',
+        [true, true, true, false] => '┤',
+        [true, true, false, true] => '├',
+        [true, false, true, true] => '┴',
+        [false, true, true, true] => '┬',
+        [true, true, true, true] => '┼',
+    }
+}
+
+/// Pistons by facing (down, up, north, south, west, east). Retracted ones show an arrow,
+/// extended ones the arm reaching out of the face toward the head
+fn piston_render(id: u16, direction: u8, extended: bool) -> BlockRender {
+    const RETRACTED: [char; 6] = ['○', '●', '↥', '↧', '↤', '↦'];
+    const EXTENDED: [char; 6] = ['◌', '◉', '╨', '╥', '╡', '╞'];
+    let glyphs = if extended { EXTENDED } else { RETRACTED };
+    let fg = if extended { (255, 58, 58) } else { (128, 128, 128) };
+    // Sticky ones have the slime face
+    let bg = if id == 29 { (108, 208, 108) } else { (108, 108, 108) };
+    BlockRender {
+        character: glyphs.get(direction as usize).copied().unwrap_or('?'),
+        fg,
+        bg: Some(bg)
+    }
+}
+
 fn to_render_block_old(id: u16, meta: u8, ctx: &GlobalContext) -> BlockRender {
     // TODO move to resources / blockinfo
     let (character, fg, bg) = match id {
         0 => ('█', Some(AIR_COLOR), None),
         1 => ('█', color(158, 158, 158), color(158, 158, 158)),
-        2 => ('█', color(10, 215, 10), color(10, 215, 10)),
+        2 => ('█', named(ctx, "grass"), named(ctx, "grass")),
         3 => ('█', color(156, 112, 76), color(156, 112, 76)),
         4 => ('▒', color(128, 128, 128), color(108, 108, 108)),
         5 => ('█', color(188, 152, 98), color(204, 205, 139)),
-        6 => ('ፑ', color(156, 112, 76), color(10, 215, 10)),
+        6 => match meta & 0x3 {
+            1 => ('ፑ', color(70, 50, 30), named(ctx, "grass")), // spruce
+            2 => ('ፑ', color(220, 220, 200), named(ctx, "grass")), // birch
+            3 => ('ፑ', color(150, 105, 40), named(ctx, "grass")), // jungle
+            _ => ('ፑ', color(156, 112, 76), named(ctx, "grass"))
+        }, // sapling
         7 => ('▒', color(128, 128, 128),color(24, 24, 24)),
-        8 => ('~', color(87, 151, 255), color(61, 64, 255)),
-        9 => ('≈', color(87, 151, 255), color(61, 64, 255)),
-        10 => ('~', color(255, 213, 0), color(255, 48, 0)),
-        11 => ('≈', color(255, 213, 0), color(255, 48, 0)),
+        8 => ('~', named(ctx, "water"), named(ctx, "deep_water")),
+        9 => ('≈', named(ctx, "water"), named(ctx, "deep_water")),
+        10 | 11 => {
+            // Glows brighter and dimmer over a second
+            let pulse = (ctx.tick % 20).abs_diff(10) as u8 * 4;
+            let character = if id == 10 { '~' } else { '≈' };
+            (character, color(255, 173 + pulse, 0), color(255, 28 + pulse, 0))
+        },
         12 => ('█', color(254, 255, 189), color(254, 255, 189)),
         13 => ('#', color(117, 112, 110), color(196, 185, 183)),
         14 => ('&', color(212, 158, 158), color(158, 158, 158)),
         15 => ('&', color(212, 158, 158), color(158, 158, 158)),
         16 => ('&', color(25, 25, 25), color(158, 158, 158)),
-        17 => ('O', color(230, 172, 110), color(110, 69, 45)),
-        18 => ('░', color(12, 223, 12), None),
+        17 => match meta & 0xC {
+            0x4 => ('─', color(230, 172, 110), color(110, 69, 45)), // east-west
+            0x8 => ('│', color(230, 172, 110), color(110, 69, 45)), // north-south
+            0xC => ('▒', color(110, 69, 45), color(90, 55, 35)), // bark all around
+            _ => ('O', color(230, 172, 110), color(110, 69, 45)) // up-down, rings seen from above
+        }, // log
+        18 => match meta & 0x3 {
+            1 => ('░', color(50, 110, 70), None), // spruce
+            2 => ('░', color(130, 190, 80), None), // birch
+            3 => ('░', color(20, 165, 10), None), // jungle
+            _ => ('░', named(ctx, "leaves"), None)
+        }, // leaves
         20 => ('‘', color(0, 255, 255), None), // glass
         21 => ('&', color(0, 69, 181), color(158, 158, 158)),
         24 => ('█', color(204, 205, 139), color(204, 205, 139)),
@@ -513,43 +1402,10 @@ fn to_render_block_old(id: u16, meta: u8, ctx: &GlobalContext) -> BlockRender {
             };
             (character, power, None)
         }, // powered rail
-        29 => {
-            let power = if meta & 0b1000 == 8 {
-                color(255, 58, 58)
-            } else {
-                color(128, 128, 128)
-            };
-            let character = match meta & 0b0111 {
-                0 => '○',
-                1 => '●',
-                2 => '↥',
-                3 => '↧',
-                4 => '↤',
-                5 => '↦',
-                _ => '?'
-            };
-            (character, power, color(108, 208, 108))
-        }, // sticky piston 
+        29 => return piston_render(id, meta & 0b0111, meta & 0b1000 != 0), // sticky piston
         30 => ('Ж', color(255, 255, 255), None),
         31 => ('⍦', color(156, 112, 76), None),
-        33 => {
-            let power = if meta & 0b1000 == 8 {
-                color(255, 58, 58)
-            } else {
-                color(128, 128, 128)
-            };
-            let character = match meta & 0b0111 {
-                0 => '○',
-                1 => '●',
-                2 => '↥',
-                3 => '↧',
-                4 => '↤',
-                5 => '↦',
-                _ => '?'
-            };
-            (character, power, color(108, 108, 108))
-
-        } // piston
+        33 => return piston_render(id, meta & 0b0111, meta & 0b1000 != 0), // piston
         34 => {
             let character = match meta & 0b0111 {
                 0 => '•',
@@ -562,10 +1418,13 @@ fn to_render_block_old(id: u16, meta: u8, ctx: &GlobalContext) -> BlockRender {
             };
             (character, color(188, 152, 98), None)
         } // sticky piston head
-        35 => ('░', color(235, 235, 235), color(205, 205, 205)),
-        37 => ('❀', color(255, 255, 0), color(10, 215, 10)),
-        38 => ('⚘', color(255, 0, 0), color(10, 215, 10)),
-        39 => ('Ⱄ', color(156, 112, 76), color(10, 215, 10)),
+        35 => {
+            let dye = DYES[(meta & 0xF) as usize].1;
+            ('░', Some(dye), Some(shade(dye, 0.87)))
+        }, // wool
+        37 => ('❀', color(255, 255, 0), named(ctx, "grass")),
+        38 => ('⚘', color(255, 0, 0), named(ctx, "grass")),
+        39 => ('Ⱄ', color(156, 112, 76), named(ctx, "grass")),
         42 => ('■', color(214, 215, 216), color(146, 146, 145)), // iron block
         43 => match meta {
             0 => ('─', color(158, 158, 158), color(198, 198, 198)),
@@ -591,7 +1450,7 @@ fn to_render_block_old(id: u16, meta: u8, ctx: &GlobalContext) -> BlockRender {
         47 => ('▤', color(188, 152, 98), None), //bookshelf
         48 => ('▒', color(128, 255, 128), color(108, 108, 108)),
         49 => ('▒', color(13, 0, 23),color(25, 0, 37)),
-        50 => ('༈', color(230, 210, 0), None),
+        50 => (torch_glyph(meta), color(230, 210, 0), None), // torch
         51 => match ctx.tick % 5 / 2 {
             0 => ('‼', color(255, 128, 0), None),
             1 => ('‼', color(255, 0, 0), None),
@@ -599,7 +1458,7 @@ fn to_render_block_old(id: u16, meta: u8, ctx: &GlobalContext) -> BlockRender {
             _ => panic!("huh")
         },
         52 => ('#', color(200, 30, 200), color(180,10, 180)),
-        53 => ('▙', color(188, 152, 98), None), // wooden stair
+        53 => (stair_glyph(meta), color(188, 152, 98), None), // wooden stair
         54 => ('⌺', color(204, 205, 139), color(110,69,45)),
         55 => {
             let power = meta * (200/15) + 50;
@@ -607,9 +1466,16 @@ fn to_render_block_old(id: u16, meta: u8, ctx: &GlobalContext) -> BlockRender {
         },
         56 => ('◆', color(125, 251, 255), color(158, 158, 158)),
         58 => ('#', color(110, 69, 45), color(230, 172, 110)),
-        61 => ('⌸', color(158, 158, 158), color(108, 108, 108)),
+        59 => match meta {
+            0..=2 => (',', crop_color(meta, 7, (220, 190, 50)), None),
+            3..=6 => ('ψ', crop_color(meta, 7, (220, 190, 50)), None),
+            _ => ('Ψ', color(235, 200, 60), None)
+        }, // wheat
+        61 => ('⌸', color(158, 158, 158), color(108, 108, 108)), // furnace
+        62 => ('⌸', color(255, 170, 40), color(130, 80, 50)), // lit furnace
         63 => ('▬', color(188, 152, 98), None), // sign
-        64 => ('+', color(204, 205, 139), None),
+        64 => ('+', color(204, 205, 139), None), // wooden door
+        71 => ('+', color(214, 215, 216), None), // iron door
         65 => ('▤', color(188, 152, 98), None), // ladder
         66 => {
             let character = match meta {
@@ -628,17 +1494,17 @@ fn to_render_block_old(id: u16, meta: u8, ctx: &GlobalContext) -> BlockRender {
             (character, color(214, 215, 216), None)
         }, // rail
         68 => ('▬', color(188, 152, 98), None), //wall sign
-        67 => ('▙', color(108, 108, 108), None),
+        67 => (stair_glyph(meta), color(108, 108, 108), None), // cobblestone stairs
         70 => ('⎽', color(158, 158, 158), None), // pressure plate
         72 => ('⎽', color(188, 152, 98), None), // pressure plate (wood)
         73 => ('&', color(255, 32, 32), color(158, 158, 158)),
-        75 => ('༈', color(80, 10, 10), None),
-        76 => ('༈', color(230, 10, 10), None),
+        75 => (torch_glyph(meta), color(80, 10, 10), None), // redstone torch (off)
+        76 => (torch_glyph(meta), named(ctx, "redstone_torch"), None), // redstone torch (on)
         77 => ('▪', color(158, 158, 158), None ), // stone button
         78 => ('▒', color(235, 235, 255),color(215, 215, 235)),
         79 => ('▒', color(91, 115, 255), color(215, 235, 255)),
         82 => ('▒', color(157, 162, 174), color(132, 138, 150)),
-        83 => ('⊪', color(50, 225, 50), None),
+        83 => ('⊪', named(ctx, "cactus"), None),
         85 => ('┼', color(188, 152, 98), None), // fence
         86 => ('ϖ', color(252, 161, 3), color(201, 110, 0)),
         87 => ('▒', color(97, 7, 7), color(93, 53, 53)), //netherrack
@@ -672,18 +1538,52 @@ fn to_render_block_old(id: u16, meta: u8, ctx: &GlobalContext) -> BlockRender {
         98 => ('▞', color(158, 158, 158), color(138, 138, 138)), //stone bricks
         101 => ('┼', color(146, 146, 145), None),
         102 => ('┼', color(225, 225, 255), None),
+        104 | 105 => match meta {
+            0..=6 => (',', crop_color(meta, 7, (170, 170, 40)), None),
+            _ => ('ɩ', color(190, 150, 40), None)
+        }, // pumpkin and melon stems
         106 => ('⸾', color(12, 223, 12), None),// vine
-        108 => ('▙', color(193, 74, 9), None), // brick stairs
-        109 => ('▙', color(138, 138, 138), None), // stone brick stairs
+        108 => (stair_glyph(meta), color(193, 74, 9), None), // brick stairs
+        109 => (stair_glyph(meta), color(138, 138, 138), None), // stone brick stairs
         112 => ('▞', color(81, 21, 21), color(114, 50, 50)), // nether brick
         113 => ('┼', color(81, 21, 21), None),// nether brick fence
-        114 => ('▙', color(81, 21, 21), None), // nether brick stairs
+        114 => (stair_glyph(meta), color(81, 21, 21), None), // nether brick stairs
+        115 => match meta {
+            0..=2 => (',', crop_color(meta, 3, (200, 40, 40)), None),
+            _ => ('ψ', color(200, 40, 40), None)
+        }, // nether wart
         123 => ('☼', color(235, 205, 0), color(55, 25, 25)), // redstone lamp (unlit)
         124 => ('☼', color(95, 65, 0), color(55, 25, 25)), // redstone lamp (lit)
         125 => ('▄', color(230, 172, 110), None),
         126 => ('█', color(230, 172, 110), color(230, 172, 110)),
+        128 => (stair_glyph(meta), color(204, 205, 139), None), // sandstone stairs
         133 => ('☼', color(100, 237, 146), color(60, 142, 87)), // emerald block
+        134 => (stair_glyph(meta), color(110, 80, 50), None), // spruce stairs
+        135 => (stair_glyph(meta), color(215, 200, 140), None), // birch stairs
+        136 => (stair_glyph(meta), color(170, 120, 85), None), // jungle stairs
+        139 => match meta {
+            1 => ('┼', color(90, 140, 90), None), // mossy cobblestone wall
+            _ => ('┼', color(128, 128, 128), None) // cobblestone wall
+        },
+        141 => match meta {
+            0..=2 => (',', crop_color(meta, 7, (240, 130, 20)), None),
+            3..=6 => ('ψ', crop_color(meta, 7, (240, 130, 20)), None),
+            _ => ('▼', color(240, 130, 20), None)
+        }, // carrots
+        142 => match meta {
+            0..=2 => (',', crop_color(meta, 7, (200, 170, 90)), None),
+            3..=6 => ('ψ', crop_color(meta, 7, (200, 170, 90)), None),
+            _ => ('●', color(200, 170, 90), None)
+        }, // potatoes
         145 => ('σ', color(68, 68, 68), None),
+        146 => ('⌺', color(224, 120, 100), color(110, 69, 45)), // trapped chest
+        156 => (stair_glyph(meta), color(235, 230, 225), None), // quartz stairs
+        159 => {
+            let clay = stained_clay(meta);
+            ('▓', Some(shade(clay, 1.1)), Some(clay))
+        }, // stained clay
+        171 => ('▁', Some(DYES[(meta & 0xF) as usize].1), None), // carpet
+        172 => ('▓', Some(shade(CLAY_COLOR, 1.1)), Some(CLAY_COLOR)), // hardened clay
 
         _ => ('?', None, None)
     };
@@ -697,3 +1597,355 @@ fn to_render_block_old(id: u16, meta: u8, ctx: &GlobalContext) -> BlockRender {
         bg
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::deflate::compress_to_vec_zlib;
+    use std::sync::Arc;
+    use crate::ui::Palette;
+
+    fn context() -> GlobalContext {
+        GlobalContext::init(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("resources")).unwrap()
+    }
+
+    fn meta(x: i32, z: i32, primary: u16, add: u16) -> ChunkMetainfo {
+        ChunkMetainfo { x, z, primary, add }
+    }
+
+    /// Uncompressed data of a column whose only section, the bottom one, is all `id`,
+    /// laid out the way ground up packets with skylight send it
+    fn column_data(id: u8, add: Option<u8>) -> Vec<u8> {
+        let mut data = vec![id; BYTE_CHUNK];
+        data.extend([0u8; HALFBYTE_CHUNK * 3]);
+        if let Some(add) = add {
+            data.extend([add | add << 4; HALFBYTE_CHUNK]);
+        }
+        data.extend([1u8; 256]);
+        data
+    }
+
+    #[test]
+    fn render_cache_skips_lookups_on_a_second_render() {
+        let mut ctx = context();
+        for x in 0..16 {
+            for z in 0..16 {
+                ctx.world.set_block(x, z, 60, 1, 0);
+            }
+        }
+        let render = |ctx: &GlobalContext| {
+            let before = ctx.world.lookups.load(Ordering::Relaxed);
+            for x in 0..16 {
+                for z in 0..16 {
+                    ctx.world.get_terrain_render((x, 62, z), ctx);
+                }
+            }
+            ctx.world.lookups.load(Ordering::Relaxed) - before
+        };
+        let first = render(&ctx);
+        let second = render(&ctx);
+        assert!(first > 0);
+        assert!(second < first, "{} lookups on the second render, {} on the first", second, first);
+    }
+
+    #[test]
+    fn column_lookups_stop_at_the_top() {
+        let mut column = ChunkColumn::new(0, 0);
+        let mut stone = Block::new();
+        stone.id = 1;
+        column.set_block((3, 255, 4), stone);
+        column.set_block((3, 256, 4), stone);
+        column.set_block((3, 257, 4), stone);
+        assert_eq!(column.get_block((3, 255, 4)).id, 1);
+        assert!(column.get_block((3, 256, 4)).is_air());
+        assert!(column.get_block((3, 257, 4)).is_air());
+        // Nothing spilled into the bottom chunk either
+        assert!(column.get_block((3, 0, 4)).is_air());
+        assert!(column.get_block((3, 1, 4)).is_air());
+    }
+
+    #[test]
+    fn negative_coordinates_land_in_their_column() {
+        let mut ctx = context();
+        for (i, x) in [-1, -16, -17].into_iter().enumerate() {
+            ctx.world.set_block(x, x, 64, i as u16 + 1, 0);
+        }
+        assert!(ctx.world.is_loaded(-1, -1));
+        assert!(ctx.world.is_loaded(-2, -2));
+        for (i, x) in [-1, -16, -17].into_iter().enumerate() {
+            assert_eq!(ctx.world.get_block((x, 64, x)).id, i as u16 + 1);
+        }
+        // Truncating toward zero would have put them in column 0 instead
+        assert!(ctx.world.get_block((15, 64, 15)).is_air());
+        assert!(ctx.world.get_block((0, 64, 0)).is_air());
+    }
+
+    #[test]
+    fn truncated_chunk_data_is_an_error() {
+        let data = column_data(1, None);
+        let (columns, error) = parse(&data[..data.len() / 2], &[meta(0, 0, 1, 0)], true, true);
+        assert!(columns.is_empty());
+        assert!(matches!(error, Some(WorldError::Truncated { column: (0, 0), .. })));
+
+        // Columns before the short one are kept
+        let mut data = column_data(1, None);
+        data.extend(&column_data(2, None)[..100]);
+        let (columns, error) = parse(&data, &[meta(0, 0, 1, 0), meta(1, 0, 1, 0)], true, true);
+        assert_eq!(columns.len(), 1);
+        assert_eq!(columns[0].get_block((0, 0, 0)).id, 1);
+        assert!(matches!(error, Some(WorldError::Truncated { column: (1, 0), .. })));
+
+        let (columns, error) = parse(&[], &[meta(0, 0, 1, 0)], true, true);
+        assert!(columns.is_empty());
+        assert!(error.is_some());
+    }
+
+    #[test]
+    fn multi_block_change_loads_its_column() {
+        let mut ctx = context();
+        // Stone at 2 70 3 and wool with metadata 14 at 15 0 0, in column 1 -1
+        let change = MultiBlockChangeData {
+            x: 1,
+            z: -1,
+            record_count: 2,
+            bytes: Box::new([0x23, 70, 0x00, 0x10, 0xF0, 0, 0x02, 0x3E])
+        };
+        ctx.world.set_block_multiple(&change);
+        assert!(ctx.world.is_loaded(1, -1));
+        assert_eq!(ctx.world.get_block((18, 70, -13)).id, 1);
+        let wool = ctx.world.get_block((31, 0, -16));
+        assert_eq!((wool.id, wool.metadata), (35, 14));
+    }
+
+    #[test]
+    fn explosion_offsets_become_air() {
+        let mut ctx = context();
+        for x in -5..1 {
+            for z in 1..6 {
+                for y in 62..67 {
+                    ctx.world.set_block(x, z, y, 1, 0);
+                }
+            }
+        }
+        // Offsets are from the center cast to int like the server does, toward zero
+        let records = BlockOffsetRecords {
+            offsets: vec![(0, 0, 0), (-1, 0, 0), (1, -1, 0), (0, 1, -1), (0, -70, 0)],
+            dx: 0.,
+            dy: 0.,
+            dz: 0.
+        };
+        ctx.world.explode((-2.5, 64.5, 3.7), &records);
+        for pos in [(-2, 64, 3), (-3, 64, 3), (-1, 63, 3), (-2, 65, 2)] {
+            assert!(ctx.world.get_block(pos).is_air(), "{:?} survived", pos);
+        }
+        for pos in [(-4, 64, 3), (-2, 64, 4), (-2, 63, 3), (-3, 65, 2)] {
+            assert_eq!(ctx.world.get_block(pos).id, 1, "{:?} was blown up", pos);
+        }
+        assert_eq!(ctx.world.explosions, vec![(-2, 64, 3)]);
+    }
+
+    /// Applies decoded chunk packets until none are left in flight
+    async fn settle(world: &mut World) {
+        for _ in 0..1000 {
+            world.apply_decoded();
+            if world.decoding.is_empty() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        panic!("chunk data still decoding");
+    }
+
+    /// Two columns the way a 1.5.2 bulk packet with skylight packs them: 2 -3 with sections
+    /// 0 and 2 and add nibbles for 2, then 3 -3 with only section 0
+    fn bulk_payload() -> (Vec<u8>, Vec<ChunkMetainfo>) {
+        let mut data = vec![];
+        data.extend([1u8; BYTE_CHUNK]);
+        data.extend([2u8; BYTE_CHUNK]);
+        data.extend([0x21u8; HALFBYTE_CHUNK]);
+        data.extend([0x43u8; HALFBYTE_CHUNK]);
+        data.extend([0x65u8; HALFBYTE_CHUNK * 2]);
+        data.extend([0x87u8; HALFBYTE_CHUNK * 2]);
+        data.extend([0x11u8; HALFBYTE_CHUNK]);
+        data.extend([4u8; 256]);
+
+        data.extend([3u8; BYTE_CHUNK]);
+        data.extend([0u8; HALFBYTE_CHUNK * 2]);
+        data.extend([0xFFu8; HALFBYTE_CHUNK]);
+        data.extend([7u8; 256]);
+        (data, vec![meta(2, -3, 0b101, 0b100), meta(3, -3, 0b1, 0)])
+    }
+
+    #[tokio::test]
+    async fn two_column_bulk_payload() {
+        let (data, metainfo) = bulk_payload();
+        let (columns, error) = parse(&data, &metainfo, true, true);
+        assert!(error.is_none(), "{:?}", error);
+        assert_eq!(columns.len(), 2);
+
+        let first = &columns[0];
+        assert_eq!((first.x, first.z), (2, -3));
+        let block = first.get_block((0, 0, 0));
+        assert_eq!((block.id, block.metadata, block.light, block.skylit), (1, 1, 5, 7));
+        let block = first.get_block((1, 0, 0));
+        assert_eq!((block.id, block.metadata, block.light, block.skylit), (1, 2, 6, 8));
+        assert!(first.get_block((0, 16, 0)).is_air());
+        let block = first.get_block((1, 32, 0));
+        assert_eq!((block.id, block.metadata), (258, 4));
+        assert!(first.biome.iter().all(|b| *b == 4));
+
+        let second = &columns[1];
+        assert_eq!((second.x, second.z), (3, -3));
+        let block = second.get_block((5, 15, 9));
+        assert_eq!((block.id, block.metadata, block.skylit), (3, 0, 15));
+        assert!(second.get_block((5, 16, 9)).is_air());
+        assert!(second.biome.iter().all(|b| *b == 7));
+
+        // Same through the packet
+        let mut ctx = context();
+        ctx.world.set_chunk_bulk(ChunkDataBulk {
+            column_count: 2,
+            has_skylight: true,
+            compressed: compress_to_vec_zlib(&data, 6).into(),
+            metainfo
+        });
+        settle(&mut ctx.world).await;
+        assert_eq!(ctx.world.get_block((32, 0, -48)).id, 1);
+        assert_eq!(ctx.world.get_block((33, 32, -48)).id, 258);
+        assert_eq!(ctx.world.get_block((63, 15, -33)).id, 3);
+    }
+
+    #[test]
+    fn add_nibble_is_the_high_id_bits() {
+        let (columns, error) = parse(&column_data(5, Some(1)), &[meta(0, 0, 1, 1)], true, true);
+        assert!(error.is_none());
+        assert_eq!(columns[0].get_block((0, 0, 0)).id, 261);
+        assert_eq!(columns[0].get_block((1, 15, 15)).id, 261);
+        // Without the add mask the same ids stay below 256
+        let (columns, _) = parse(&column_data(5, None), &[meta(0, 0, 1, 0)], true, true);
+        assert_eq!(columns[0].get_block((0, 0, 0)).id, 5);
+    }
+
+    #[tokio::test]
+    async fn empty_ground_up_chunk_unloads_the_column() {
+        let mut ctx = context();
+        let chunk = |primary, data: &[u8]| ChunkData {
+            ground_up_continuous: true,
+            compressed: compress_to_vec_zlib(data, 6).into(),
+            metainfo: meta(-1, 2, primary, 0)
+        };
+        ctx.world.set_chunk(chunk(1, &column_data(1, None)));
+        settle(&mut ctx.world).await;
+        assert!(ctx.world.is_loaded(-1, 2));
+        assert_eq!(ctx.world.get_block((-16, 5, 32)).id, 1);
+
+        ctx.world.update = false;
+        ctx.world.set_chunk(chunk(0, &[]));
+        settle(&mut ctx.world).await;
+        assert!(!ctx.world.is_loaded(-1, 2));
+        assert!(ctx.world.get_block((-16, 5, 32)).is_air());
+        assert!(ctx.world.update);
+    }
+
+    #[test]
+    fn is_solid_at_a_loaded_boundary() {
+        let mut ctx = context();
+        for z in 0..16 {
+            ctx.world.set_block(15, z, 64, 1, 0);
+        }
+        assert!(ctx.world.is_solid((15, 64, 0)));
+        assert!(!ctx.world.is_solid((15, 65, 0)));
+        assert!(!ctx.world.is_solid((14, 64, 0)));
+        // Next door on either side isn't loaded
+        assert_eq!(ctx.world.is_solid((16, 64, 0)), UNLOADED_SOLID);
+        assert_eq!(ctx.world.is_solid((15, 64, -1)), UNLOADED_SOLID);
+        assert_eq!(ctx.world.is_solid((-1, 64, 15)), UNLOADED_SOLID);
+    }
+
+    fn glyph(ctx: &GlobalContext, pos: (i32, i32, i32)) -> char {
+        ctx.world.render_block(&ctx.world.get_block(pos), pos, ctx).character
+    }
+
+    #[test]
+    fn fences_join_their_neighbors() {
+        let mut ctx = context();
+        // Isolated post
+        ctx.world.set_block(2, 2, 64, 85, 0);
+        assert_eq!(glyph(&ctx, (2, 64, 2)), '•');
+
+        // Straight north to south
+        for z in 4..7 {
+            ctx.world.set_block(5, z, 64, 85, 0);
+        }
+        assert_eq!(glyph(&ctx, (5, 64, 5)), '│');
+        assert_eq!(glyph(&ctx, (5, 64, 4)), '│');
+
+        // Corner, north and east
+        ctx.world.set_block(10, 10, 64, 113, 0);
+        ctx.world.set_block(10, 9, 64, 113, 0);
+        ctx.world.set_block(11, 10, 64, 113, 0);
+        assert_eq!(glyph(&ctx, (10, 64, 10)), '└');
+
+        // A wall between two solid blocks, a wooden fence doesn't join a nether brick one
+        ctx.world.set_block(2, 12, 64, 139, 0);
+        ctx.world.set_block(1, 12, 64, 1, 0);
+        ctx.world.set_block(3, 12, 64, 1, 0);
+        assert_eq!(glyph(&ctx, (2, 64, 12)), '─');
+        ctx.world.set_block(12, 10, 64, 85, 0);
+        assert_eq!(glyph(&ctx, (12, 64, 10)), '•');
+    }
+
+    #[test]
+    fn door_opens_with_a_block_change() {
+        let mut ctx = context();
+        // Lower half facing east, upper half with the hinge on the left
+        ctx.world.set_block(3, 3, 64, 64, 0);
+        ctx.world.set_block(3, 3, 65, 64, 8);
+        assert_eq!(glyph(&ctx, (3, 64, 3)), '▌');
+        assert_eq!(glyph(&ctx, (3, 65, 3)), '▌');
+        // What the server sends when it's opened
+        ctx.world.set_block(3, 3, 64, 64, 4);
+        assert_eq!(glyph(&ctx, (3, 64, 3)), '▔');
+        assert_eq!(glyph(&ctx, (3, 65, 3)), '▔');
+        // Hinge on the right swings it the other way
+        ctx.world.set_block(3, 3, 65, 64, 9);
+        assert_eq!(glyph(&ctx, (3, 64, 3)), '▁');
+    }
+
+    #[test]
+    fn species_color_leaves_and_saplings() {
+        let ctx = context();
+        let fg = |id, meta| to_render_block_old(id, meta, &ctx).fg;
+        assert_ne!(fg(18, 1), fg(18, 2));
+        assert_ne!(fg(6, 1), fg(6, 3));
+        assert_ne!(fg(18, 0), fg(18, 3));
+        // Decay bits above the species don't change it
+        assert_eq!(fg(18, 0x8 | 2), fg(18, 2));
+        assert_eq!(fg(18, 0x4), fg(18, 0));
+    }
+
+    #[test]
+    fn wool_colors_from_metadata() {
+        let ctx = context();
+        let wool = |meta| to_render_block_old(35, meta, &ctx);
+        assert_eq!(wool(0).fg, (234, 236, 237));
+        assert_eq!(wool(14).fg, (160, 39, 34));
+        assert_eq!(wool(15).fg, (20, 21, 25));
+        assert_eq!(wool(14).bg, Some(shade((160, 39, 34), 0.87)));
+        // Stained clay follows the same dye, carpet is the dye as is
+        assert_ne!(to_render_block_old(159, 0, &ctx).bg, to_render_block_old(159, 15, &ctx).bg);
+        assert_eq!(to_render_block_old(171, 14, &ctx).fg, (160, 39, 34));
+    }
+
+    #[test]
+    fn palette_overrides_stay_on_their_block() {
+        let mut ctx = context();
+        let mut palette = Palette::default();
+        palette.set("leaves", (1, 2, 3)).unwrap();
+        ctx.palette = Arc::new(palette);
+        assert_eq!(to_render_block_old(18, 0, &ctx).fg, (1, 2, 3));
+        // Vines are drawn in the same green but aren't leaves
+        assert_eq!(to_render_block_old(106, 0, &ctx).fg, (12, 223, 12));
+        assert_eq!(to_render_block_old(2, 0, &ctx).fg, (10, 215, 10));
+    }
+}